@@ -0,0 +1,188 @@
+//! Push write throughput: the previous per-item path, a lookup and an `INSERT OR REPLACE`
+//! each committed on its own, against chunked transactions. Run with `cargo bench --bench push`.
+#![allow(clippy::result_large_err)]
+// Without a test harness the shared modules' #[test] functions are dropped, leaving their imports
+#![cfg_attr(test, allow(unused_imports))]
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tokio::runtime::Runtime;
+
+// The server modules, compiled into the benchmark the same way admin-cli shares them.
+#[path = "../src/auth.rs"]
+#[allow(dead_code)]
+mod auth;
+#[path = "../src/bitset.rs"]
+#[allow(dead_code)]
+mod bitset;
+#[path = "../src/catalog.rs"]
+#[allow(dead_code)]
+mod catalog;
+#[path = "../src/database.rs"]
+#[allow(dead_code)]
+mod database;
+#[path = "../src/errors.rs"]
+#[allow(dead_code)]
+mod errors;
+#[path = "../src/history.rs"]
+#[allow(dead_code)]
+mod history;
+#[path = "../src/idempotency.rs"]
+#[allow(dead_code)]
+mod idempotency;
+#[path = "../src/pronunciation.rs"]
+#[allow(dead_code)]
+mod pronunciation;
+#[path = "../src/pull.rs"]
+#[allow(dead_code)]
+mod pull;
+#[path = "../src/push.rs"]
+#[allow(dead_code)]
+mod push;
+#[path = "../src/report.rs"]
+#[allow(dead_code)]
+mod report;
+#[path = "../src/rules.rs"]
+#[allow(dead_code)]
+mod rules;
+#[path = "../src/ruleset.rs"]
+#[allow(dead_code)]
+mod ruleset;
+#[path = "../src/server.rs"]
+#[allow(dead_code)]
+mod server;
+#[path = "../src/staleness.rs"]
+#[allow(dead_code)]
+mod staleness;
+#[path = "../src/sync.rs"]
+#[allow(dead_code)]
+mod sync;
+#[path = "../src/timestamp.rs"]
+#[allow(dead_code)]
+mod timestamp;
+
+use database::Database;
+use push::{apply_feature_override_chunk, StampedItem, PUSH_CHUNK_SIZE};
+use server::proto::FeatureOverride;
+use server::validate_feature_override;
+
+const ITEMS: usize = 2_000;
+const ACTOR: &str = "bench";
+
+/// A fresh file-backed database, so commits pay for the journal like in production.
+/// The file is removed when the iteration's output is dropped.
+struct BenchDatabase {
+    db: Database,
+    path: std::path::PathBuf,
+}
+
+impl Drop for BenchDatabase {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn fresh_database() -> BenchDatabase {
+    let path = std::env::temp_dir().join(format!("push-bench-{}.db", uuid::Uuid::new_v4()));
+    let db = Database::new(&format!("sqlite://{}", path.display())).await.unwrap();
+    db.migrate().await.unwrap();
+    BenchDatabase { db, path }
+}
+
+fn items() -> Vec<FeatureOverride> {
+    (0..ITEMS)
+        .map(|i| FeatureOverride {
+            pronunciation: format!("カード{:05}", i),
+            fixed_bits1: i as i64,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The push loop as it was before chunking: no transaction and no history.
+async fn per_item(db: &Database, items: &[FeatureOverride]) {
+    for item in items {
+        let (created_at, updated_at) = validate_feature_override(item).unwrap();
+        let created_at = timestamp::format(&created_at);
+        let updated_at = timestamp::format(&updated_at);
+
+        sqlx::query!(
+            "SELECT pronunciation FROM card_feature_override WHERE pronunciation = ?",
+            item.pronunciation
+        )
+        .fetch_optional(db.pool())
+        .await
+        .unwrap();
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO card_feature_override
+             (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            item.pronunciation,
+            item.fixed_bits1,
+            item.fixed_bits2,
+            item.fixed_burst_bits,
+            created_at,
+            updated_at,
+            item.note
+        )
+        .execute(db.pool())
+        .await
+        .unwrap();
+    }
+}
+
+async fn chunked(db: &Database, items: &[FeatureOverride]) {
+    let stamped: Vec<StampedItem<FeatureOverride>> = items
+        .iter()
+        .map(|item| {
+            let (created_at, updated_at) = validate_feature_override(item).unwrap();
+            StampedItem {
+                item: item.clone(),
+                created_at: timestamp::format(&created_at),
+                updated_at: timestamp::format(&updated_at),
+            }
+        })
+        .collect();
+    let stamped: Vec<&StampedItem<FeatureOverride>> = stamped.iter().collect();
+
+    for chunk in stamped.chunks(PUSH_CHUNK_SIZE) {
+        let mut tx = db.pool().begin().await.unwrap();
+        apply_feature_override_chunk(&mut tx, chunk, ACTOR).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+}
+
+fn push_feature_overrides(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let items = items();
+
+    let mut group = c.benchmark_group("push_feature_overrides");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(ITEMS as u64));
+
+    group.bench_function("per_item_autocommit", |b| {
+        b.iter_batched(
+            || rt.block_on(fresh_database()),
+            |bench| {
+                rt.block_on(per_item(&bench.db, &items));
+                bench
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("chunked_transaction", |b| {
+        b.iter_batched(
+            || rt.block_on(fresh_database()),
+            |bench| {
+                rt.block_on(chunked(&bench.db, &items));
+                bench
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, push_feature_overrides);
+criterion_main!(benches);
@@ -1,7 +1,17 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // sqlx::migrate! embeds the migrations at compile time
+    println!("cargo:rerun-if-changed=migrations");
+
     tonic_build::configure()
         .build_server(true)
         .build_client(true)
-        .compile_protos(&["proto/admin.proto"], &["proto"])?;
+        .compile_protos(
+            &[
+                "proto/admin.proto",
+                "proto/google/rpc/status.proto",
+                "proto/google/rpc/error_details.proto",
+            ],
+            &["proto"],
+        )?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,9 @@
+-- Rule patterns are identified by (keyword, pattern) when pushed from wx_db
+
+-- Drop duplicates left over from manual inserts, keeping the newest row
+DELETE FROM rule_pattern
+WHERE id NOT IN (
+    SELECT MAX(id) FROM rule_pattern GROUP BY keyword, pattern
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_rule_pattern_keyword_pattern ON rule_pattern(keyword, pattern);
@@ -0,0 +1,28 @@
+-- Change history for card_feature_override
+-- Each row is one version of a pronunciation; old_* is NULL for creates and new_* is NULL for deletes.
+CREATE TABLE IF NOT EXISTS card_feature_override_history (
+    version INTEGER PRIMARY KEY AUTOINCREMENT,
+    pronunciation TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    actor TEXT NOT NULL,  -- client_name of the API key, or the CLI
+    changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+    old_fixed_bits1 INTEGER,
+    old_fixed_bits2 INTEGER,
+    old_fixed_burst_bits INTEGER,
+    old_note TEXT,
+    new_fixed_bits1 INTEGER,
+    new_fixed_bits2 INTEGER,
+    new_fixed_burst_bits INTEGER,
+    new_note TEXT,
+    reverted_from INTEGER REFERENCES card_feature_override_history(version)
+);
+
+CREATE INDEX IF NOT EXISTS idx_feature_override_history_pronunciation
+    ON card_feature_override_history(pronunciation, version);
+
+-- Seed a baseline version for rows that existed before history was recorded
+INSERT INTO card_feature_override_history
+    (pronunciation, action, actor, changed_at, new_fixed_bits1, new_fixed_bits2, new_fixed_burst_bits, new_note)
+SELECT pronunciation, 'create', 'migration', updated_at, fixed_bits1, fixed_bits2, fixed_burst_bits, note
+FROM card_feature_override
+ORDER BY updated_at;
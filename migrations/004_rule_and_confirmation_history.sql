@@ -0,0 +1,52 @@
+-- Change history for rule_pattern and feature_confirmation, mirroring card_feature_override_history
+CREATE TABLE IF NOT EXISTS rule_pattern_history (
+    version INTEGER PRIMARY KEY AUTOINCREMENT,
+    keyword TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    actor TEXT NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+    old_feature_name TEXT,
+    old_is_enabled INTEGER,
+    new_feature_name TEXT,
+    new_is_enabled INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_rule_pattern_history_key
+    ON rule_pattern_history(keyword, pattern, version);
+
+CREATE TABLE IF NOT EXISTS feature_confirmation_history (
+    version INTEGER PRIMARY KEY AUTOINCREMENT,
+    pronunciation TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    actor TEXT NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+    old_confirmed_by TEXT,
+    old_rule_version TEXT,
+    old_feature_bits1 INTEGER,
+    old_feature_bits2 INTEGER,
+    old_burst_bits INTEGER,
+    new_confirmed_by TEXT,
+    new_rule_version TEXT,
+    new_feature_bits1 INTEGER,
+    new_feature_bits2 INTEGER,
+    new_burst_bits INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_feature_confirmation_history_pronunciation
+    ON feature_confirmation_history(pronunciation, version);
+
+-- Seed a baseline version for rows that existed before history was recorded
+INSERT INTO rule_pattern_history
+    (keyword, pattern, action, actor, changed_at, new_feature_name, new_is_enabled)
+SELECT keyword, pattern, 'create', 'migration', updated_at, feature_name, is_enabled
+FROM rule_pattern
+ORDER BY updated_at;
+
+INSERT INTO feature_confirmation_history
+    (pronunciation, action, actor, changed_at,
+     new_confirmed_by, new_rule_version, new_feature_bits1, new_feature_bits2, new_burst_bits)
+SELECT pronunciation, 'create', 'migration', confirmed_at,
+       confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits
+FROM feature_confirmation
+ORDER BY confirmed_at;
@@ -0,0 +1,20 @@
+-- Global ordering of every history version across data types.
+-- The revision is what WatchChanges clients use as their resume cursor.
+CREATE TABLE IF NOT EXISTS change_log (
+    revision INTEGER PRIMARY KEY AUTOINCREMENT,
+    data_type TEXT NOT NULL CHECK (data_type IN ('feature_override', 'rule_pattern', 'confirmed_feature')),
+    history_version INTEGER NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (data_type, history_version)
+);
+
+-- Backfill from the history recorded so far, oldest first
+INSERT INTO change_log (data_type, history_version, changed_at)
+SELECT data_type, version, changed_at FROM (
+    SELECT 'feature_override' AS data_type, version, changed_at FROM card_feature_override_history
+    UNION ALL
+    SELECT 'rule_pattern', version, changed_at FROM rule_pattern_history
+    UNION ALL
+    SELECT 'confirmed_feature', version, changed_at FROM feature_confirmation_history
+)
+ORDER BY julianday(changed_at), version;
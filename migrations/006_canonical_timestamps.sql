@@ -0,0 +1,117 @@
+-- Store every timestamp as UTC RFC3339 with millisecond precision: YYYY-MM-DDTHH:MM:SS.sssZ
+-- The fixed width keeps text comparison and ORDER BY in time order.
+-- Values SQLite cannot parse are left untouched; the server refuses to start until they are fixed.
+
+-- Synced and administrative tables are rebuilt so their defaults produce the canonical form
+
+CREATE TABLE card_feature_override_new (
+    pronunciation TEXT PRIMARY KEY NOT NULL,
+    fixed_bits1 INTEGER NOT NULL,
+    fixed_bits2 INTEGER NOT NULL,
+    fixed_burst_bits INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    note TEXT
+);
+INSERT INTO card_feature_override_new
+SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits,
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', created_at), created_at),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', updated_at), updated_at),
+       note
+FROM card_feature_override;
+DROP TABLE card_feature_override;
+ALTER TABLE card_feature_override_new RENAME TO card_feature_override;
+CREATE INDEX idx_feature_override_updated_at ON card_feature_override(updated_at);
+
+CREATE TABLE feature_confirmation_new (
+    pronunciation TEXT PRIMARY KEY NOT NULL,
+    confirmed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    confirmed_by TEXT NOT NULL,  -- client_id
+    rule_version TEXT,
+    feature_bits1 INTEGER NOT NULL,
+    feature_bits2 INTEGER NOT NULL,
+    burst_bits INTEGER NOT NULL
+);
+INSERT INTO feature_confirmation_new
+SELECT pronunciation,
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', confirmed_at), confirmed_at),
+       confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits
+FROM feature_confirmation;
+DROP TABLE feature_confirmation;
+ALTER TABLE feature_confirmation_new RENAME TO feature_confirmation;
+CREATE INDEX idx_feature_confirmation_confirmed_at ON feature_confirmation(confirmed_at);
+
+CREATE TABLE rule_pattern_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    keyword TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    feature_name TEXT NOT NULL,
+    is_enabled INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+INSERT INTO rule_pattern_new
+SELECT id, keyword, pattern, feature_name, is_enabled,
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', created_at), created_at),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', updated_at), updated_at)
+FROM rule_pattern;
+DROP TABLE rule_pattern;
+ALTER TABLE rule_pattern_new RENAME TO rule_pattern;
+CREATE INDEX idx_rule_pattern_keyword ON rule_pattern(keyword);
+CREATE UNIQUE INDEX idx_rule_pattern_keyword_pattern ON rule_pattern(keyword, pattern);
+CREATE INDEX idx_rule_pattern_updated_at ON rule_pattern(updated_at);
+
+CREATE TABLE sync_metadata_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_id TEXT NOT NULL,
+    sync_type TEXT NOT NULL CHECK (sync_type IN ('push', 'pull')),
+    data_type TEXT NOT NULL CHECK (data_type IN ('feature_override', 'rule_pattern', 'confirmed_feature')),
+    items_count INTEGER NOT NULL,
+    synced_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+INSERT INTO sync_metadata_new
+SELECT id, client_id, sync_type, data_type, items_count,
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', synced_at), synced_at)
+FROM sync_metadata;
+DROP TABLE sync_metadata;
+ALTER TABLE sync_metadata_new RENAME TO sync_metadata;
+CREATE INDEX idx_sync_metadata_client ON sync_metadata(client_id, synced_at);
+
+CREATE TABLE api_keys_new (
+    key_hash TEXT PRIMARY KEY NOT NULL,
+    client_name TEXT NOT NULL UNIQUE,
+    permissions TEXT NOT NULL CHECK (permissions IN ('read', 'read_write')),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    last_used_at TEXT
+);
+INSERT INTO api_keys_new
+SELECT key_hash, client_name, permissions,
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', created_at), created_at),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', last_used_at), last_used_at)
+FROM api_keys;
+DROP TABLE api_keys;
+ALTER TABLE api_keys_new RENAME TO api_keys;
+CREATE INDEX idx_api_keys_client_name ON api_keys(client_name);
+
+-- History tables are converted in place: the server always writes changed_at itself, and
+-- card_feature_override_history cannot be rebuilt while it references its own rows
+
+UPDATE card_feature_override_history
+SET changed_at = strftime('%Y-%m-%dT%H:%M:%fZ', changed_at)
+WHERE strftime('%Y-%m-%dT%H:%M:%fZ', changed_at) IS NOT NULL;
+
+UPDATE rule_pattern_history
+SET changed_at = strftime('%Y-%m-%dT%H:%M:%fZ', changed_at)
+WHERE strftime('%Y-%m-%dT%H:%M:%fZ', changed_at) IS NOT NULL;
+
+UPDATE feature_confirmation_history
+SET changed_at = strftime('%Y-%m-%dT%H:%M:%fZ', changed_at)
+WHERE strftime('%Y-%m-%dT%H:%M:%fZ', changed_at) IS NOT NULL;
+
+UPDATE change_log
+SET changed_at = strftime('%Y-%m-%dT%H:%M:%fZ', changed_at)
+WHERE strftime('%Y-%m-%dT%H:%M:%fZ', changed_at) IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_feature_override_history_changed_at ON card_feature_override_history(changed_at);
+CREATE INDEX IF NOT EXISTS idx_rule_pattern_history_changed_at ON rule_pattern_history(changed_at);
+CREATE INDEX IF NOT EXISTS idx_feature_confirmation_history_changed_at ON feature_confirmation_history(changed_at);
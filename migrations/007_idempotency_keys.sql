@@ -0,0 +1,14 @@
+-- Responses of push and confirm RPCs sent with an idempotency-key, so a retried request
+-- gets the original response instead of being applied twice
+
+CREATE TABLE IF NOT EXISTS idempotency_key (
+    client_name TEXT NOT NULL,
+    rpc TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    request_hash TEXT,  -- SHA-256 of the request messages, set with the response
+    response BLOB,  -- Encoded response; NULL while the first request is still running
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (client_name, rpc, idempotency_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_key_created_at ON idempotency_key(created_at);
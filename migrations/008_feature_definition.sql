@@ -0,0 +1,67 @@
+-- Catalog of named features and the bit each one occupies, so clients can decode
+-- fixed_bits1 / fixed_bits2 / fixed_burst_bits (and the feature_bits* of confirmations)
+-- without wx_db's bit table.
+-- bit_field is 'bits1', 'bits2' or 'burst': the override field fixed_bits1, fixed_bits2 or
+-- fixed_burst_bits, and the confirmation field feature_bits1, feature_bits2 or burst_bits.
+CREATE TABLE IF NOT EXISTS feature_definition (
+    name TEXT PRIMARY KEY NOT NULL,
+    bit_field TEXT NOT NULL CHECK (bit_field IN ('bits1', 'bits2', 'burst')),
+    bit_index INTEGER NOT NULL CHECK (bit_index BETWEEN 0 AND 63),
+    description TEXT,
+    category TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (bit_field, bit_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_feature_definition_updated_at ON feature_definition(updated_at);
+
+-- Change history for feature_definition, mirroring card_feature_override_history
+CREATE TABLE IF NOT EXISTS feature_definition_history (
+    version INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    actor TEXT NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    old_bit_field TEXT,
+    old_bit_index INTEGER,
+    old_description TEXT,
+    old_category TEXT,
+    new_bit_field TEXT,
+    new_bit_index INTEGER,
+    new_description TEXT,
+    new_category TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_feature_definition_history_name
+    ON feature_definition_history(name, version);
+CREATE INDEX IF NOT EXISTS idx_feature_definition_history_changed_at
+    ON feature_definition_history(changed_at);
+
+-- change_log and sync_metadata only accept the data types they were created with; rebuild
+-- them to add the catalog. Revisions are copied as they are, so clients' cursors stay valid.
+CREATE TABLE change_log_new (
+    revision INTEGER PRIMARY KEY AUTOINCREMENT,
+    data_type TEXT NOT NULL CHECK (data_type IN ('feature_override', 'rule_pattern', 'confirmed_feature', 'feature_definition')),
+    history_version INTEGER NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (data_type, history_version)
+);
+INSERT INTO change_log_new (revision, data_type, history_version, changed_at)
+SELECT revision, data_type, history_version, changed_at FROM change_log;
+DROP TABLE change_log;
+ALTER TABLE change_log_new RENAME TO change_log;
+
+CREATE TABLE sync_metadata_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_id TEXT NOT NULL,
+    sync_type TEXT NOT NULL CHECK (sync_type IN ('push', 'pull')),
+    data_type TEXT NOT NULL CHECK (data_type IN ('feature_override', 'rule_pattern', 'confirmed_feature', 'feature_definition')),
+    items_count INTEGER NOT NULL,
+    synced_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+INSERT INTO sync_metadata_new
+SELECT id, client_id, sync_type, data_type, items_count, synced_at FROM sync_metadata;
+DROP TABLE sync_metadata;
+ALTER TABLE sync_metadata_new RENAME TO sync_metadata;
+CREATE INDEX idx_sync_metadata_client ON sync_metadata(client_id, synced_at);
@@ -0,0 +1,32 @@
+-- Feature bitsets wider than the two int64 fields.
+-- Word i of a bitset holds bits 64*i to 64*i+63. Words 0 and 1 stay in fixed_bits1 / fixed_bits2
+-- (feature_bits1 / feature_bits2 for confirmations); extra_bits holds words 2 and up as 8
+-- little-endian bytes each, with trailing zero words trimmed. Empty means no bits beyond 127.
+ALTER TABLE card_feature_override ADD COLUMN extra_bits BLOB NOT NULL DEFAULT X'';
+ALTER TABLE feature_confirmation ADD COLUMN extra_bits BLOB NOT NULL DEFAULT X'';
+
+-- NULL in versions recorded before this migration, which had no bits beyond 127
+ALTER TABLE card_feature_override_history ADD COLUMN old_extra_bits BLOB;
+ALTER TABLE card_feature_override_history ADD COLUMN new_extra_bits BLOB;
+ALTER TABLE feature_confirmation_history ADD COLUMN old_extra_bits BLOB;
+ALTER TABLE feature_confirmation_history ADD COLUMN new_extra_bits BLOB;
+
+-- Definitions for the words in extra_bits: bit_field 'extended', with bit_index counted from
+-- bit 128 (bit 0 of word 2). Bitsets are at most 16 words, leaving 896 extended bits.
+CREATE TABLE feature_definition_new (
+    name TEXT PRIMARY KEY NOT NULL,
+    bit_field TEXT NOT NULL CHECK (bit_field IN ('bits1', 'bits2', 'burst', 'extended')),
+    bit_index INTEGER NOT NULL CHECK (
+        bit_index >= 0 AND bit_index < CASE WHEN bit_field = 'extended' THEN 896 ELSE 64 END
+    ),
+    description TEXT,
+    category TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (bit_field, bit_index)
+);
+INSERT INTO feature_definition_new (name, bit_field, bit_index, description, category, created_at, updated_at)
+SELECT name, bit_field, bit_index, description, category, created_at, updated_at FROM feature_definition;
+DROP TABLE feature_definition;
+ALTER TABLE feature_definition_new RENAME TO feature_definition;
+CREATE INDEX idx_feature_definition_updated_at ON feature_definition(updated_at);
@@ -0,0 +1,7 @@
+-- Card texts by pronunciation key: the corpus rule patterns are tested against.
+-- Loaded with `admin-cli card-text import`; not synced.
+CREATE TABLE card_text (
+    pronunciation TEXT PRIMARY KEY NOT NULL,
+    text TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
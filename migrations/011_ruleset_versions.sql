@@ -0,0 +1,19 @@
+-- Ruleset versions: content hashes of the enabled rule patterns and the feature definitions.
+-- A version is recorded in the transaction that changes the rules, so every ruleset clients
+-- could have seen is known.
+CREATE TABLE ruleset_version (
+    version TEXT PRIMARY KEY NOT NULL,  -- Lowercase hex SHA-256
+    revision INTEGER NOT NULL,  -- change_log revision of the last rule or definition change when first seen
+    first_seen_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Names clients give to ruleset versions. A name always refers to the same version.
+CREATE TABLE ruleset_release (
+    name TEXT PRIMARY KEY NOT NULL,
+    version TEXT NOT NULL REFERENCES ruleset_version(version),
+    note TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE INDEX idx_ruleset_release_version ON ruleset_release(version);
@@ -0,0 +1,30 @@
+-- Confirmations whose bits differ from what the current ruleset computes for the card text, or
+-- from what the rules alone detect when a stored override supplies the computed bits.
+-- A row describes the confirmation as of confirmed_at; once the card is confirmed again the
+-- row no longer applies, and the next check replaces or removes it.
+CREATE TABLE stale_confirmation (
+    pronunciation TEXT PRIMARY KEY NOT NULL,
+    confirmed_at TEXT NOT NULL,  -- feature_confirmation.confirmed_at that was checked
+    ruleset_version TEXT NOT NULL,  -- Ruleset the bits were computed with
+    computed_bits1 INTEGER NOT NULL,
+    computed_bits2 INTEGER NOT NULL,
+    computed_burst_bits INTEGER NOT NULL,
+    computed_extra_bits BLOB NOT NULL DEFAULT X'',
+    override_applied INTEGER NOT NULL,  -- 1 when a stored override supplied the computed bits
+    detected_bits1 INTEGER NOT NULL,  -- What the rules alone detect, override or not
+    detected_bits2 INTEGER NOT NULL,
+    detected_burst_bits INTEGER NOT NULL,
+    detected_extra_bits BLOB NOT NULL DEFAULT X'',
+    detected_at TEXT NOT NULL  -- First check that found this confirmation stale
+);
+
+-- The latest stale confirmation check (a single row)
+CREATE TABLE stale_check (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    ruleset_version TEXT NOT NULL,
+    confirmations_checked INTEGER NOT NULL,
+    confirmations_stale INTEGER NOT NULL,
+    confirmations_overridden INTEGER NOT NULL,  -- Checked confirmations whose card has an override
+    confirmations_without_text INTEGER NOT NULL,
+    checked_at TEXT NOT NULL
+);
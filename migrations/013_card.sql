@@ -0,0 +1,83 @@
+-- Card master data by pronunciation key: the card the overrides and confirmations describe.
+-- card_text and burst_text are what rule patterns are evaluated against.
+-- name, card_number and set_code are NULL only for cards migrated from card_text, whose text
+-- is all that is known. Pushed cards always carry all three.
+CREATE TABLE card (
+    pronunciation TEXT PRIMARY KEY NOT NULL,
+    name TEXT,  -- NULL when unknown
+    card_number TEXT,  -- NULL when unknown
+    set_code TEXT,  -- NULL when unknown
+    card_text TEXT NOT NULL,
+    burst_text TEXT,  -- Life burst text; NULL for cards without one
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE INDEX idx_card_updated_at ON card(updated_at);
+
+-- Change history for card, mirroring card_feature_override_history
+CREATE TABLE card_history (
+    version INTEGER PRIMARY KEY AUTOINCREMENT,
+    pronunciation TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    actor TEXT NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    old_name TEXT,
+    old_card_number TEXT,
+    old_set_code TEXT,
+    old_card_text TEXT,
+    old_burst_text TEXT,
+    new_name TEXT,
+    new_card_number TEXT,
+    new_set_code TEXT,
+    new_card_text TEXT,
+    new_burst_text TEXT
+);
+
+CREATE INDEX idx_card_history_pronunciation ON card_history(pronunciation, version);
+CREATE INDEX idx_card_history_changed_at ON card_history(changed_at);
+
+-- change_log and sync_metadata only accept the data types they were created with; rebuild
+-- them to add cards. Revisions are copied as they are, so clients' cursors stay valid.
+CREATE TABLE change_log_new (
+    revision INTEGER PRIMARY KEY AUTOINCREMENT,
+    data_type TEXT NOT NULL CHECK (data_type IN ('feature_override', 'rule_pattern', 'confirmed_feature', 'feature_definition', 'card')),
+    history_version INTEGER NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (data_type, history_version)
+);
+INSERT INTO change_log_new (revision, data_type, history_version, changed_at)
+SELECT revision, data_type, history_version, changed_at FROM change_log;
+DROP TABLE change_log;
+ALTER TABLE change_log_new RENAME TO change_log;
+
+CREATE TABLE sync_metadata_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_id TEXT NOT NULL,
+    sync_type TEXT NOT NULL CHECK (sync_type IN ('push', 'pull')),
+    data_type TEXT NOT NULL CHECK (data_type IN ('feature_override', 'rule_pattern', 'confirmed_feature', 'feature_definition', 'card')),
+    items_count INTEGER NOT NULL,
+    synced_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+INSERT INTO sync_metadata_new
+SELECT id, client_id, sync_type, data_type, items_count, synced_at FROM sync_metadata;
+DROP TABLE sync_metadata;
+ALTER TABLE sync_metadata_new RENAME TO sync_metadata;
+CREATE INDEX idx_sync_metadata_client ON sync_metadata(client_id, synced_at);
+
+-- Texts imported into card_text become cards. Only the text is known, so name, card_number
+-- and set_code stay NULL until the card is pushed.
+INSERT INTO card (pronunciation, card_text, created_at, updated_at)
+SELECT pronunciation, text, updated_at, updated_at FROM card_text;
+
+-- Seed a baseline version for them, and place it in the global revision order
+INSERT INTO card_history
+    (pronunciation, action, actor, changed_at, new_name, new_card_number, new_set_code, new_card_text)
+SELECT pronunciation, 'create', 'migration', updated_at, name, card_number, set_code, card_text
+FROM card
+ORDER BY updated_at, pronunciation;
+
+INSERT INTO change_log (data_type, history_version, changed_at)
+SELECT 'card', version, changed_at FROM card_history ORDER BY version;
+
+DROP TABLE card_text;
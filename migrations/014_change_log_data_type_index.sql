@@ -0,0 +1,3 @@
+-- Pulls page through each table in change_log revision order; this lets them seek to a
+-- page's revision within one data type instead of sorting the whole log on every page.
+CREATE INDEX IF NOT EXISTS idx_change_log_data_type ON change_log(data_type, revision);
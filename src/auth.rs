@@ -26,13 +26,15 @@ impl AuthService {
     pub async fn generate_api_key(&self, client_name: &str, permissions: &str) -> Result<String> {
         let raw_key = format!("ADM_{}", uuid::Uuid::new_v4().to_string().replace('-', ""));
         let key_hash = self.hash_key(&raw_key)?;
+        let created_at = crate::timestamp::now();
 
         sqlx::query!(
             "INSERT INTO api_keys (key_hash, client_name, permissions, created_at) 
-             VALUES (?, ?, ?, datetime('now'))",
+             VALUES (?, ?, ?, ?)",
             key_hash,
             client_name,
-            permissions
+            permissions,
+            created_at
         )
         .execute(&self.pool)
         .await?;
@@ -61,8 +63,10 @@ impl AuthService {
     }
 
     async fn update_last_used(&self, key_hash: &str) -> Result<()> {
+        let now = crate::timestamp::now();
         sqlx::query!(
-            "UPDATE api_keys SET last_used_at = datetime('now') WHERE key_hash = ?",
+            "UPDATE api_keys SET last_used_at = ? WHERE key_hash = ?",
+            now,
             key_hash
         )
         .execute(&self.pool)
@@ -105,8 +109,16 @@ pub async fn authenticate_request<T>(
     auth_service: &AuthService,
 ) -> Result<ApiKey, Status> {
     let api_key = extract_api_key(request)?;
-    
-    match auth_service.verify_api_key(&api_key).await {
+    authenticate_api_key(&api_key, auth_service).await
+}
+
+/// Verifies an already extracted API key. Streaming handlers use this because
+/// `Request<Streaming<_>>` cannot be held across an await point.
+pub async fn authenticate_api_key(
+    api_key: &str,
+    auth_service: &AuthService,
+) -> Result<ApiKey, Status> {
+    match auth_service.verify_api_key(api_key).await {
         Ok(Some(api_key_info)) => {
             info!("Authenticated request from client: {}", api_key_info.client_name);
             Ok(api_key_info)
@@ -1,16 +1,57 @@
+#![allow(clippy::result_large_err)]
+
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+
+use sqlx::{SqliteConnection, SqlitePool};
 use tracing::{error, info};
 
 #[path = "../auth.rs"]
+#[allow(dead_code)]
 mod auth;
 
+#[path = "../bitset.rs"]
+#[allow(dead_code)]
+mod bitset;
+
+#[path = "../catalog.rs"]
+#[allow(dead_code)]
+mod catalog;
+
 #[path = "../database.rs"]
+#[allow(dead_code)]
 mod database;
 
+#[path = "../history.rs"]
+#[allow(dead_code)]
+mod history;
+
+#[path = "../pronunciation.rs"]
+#[allow(dead_code)]
+mod pronunciation;
+
+#[path = "../report.rs"]
+#[allow(dead_code)]
+mod report;
+
+#[path = "../rules.rs"]
+#[allow(dead_code)]
+mod rules;
+
+#[path = "../timestamp.rs"]
+#[allow(dead_code)]
+mod timestamp;
+
 use auth::{ApiKey, AuthService};
+use catalog::{describe_undefined_bits, BitField, FeatureCatalog};
 use database::Database;
+use history::{CardValues, ConfirmationValues, OverrideValues};
+use report::DiscrepancyKind;
+use rules::RuleSet;
+
+/// Actor recorded in history for changes made from this tool
+const CLI_ACTOR: &str = "admin-cli";
 
 #[derive(Parser)]
 #[command(author, version, about = "Admin Backend CLI - API Key Management Tool", long_about = None)]
@@ -55,6 +96,114 @@ enum Commands {
         #[arg(short, long)]
         client: String,
     },
+
+    /// Inspect and revert feature override history
+    Override {
+        #[command(subcommand)]
+        command: OverrideCommands,
+    },
+
+    /// Maintain pronunciation keys
+    Pronunciation {
+        #[command(subcommand)]
+        command: PronunciationCommands,
+    },
+
+    /// Check stored data against the feature definition catalog
+    Features {
+        #[command(subcommand)]
+        command: FeatureCommands,
+    },
+
+    /// Maintain the card master data rule patterns are tested against
+    Card {
+        #[command(subcommand)]
+        command: CardCommands,
+    },
+
+    /// Check the enabled rule patterns against the stored card texts
+    Rules {
+        #[command(subcommand)]
+        command: RuleCommands,
+    },
+
+    /// Compare stored data
+    Report {
+        #[command(subcommand)]
+        command: ReportCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportCommands {
+    /// List pronunciations whose override and confirmation bits differ, overrides with no
+    /// confirmation and confirmations with no override
+    Discrepancies,
+}
+
+#[derive(Subcommand)]
+enum RuleCommands {
+    /// Report rules that never match, rules that only match where another rule does, and
+    /// texts where rules for different features match the same span
+    Analyze {
+        /// Card texts to analyze, in pronunciation order (default: all)
+        #[arg(short, long)]
+        limit: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CardCommands {
+    /// Load cards from a JSON Lines file of {"pronunciation", "name", "card_number", "set_code",
+    /// "card_text", "burst_text"} objects; burst_text may be omitted. Existing cards with the same
+    /// pronunciation key are replaced, and every change is recorded in history.
+    Import {
+        /// File to read
+        #[arg(short, long)]
+        file: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum FeatureCommands {
+    /// List overrides and confirmations with bits that have no feature definition
+    Scan,
+}
+
+#[derive(Subcommand)]
+enum PronunciationCommands {
+    /// Rename keys stored before normalization and merge rows that normalize to the same key.
+    /// Without --apply only the plan is printed.
+    Dedupe {
+        /// Write the changes (asks for confirmation)
+        #[arg(long)]
+        apply: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum OverrideCommands {
+    /// Show the change history of a pronunciation
+    History {
+        /// Pronunciation to show history for
+        #[arg(short, long)]
+        pronunciation: String,
+
+        /// Number of versions to show (newest first)
+        #[arg(short, long, default_value_t = 20)]
+        limit: i64,
+    },
+
+    /// Revert a pronunciation to a historical version
+    Revert {
+        /// Pronunciation to revert
+        #[arg(short, long)]
+        pronunciation: String,
+
+        /// History version to restore
+        #[arg(short, long)]
+        version: i64,
+    },
 }
 
 #[tokio::main]
@@ -84,14 +233,47 @@ async fn main() -> Result<()> {
             generate_key(&auth_service, &client, &permissions).await?;
         }
         Commands::List { active } => {
-            list_keys(&pool, active).await?;
+            list_keys(pool, active).await?;
         }
         Commands::Revoke { client } => {
-            revoke_key(&pool, &client).await?;
+            revoke_key(pool, &client).await?;
         }
         Commands::Info { client } => {
-            show_key_info(&pool, &client).await?;
+            show_key_info(pool, &client).await?;
         }
+        Commands::Override { command } => match command {
+            OverrideCommands::History { pronunciation, limit } => {
+                show_override_history(pool, &pronunciation, limit).await?;
+            }
+            OverrideCommands::Revert { pronunciation, version } => {
+                revert_override(pool, &pronunciation, version).await?;
+            }
+        },
+        Commands::Pronunciation { command } => match command {
+            PronunciationCommands::Dedupe { apply } => {
+                dedupe_pronunciations(pool, apply).await?;
+            }
+        },
+        Commands::Features { command } => match command {
+            FeatureCommands::Scan => {
+                scan_feature_bits(pool).await?;
+            }
+        },
+        Commands::Card { command } => match command {
+            CardCommands::Import { file } => {
+                import_cards(pool, &file).await?;
+            }
+        },
+        Commands::Rules { command } => match command {
+            RuleCommands::Analyze { limit } => {
+                analyze_rules(pool, limit).await?;
+            }
+        },
+        Commands::Report { command } => match command {
+            ReportCommands::Discrepancies => {
+                report_discrepancies(pool).await?;
+            }
+        },
     }
 
     Ok(())
@@ -252,3 +434,699 @@ async fn show_key_info(pool: &SqlitePool, client_name: &str) -> Result<()> {
     Ok(())
 }
 
+async fn show_override_history(pool: &SqlitePool, pronunciation: &str, limit: i64) -> Result<()> {
+    let entries = history::override_history(pool, pronunciation, limit).await?;
+
+    if entries.is_empty() {
+        println!("No history found for '{}'.", pronunciation);
+        return Ok(());
+    }
+
+    println!("\n{:<8} {:<8} {:<20} {:<34} Change", "Version", "Action", "Actor", "Changed At");
+    println!("{}", "-".repeat(110));
+
+    for entry in entries {
+        let reverted = entry
+            .reverted_from
+            .map(|v| format!(" (revert to {})", v))
+            .unwrap_or_default();
+        println!(
+            "{:<8} {:<8} {:<20} {:<34} {} -> {}{}",
+            entry.version,
+            entry.action,
+            entry.actor,
+            entry.changed_at,
+            format_override_values(entry.old_values.as_ref()),
+            format_override_values(entry.new_values.as_ref()),
+            reverted
+        );
+    }
+
+    Ok(())
+}
+
+async fn revert_override(pool: &SqlitePool, pronunciation: &str, version: i64) -> Result<()> {
+    println!("Revert '{}' to history version {}?", pronunciation, version);
+    println!("The revert is recorded as a new version. Type 'yes' to confirm:");
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+
+    if input.trim().to_lowercase() != "yes" {
+        println!("Revert cancelled.");
+        return Ok(());
+    }
+
+    match history::revert_override(pool, pronunciation, version, CLI_ACTOR).await? {
+        Some(change) => {
+            println!("Reverted '{}' to version {} (new version {}).", pronunciation, version, change.version);
+        }
+        None => {
+            println!("'{}' already matches version {}. Nothing to do.", pronunciation, version);
+        }
+    }
+
+    Ok(())
+}
+
+/// A stored row of a pronunciation-keyed table
+struct KeyedRow<V> {
+    pronunciation: String,
+    /// updated_at for overrides, confirmed_at for confirmations
+    written_at: String,
+    values: V,
+    created_at: String,
+}
+
+/// Rows whose pronunciations normalize to `key`. The most recently written row survives
+/// under `key`; the others are deleted.
+struct KeyMerge<V> {
+    key: String,
+    rows: Vec<KeyedRow<V>>,
+}
+
+impl<V> KeyMerge<V> {
+    fn survivor(&self) -> &KeyedRow<V> {
+        // On equal timestamps the row already stored under the key wins
+        self.rows
+            .iter()
+            .max_by(|a, b| {
+                a.written_at
+                    .cmp(&b.written_at)
+                    .then_with(|| (a.pronunciation == self.key).cmp(&(b.pronunciation == self.key)))
+            })
+            .expect("a merge has at least one row")
+    }
+
+    fn existing(&self) -> Option<&KeyedRow<V>> {
+        self.rows.iter().find(|row| row.pronunciation == self.key)
+    }
+}
+
+/// Groups rows by normalized key, keeping only groups that need a change. Keys that cannot
+/// be normalized at all are returned separately; they must be fixed by hand.
+fn plan_merges<V>(rows: Vec<KeyedRow<V>>) -> (Vec<KeyMerge<V>>, Vec<(String, String)>) {
+    let mut groups: BTreeMap<String, Vec<KeyedRow<V>>> = BTreeMap::new();
+    let mut invalid = Vec::new();
+
+    for row in rows {
+        match pronunciation::normalize(&row.pronunciation) {
+            Ok(key) => groups.entry(key).or_default().push(row),
+            Err(reason) => invalid.push((row.pronunciation, reason)),
+        }
+    }
+
+    let merges = groups
+        .into_iter()
+        .filter(|(key, rows)| rows.len() > 1 || rows[0].pronunciation != *key)
+        .map(|(key, rows)| KeyMerge { key, rows })
+        .collect();
+    (merges, invalid)
+}
+
+async fn dedupe_pronunciations(pool: &SqlitePool, apply: bool) -> Result<()> {
+    let overrides = sqlx::query!(
+        "SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, note, extra_bits, created_at, updated_at
+         FROM card_feature_override"
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| KeyedRow {
+        pronunciation: row.pronunciation,
+        written_at: row.updated_at.clone(),
+        values: OverrideValues {
+            fixed_bits1: row.fixed_bits1,
+            fixed_bits2: row.fixed_bits2,
+            fixed_burst_bits: row.fixed_burst_bits,
+            note: row.note,
+            extra_bits: row.extra_bits,
+        },
+        created_at: row.created_at,
+    })
+    .collect();
+
+    let confirmations = sqlx::query!(
+        "SELECT pronunciation, confirmed_at, confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits,
+                extra_bits
+         FROM feature_confirmation"
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| KeyedRow {
+        pronunciation: row.pronunciation,
+        written_at: row.confirmed_at.clone(),
+        values: ConfirmationValues {
+            confirmed_by: row.confirmed_by,
+            rule_version: row.rule_version,
+            feature_bits1: row.feature_bits1,
+            feature_bits2: row.feature_bits2,
+            burst_bits: row.burst_bits,
+            extra_bits: row.extra_bits,
+        },
+        created_at: row.confirmed_at,
+    })
+    .collect();
+
+    let cards = sqlx::query!(
+        "SELECT pronunciation, name, card_number, set_code, card_text, burst_text, created_at, updated_at FROM card"
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| KeyedRow {
+        pronunciation: row.pronunciation,
+        written_at: row.updated_at.clone(),
+        values: CardValues {
+            name: row.name,
+            card_number: row.card_number,
+            set_code: row.set_code,
+            card_text: row.card_text,
+            burst_text: row.burst_text,
+        },
+        created_at: row.created_at,
+    })
+    .collect();
+
+    let (override_merges, invalid_overrides) = plan_merges(overrides);
+    let (confirmation_merges, invalid_confirmations) = plan_merges(confirmations);
+    let (card_merges, invalid_cards) = plan_merges(cards);
+
+    for (table, invalid) in [
+        ("card_feature_override", &invalid_overrides),
+        ("feature_confirmation", &invalid_confirmations),
+        ("card", &invalid_cards),
+    ] {
+        for (pronunciation, reason) in invalid {
+            println!("{}: {:?} cannot be normalized ({}); fix or delete it by hand", table, pronunciation, reason);
+        }
+    }
+
+    if override_merges.is_empty() && confirmation_merges.is_empty() && card_merges.is_empty() {
+        println!("No pronunciation keys to normalize.");
+        return Ok(());
+    }
+
+    for (table, merges) in [
+        ("card_feature_override", print_plan(&override_merges)),
+        ("feature_confirmation", print_plan(&confirmation_merges)),
+        ("card", print_plan(&card_merges)),
+    ] {
+        if !merges.is_empty() {
+            println!("\n{}:", table);
+            for line in merges {
+                println!("  {}", line);
+            }
+        }
+    }
+
+    if !apply {
+        println!("\nDry run. Re-run with --apply to write these changes.");
+        return Ok(());
+    }
+
+    println!("\nApply these changes? Each is recorded in history. Type 'yes' to confirm:");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+
+    if input.trim().to_lowercase() != "yes" {
+        println!("Dedupe cancelled.");
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+    for merge in &override_merges {
+        merge_override(&mut tx, merge).await?;
+    }
+    for merge in &confirmation_merges {
+        merge_confirmation(&mut tx, merge).await?;
+    }
+    for merge in &card_merges {
+        merge_card(&mut tx, merge).await?;
+    }
+    tx.commit().await?;
+
+    info!(
+        "Normalized {} override keys, {} confirmation keys and {} card keys",
+        override_merges.len(),
+        confirmation_merges.len(),
+        card_merges.len()
+    );
+    println!(
+        "Normalized {} override keys, {} confirmation keys and {} card keys.",
+        override_merges.len(),
+        confirmation_merges.len(),
+        card_merges.len()
+    );
+
+    Ok(())
+}
+
+fn print_plan<V>(merges: &[KeyMerge<V>]) -> Vec<String> {
+    merges
+        .iter()
+        .map(|merge| {
+            let survivor = merge.survivor();
+            let dropped: Vec<String> = merge
+                .rows
+                .iter()
+                .filter(|row| !std::ptr::eq(*row, survivor))
+                .map(|row| format!("{:?}", row.pronunciation))
+                .collect();
+            let mut line = format!("{:?} <- keep {:?}", merge.key, survivor.pronunciation);
+            if !dropped.is_empty() {
+                line.push_str(&format!(", drop {}", dropped.join(", ")));
+            }
+            line
+        })
+        .collect()
+}
+
+async fn merge_override(conn: &mut SqliteConnection, merge: &KeyMerge<OverrideValues>) -> Result<()> {
+    for row in merge.rows.iter().filter(|row| row.pronunciation != merge.key) {
+        sqlx::query!("DELETE FROM card_feature_override WHERE pronunciation = ?", row.pronunciation)
+            .execute(&mut *conn)
+            .await?;
+        history::record_override_change(conn, &row.pronunciation, CLI_ACTOR, Some(&row.values), None, None).await?;
+    }
+
+    let survivor = merge.survivor();
+    if survivor.pronunciation == merge.key {
+        return Ok(());
+    }
+    let values = &survivor.values;
+    sqlx::query!(
+        "INSERT INTO card_feature_override
+         (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note, extra_bits)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(pronunciation) DO UPDATE SET
+             fixed_bits1 = excluded.fixed_bits1,
+             fixed_bits2 = excluded.fixed_bits2,
+             fixed_burst_bits = excluded.fixed_burst_bits,
+             updated_at = excluded.updated_at,
+             note = excluded.note,
+             extra_bits = excluded.extra_bits",
+        merge.key,
+        values.fixed_bits1,
+        values.fixed_bits2,
+        values.fixed_burst_bits,
+        survivor.created_at,
+        survivor.written_at,
+        values.note,
+        values.extra_bits
+    )
+    .execute(&mut *conn)
+    .await?;
+    let old = merge.existing().map(|row| &row.values);
+    history::record_override_change(conn, &merge.key, CLI_ACTOR, old, Some(values), None).await?;
+
+    Ok(())
+}
+
+async fn merge_confirmation(conn: &mut SqliteConnection, merge: &KeyMerge<ConfirmationValues>) -> Result<()> {
+    for row in merge.rows.iter().filter(|row| row.pronunciation != merge.key) {
+        sqlx::query!("DELETE FROM feature_confirmation WHERE pronunciation = ?", row.pronunciation)
+            .execute(&mut *conn)
+            .await?;
+        history::record_confirmation_change(conn, &row.pronunciation, CLI_ACTOR, Some(&row.values), None).await?;
+    }
+
+    let survivor = merge.survivor();
+    if survivor.pronunciation == merge.key {
+        return Ok(());
+    }
+    let values = &survivor.values;
+    sqlx::query!(
+        "INSERT INTO feature_confirmation
+         (pronunciation, confirmed_at, confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits, extra_bits)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(pronunciation) DO UPDATE SET
+             confirmed_at = excluded.confirmed_at,
+             confirmed_by = excluded.confirmed_by,
+             rule_version = excluded.rule_version,
+             feature_bits1 = excluded.feature_bits1,
+             feature_bits2 = excluded.feature_bits2,
+             burst_bits = excluded.burst_bits,
+             extra_bits = excluded.extra_bits",
+        merge.key,
+        survivor.written_at,
+        values.confirmed_by,
+        values.rule_version,
+        values.feature_bits1,
+        values.feature_bits2,
+        values.burst_bits,
+        values.extra_bits
+    )
+    .execute(&mut *conn)
+    .await?;
+    let old = merge.existing().map(|row| &row.values);
+    history::record_confirmation_change(conn, &merge.key, CLI_ACTOR, old, Some(values)).await?;
+
+    Ok(())
+}
+
+async fn merge_card(conn: &mut SqliteConnection, merge: &KeyMerge<CardValues>) -> Result<()> {
+    for row in merge.rows.iter().filter(|row| row.pronunciation != merge.key) {
+        sqlx::query!("DELETE FROM card WHERE pronunciation = ?", row.pronunciation)
+            .execute(&mut *conn)
+            .await?;
+        history::record_card_change(conn, &row.pronunciation, CLI_ACTOR, Some(&row.values), None).await?;
+    }
+
+    let survivor = merge.survivor();
+    if survivor.pronunciation == merge.key {
+        return Ok(());
+    }
+    let values = &survivor.values;
+    sqlx::query!(
+        "INSERT INTO card (pronunciation, name, card_number, set_code, card_text, burst_text, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(pronunciation) DO UPDATE SET
+             name = excluded.name,
+             card_number = excluded.card_number,
+             set_code = excluded.set_code,
+             card_text = excluded.card_text,
+             burst_text = excluded.burst_text,
+             updated_at = excluded.updated_at",
+        merge.key,
+        values.name,
+        values.card_number,
+        values.set_code,
+        values.card_text,
+        values.burst_text,
+        survivor.created_at,
+        survivor.written_at
+    )
+    .execute(&mut *conn)
+    .await?;
+    let old = merge.existing().map(|row| &row.values);
+    history::record_card_change(conn, &merge.key, CLI_ACTOR, old, Some(values)).await?;
+
+    Ok(())
+}
+
+async fn scan_feature_bits(pool: &SqlitePool) -> Result<()> {
+    let catalog = FeatureCatalog::load(pool).await?;
+    if catalog.is_empty() {
+        println!("No feature definitions registered; nothing to check against.");
+        return Ok(());
+    }
+
+    let overrides = sqlx::query!(
+        "SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, extra_bits
+         FROM card_feature_override ORDER BY pronunciation"
+    )
+    .fetch_all(pool)
+    .await?;
+    let confirmations = sqlx::query!(
+        "SELECT pronunciation, feature_bits1, feature_bits2, burst_bits, extra_bits
+         FROM feature_confirmation ORDER BY pronunciation"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut findings = Vec::new();
+    for row in &overrides {
+        let fields = [
+            ("fixed_bits1", BitField::Bits1, vec![row.fixed_bits1 as u64]),
+            ("fixed_bits2", BitField::Bits2, vec![row.fixed_bits2 as u64]),
+            ("fixed_burst_bits", BitField::Burst, vec![row.fixed_burst_bits as u64]),
+            ("extra_bits", BitField::Extended, bitset::decode_extra(&row.extra_bits)),
+        ];
+        for (name, field, value) in fields {
+            let bits = catalog.undefined_bits(field, &value);
+            if !bits.is_empty() {
+                findings.push(("override", &row.pronunciation, name, describe_undefined_bits(&bits)));
+            }
+        }
+    }
+    for row in &confirmations {
+        let fields = [
+            ("feature_bits1", BitField::Bits1, vec![row.feature_bits1 as u64]),
+            ("feature_bits2", BitField::Bits2, vec![row.feature_bits2 as u64]),
+            ("burst_bits", BitField::Burst, vec![row.burst_bits as u64]),
+            ("extra_bits", BitField::Extended, bitset::decode_extra(&row.extra_bits)),
+        ];
+        for (name, field, value) in fields {
+            let bits = catalog.undefined_bits(field, &value);
+            if !bits.is_empty() {
+                findings.push(("confirmation", &row.pronunciation, name, describe_undefined_bits(&bits)));
+            }
+        }
+    }
+
+    println!(
+        "Scanned {} overrides and {} confirmations.",
+        overrides.len(),
+        confirmations.len()
+    );
+    if findings.is_empty() {
+        println!("All set bits have a feature definition.");
+        return Ok(());
+    }
+
+    println!("\n{:<14} {:<30} {:<18} Problem", "Table", "Pronunciation", "Field");
+    println!("{}", "-".repeat(100));
+    for (table, pronunciation, field, problem) in &findings {
+        println!("{:<14} {:<30} {:<18} {}", table, pronunciation, field, problem);
+    }
+    println!("\n{} fields with undefined bits.", findings.len());
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+struct CardLine {
+    pronunciation: String,
+    name: String,
+    card_number: String,
+    set_code: String,
+    card_text: String,
+    burst_text: Option<String>,
+}
+
+impl CardLine {
+    /// The first field that would be rejected by PushCards, with the reason
+    fn problem(&self) -> Option<String> {
+        for (field, value) in [("name", &self.name), ("card_number", &self.card_number), ("set_code", &self.set_code)] {
+            if value.trim().is_empty() {
+                return Some(format!("{} must not be empty", field));
+            }
+            if value.trim() != value {
+                return Some(format!("{} must not start or end with whitespace", field));
+            }
+        }
+        if self.card_text.trim().is_empty() {
+            return Some("card_text must not be empty".to_string());
+        }
+        if self.burst_text.as_ref().is_some_and(|text| text.trim().is_empty()) {
+            return Some("burst_text must not be empty; omit it for cards without one".to_string());
+        }
+        None
+    }
+}
+
+async fn import_cards(pool: &SqlitePool, file: &std::path::Path) -> Result<()> {
+    let contents = std::fs::read_to_string(file)?;
+
+    // Check every line before writing anything, so a bad file leaves the table untouched
+    let mut cards = BTreeMap::new();
+    let mut problems = Vec::new();
+    for (number, line) in contents.lines().enumerate().map(|(index, line)| (index + 1, line)) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed: CardLine = match serde_json::from_str(line) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                problems.push(format!("line {}: {}", number, e));
+                continue;
+            }
+        };
+        let key = match pronunciation::normalize(&parsed.pronunciation) {
+            Ok(key) => key,
+            Err(reason) => {
+                problems.push(format!("line {}: pronunciation {:?} {}", number, parsed.pronunciation, reason));
+                continue;
+            }
+        };
+        if let Some(problem) = parsed.problem() {
+            problems.push(format!("line {}: {}", number, problem));
+            continue;
+        }
+        let values = CardValues {
+            name: Some(parsed.name),
+            card_number: Some(parsed.card_number),
+            set_code: Some(parsed.set_code),
+            card_text: parsed.card_text,
+            burst_text: parsed.burst_text,
+        };
+        if let Some(first) = cards.insert(key.clone(), (number, values)).map(|(first, _)| first) {
+            problems.push(format!("line {}: pronunciation {:?} already given on line {}", number, key, first));
+        }
+    }
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        anyhow::bail!("{} problems in {}; nothing imported", problems.len(), file.display());
+    }
+
+    let (mut created, mut updated) = (0, 0);
+    let mut tx = pool.begin().await?;
+    for (pronunciation, (_, values)) in &cards {
+        let old = history::current_card(&mut tx, pronunciation).await?;
+        if old.as_ref() == Some(values) {
+            continue;
+        }
+        sqlx::query!(
+            "INSERT INTO card (pronunciation, name, card_number, set_code, card_text, burst_text, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+             ON CONFLICT(pronunciation) DO UPDATE SET
+                 name = excluded.name,
+                 card_number = excluded.card_number,
+                 set_code = excluded.set_code,
+                 card_text = excluded.card_text,
+                 burst_text = excluded.burst_text,
+                 updated_at = excluded.updated_at",
+            pronunciation,
+            values.name,
+            values.card_number,
+            values.set_code,
+            values.card_text,
+            values.burst_text
+        )
+        .execute(&mut *tx)
+        .await?;
+        history::record_card_change(&mut tx, pronunciation, CLI_ACTOR, old.as_ref(), Some(values)).await?;
+        if old.is_some() {
+            updated += 1;
+        } else {
+            created += 1;
+        }
+    }
+    tx.commit().await?;
+
+    info!("Imported {} new and {} changed cards from {}", created, updated, file.display());
+    println!(
+        "Read {} cards: {} created, {} updated, {} unchanged.",
+        cards.len(),
+        created,
+        updated,
+        cards.len() - created - updated
+    );
+
+    Ok(())
+}
+
+async fn analyze_rules(pool: &SqlitePool, limit: Option<i64>) -> Result<()> {
+    let rule_set = RuleSet::load(&mut *pool.acquire().await?).await?;
+    let texts = rules::load_card_texts(pool, limit).await?;
+    if texts.is_empty() {
+        println!("No cards stored; import some with `card import` or push them first.");
+        return Ok(());
+    }
+
+    let analysis = rule_set.analyze(&texts);
+    let rule = |rule: &rules::Rule| format!("{} / {} -> {}", rule.keyword, rule.pattern, rule.feature_name);
+
+    println!("Analyzed {} rules over {} card texts.", rule_set.rules.len(), texts.len());
+    for invalid in &rule_set.invalid {
+        println!("Skipped {} / {}: {}", invalid.keyword, invalid.pattern, invalid.error);
+    }
+
+    println!("\nNever matched ({}):", analysis.never_matched.len());
+    for never in &analysis.never_matched {
+        println!("  {}", rule(never));
+    }
+
+    println!("\nAlways matched together ({}):", analysis.co_matches.len());
+    for co_match in &analysis.co_matches {
+        println!(
+            "  {}\n    {} {} ({} texts)",
+            rule(co_match.rule),
+            if co_match.identical { "same texts as" } else { "only where" },
+            rule(co_match.other),
+            co_match.texts
+        );
+    }
+
+    println!("\nConflicting features ({}):", analysis.conflicts.len());
+    for conflict in &analysis.conflicts {
+        println!("  {}", texts[conflict.text].pronunciation);
+        for found in [&conflict.first, &conflict.second] {
+            println!(
+                "    {} [{}..{}] {:?}",
+                rule(found.rule),
+                found.span.start,
+                found.span.end,
+                found.span.text
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn report_discrepancies(pool: &SqlitePool) -> Result<()> {
+    let discrepancies = report::discrepancies(pool).await?;
+    if discrepancies.is_empty() {
+        println!("Every override has a confirmation with the same bits.");
+        return Ok(());
+    }
+
+    for (kind, title) in [
+        (DiscrepancyKind::BitsDiffer, "Confirmed bits differ from the override"),
+        (DiscrepancyKind::OverrideOnly, "Overrides with no confirmation"),
+        (DiscrepancyKind::ConfirmationOnly, "Confirmations with no override"),
+    ] {
+        let listed: Vec<_> = discrepancies.iter().filter(|d| d.kind == kind).collect();
+        println!("\n{} ({}):", title, listed.len());
+        for discrepancy in listed {
+            println!("  {}", discrepancy.pronunciation);
+            for difference in &discrepancy.differences {
+                let side = match (difference.in_override, difference.in_confirmation) {
+                    (true, false) => "override only",
+                    _ => "confirmation only",
+                };
+                println!(
+                    "    {:<8} bit {:<3} {:<30} {}",
+                    difference.field.as_str(),
+                    difference.index,
+                    difference.feature_name.as_deref().unwrap_or("(undefined)"),
+                    side
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn format_override_values(values: Option<&OverrideValues>) -> String {
+    match values {
+        Some(v) => format!(
+            "[{}, {}, {}{}{}]",
+            v.fixed_bits1,
+            v.fixed_bits2,
+            v.fixed_burst_bits,
+            format_extra_bits(&v.extra_bits),
+            v.note.as_deref().map(|n| format!(", \"{}\"", n)).unwrap_or_default()
+        ),
+        None => "(none)".to_string(),
+    }
+}
+
+/// Extended bits as ", +{...}" with bit numbers counted from 128, or nothing when there are none.
+fn format_extra_bits(extra_bits: &[u8]) -> String {
+    let bits: Vec<String> = bitset::set_bits(&bitset::decode_extra(extra_bits))
+        .map(|index| (128 + index).to_string())
+        .collect();
+    if bits.is_empty() {
+        String::new()
+    } else {
+        format!(", +{{{}}}", bits.join(", "))
+    }
+}
@@ -0,0 +1,105 @@
+//! Feature bitsets wider than the bits1 / bits2 pair.
+//!
+//! Word i of a bitset holds bits 64*i to 64*i+63. Words 0 and 1 are stored in the bits1 / bits2
+//! columns and the remaining words in `extra_bits`, 8 little-endian bytes per word, with
+//! trailing zero words trimmed so that equal bitsets are stored identically.
+
+/// Widest bitset accepted, in 64-bit words
+pub const MAX_WORDS: usize = 16;
+
+/// Bits beyond bits1 / bits2, addressed by the 'extended' bit field
+pub const EXTENDED_BITS: usize = (MAX_WORDS - 2) * 64;
+
+/// Encodes words 2 and up of `words` for the extra_bits column.
+pub fn encode_extra(words: &[u64]) -> Vec<u8> {
+    let extra = words.get(2..).unwrap_or_default();
+    let len = extra.iter().rposition(|word| *word != 0).map_or(0, |last| last + 1);
+    extra[..len].iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// Decodes an extra_bits value into words 2 and up. A trailing partial word is zero-padded.
+pub fn decode_extra(extra: &[u8]) -> Vec<u64> {
+    extra
+        .chunks(8)
+        .map(|chunk| {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        })
+        .collect()
+}
+
+/// The whole bitset: bits1, bits2, then the words of extra_bits.
+pub fn words(bits1: i64, bits2: i64, extra: &[u8]) -> Vec<u64> {
+    let mut words = vec![bits1 as u64, bits2 as u64];
+    words.extend(decode_extra(extra));
+    words
+}
+
+pub fn is_set(words: &[u64], index: usize) -> bool {
+    words.get(index / 64).is_some_and(|word| (word >> (index % 64)) & 1 == 1)
+}
+
+/// True when both bitsets have the same bits set, whatever their lengths.
+pub fn equal(a: &[u64], b: &[u64]) -> bool {
+    (0..a.len().max(b.len())).all(|i| a.get(i).copied().unwrap_or(0) == b.get(i).copied().unwrap_or(0))
+}
+
+/// Indexes of the set bits, in ascending order.
+pub fn set_bits(words: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    (0..words.len() * 64).filter(move |index| is_set(words, *index))
+}
+
+/// Sets bit `index`, growing `words` as needed.
+pub fn set(words: &mut Vec<u64>, index: usize) {
+    if words.len() <= index / 64 {
+        words.resize(index / 64 + 1, 0);
+    }
+    words[index / 64] |= 1 << (index % 64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_extra_trims_trailing_zero_words() {
+        assert!(encode_extra(&[1, 2]).is_empty());
+        assert!(encode_extra(&[1, 2, 0, 0]).is_empty());
+        assert_eq!(encode_extra(&[0, 0, 0, 5, 0]), [vec![0; 8], 5u64.to_le_bytes().to_vec()].concat());
+        assert_eq!(encode_extra(&[0, 0, 0, 5]), encode_extra(&[0, 0, 0, 5, 0, 0]));
+    }
+
+    #[test]
+    fn decode_extra_pads_a_partial_word() {
+        assert_eq!(decode_extra(&[]), Vec::<u64>::new());
+        assert_eq!(decode_extra(&[1, 2, 3]), vec![0x03_02_01]);
+        let mut bytes = u64::MAX.to_le_bytes().to_vec();
+        bytes.push(0x80);
+        assert_eq!(decode_extra(&bytes), vec![u64::MAX, 0x80]);
+    }
+
+    #[test]
+    fn words_round_trip_through_the_columns() {
+        let bitset = vec![1, u64::MAX, 0, 1 << 63];
+        let stored = words(bitset[0] as i64, bitset[1] as i64, &encode_extra(&bitset));
+        assert_eq!(stored, bitset);
+        assert!(set_bits(&stored).eq([0, 64, 65].into_iter().chain(66..128).chain([255])));
+    }
+
+    #[test]
+    fn equal_ignores_trailing_zero_words() {
+        assert!(equal(&[1, 0, 0], &[1]));
+        assert!(equal(&[], &[0, 0]));
+        assert!(!equal(&[1, 0, 1], &[1]));
+        assert!(!equal(&[1], &[2]));
+    }
+
+    #[test]
+    fn set_grows_the_bitset() {
+        let mut bitset = vec![1];
+        set(&mut bitset, 130);
+        assert_eq!(bitset, vec![1, 0, 4]);
+        assert!(is_set(&bitset, 130) && !is_set(&bitset, 131) && !is_set(&bitset, 1000));
+    }
+}
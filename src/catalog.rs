@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use sqlx::SqliteExecutor;
+
+use crate::bitset;
+
+/// Default for FEATURE_BIT_POLICY
+const DEFAULT_BIT_POLICY: BitPolicy = BitPolicy::Warn;
+
+/// The bit fields a feature can occupy, in the order decoded names are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BitField {
+    Bits1,
+    Bits2,
+    Burst,
+    /// Bits from 128 up of a wide bitset; bit_index 0 is bit 128
+    Extended,
+}
+
+impl BitField {
+    pub const ALL: [BitField; 4] = [BitField::Bits1, BitField::Bits2, BitField::Burst, BitField::Extended];
+
+    /// Value of feature_definition.bit_field
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BitField::Bits1 => "bits1",
+            BitField::Bits2 => "bits2",
+            BitField::Burst => "burst",
+            BitField::Extended => "extended",
+        }
+    }
+
+    /// Number of bit indexes a definition in this field can use
+    pub fn width(self) -> usize {
+        match self {
+            BitField::Bits1 | BitField::Bits2 | BitField::Burst => 64,
+            BitField::Extended => bitset::EXTENDED_BITS,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        BitField::ALL.into_iter().find(|field| field.as_str() == value)
+    }
+}
+
+/// What happens to a write whose bits have no feature definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitPolicy {
+    /// Reject the item
+    Reject,
+    /// Accept the item and report a warning with it
+    Warn,
+    /// Accept the item without checking
+    Off,
+}
+
+impl BitPolicy {
+    /// Reads FEATURE_BIT_POLICY: "reject", "warn" or "off". Unset or unknown values use the default.
+    pub fn from_env() -> Self {
+        std::env::var("FEATURE_BIT_POLICY")
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or(DEFAULT_BIT_POLICY)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "reject" => Some(BitPolicy::Reject),
+            "warn" => Some(BitPolicy::Warn),
+            "off" => Some(BitPolicy::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the registered features by bit, for decoding bit fields.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureCatalog {
+    names: BTreeMap<(BitField, usize), String>,
+}
+
+impl FeatureCatalog {
+    pub async fn load<'e>(executor: impl SqliteExecutor<'e>) -> Result<Self> {
+        let rows = sqlx::query!("SELECT name, bit_field, bit_index FROM feature_definition")
+            .fetch_all(executor)
+            .await?;
+
+        let names = rows
+            .into_iter()
+            .filter_map(|row| {
+                let field = BitField::parse(&row.bit_field)?;
+                let index = usize::try_from(row.bit_index).ok()?;
+                Some(((field, index), row.name))
+            })
+            .collect();
+
+        Ok(Self { names })
+    }
+
+    /// A catalog of the given definitions, without a database
+    #[cfg(test)]
+    pub fn from_names<'a>(names: impl IntoIterator<Item = (BitField, usize, &'a str)>) -> Self {
+        Self {
+            names: names
+                .into_iter()
+                .map(|(field, index, name)| ((field, index), name.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Names of the set bits that have a definition, by field and then bit index.
+    /// Set bits without a definition are skipped. `words` is the whole bitset, see [`bitset`].
+    pub fn decode(&self, words: &[u64], burst: i64) -> Vec<String> {
+        self.names
+            .iter()
+            .filter(|((field, index), _)| match field {
+                BitField::Bits1 => bitset::is_set(words, *index),
+                BitField::Bits2 => bitset::is_set(words, 64 + index),
+                BitField::Burst => bitset::is_set(&[burst as u64], *index),
+                BitField::Extended => bitset::is_set(words, 128 + index),
+            })
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// The feature defined on a bit.
+    pub fn name(&self, field: BitField, index: usize) -> Option<&str> {
+        self.names.get(&(field, index)).map(String::as_str)
+    }
+
+    /// The bit a feature is defined on.
+    pub fn bit_of(&self, name: &str) -> Option<(BitField, usize)> {
+        self.names.iter().find(|(_, defined)| *defined == name).map(|(bit, _)| *bit)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Indexes of the bits set in `words` that no feature in `field` is defined for. `words`
+    /// holds the field's own bits: one word for bits1, bits2 and burst, words 2 and up of the
+    /// bitset for extended.
+    pub fn undefined_bits(&self, field: BitField, words: &[u64]) -> Vec<usize> {
+        bitset::set_bits(words)
+            .filter(|index| !self.names.contains_key(&(field, *index)))
+            .collect()
+    }
+}
+
+/// Describes undefined bits for an error or warning, e.g. "bits 3, 5 have no feature definition".
+pub fn describe_undefined_bits(bits: &[usize]) -> String {
+    let list = bits.iter().map(usize::to_string).collect::<Vec<_>>().join(", ");
+    if bits.len() == 1 {
+        format!("bit {} has no feature definition", list)
+    } else {
+        format!("bits {} have no feature definition", list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn decode_lists_defined_set_bits_by_field_then_index() {
+        let catalog = FeatureCatalog::from_names([
+            (BitField::Extended, 0, "拡張"),
+            (BitField::Bits1, 5, "破壊"),
+            (BitField::Bits1, 0, "ドロー"),
+            (BitField::Bits2, 0, "サーチ"),
+            (BitField::Burst, 1, "バースト"),
+        ]);
+
+        // bits1 0 and 5, bits2 0, extended 0 (bit 128), and undefined bits1 3 / bits2 7
+        let words = [0b10_1001, 0b1000_0001, 1];
+        assert_eq!(catalog.decode(&words, 0b10), ["ドロー", "破壊", "サーチ", "バースト", "拡張"]);
+        assert_eq!(catalog.decode(&[1], 0b01), ["ドロー"]);
+        assert!(catalog.decode(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn lookups_by_bit_and_by_name() {
+        let catalog = FeatureCatalog::from_names([(BitField::Bits2, 3, "サーチ")]);
+        assert_eq!(catalog.name(BitField::Bits2, 3), Some("サーチ"));
+        assert_eq!(catalog.name(BitField::Bits1, 3), None);
+        assert_eq!(catalog.bit_of("サーチ"), Some((BitField::Bits2, 3)));
+        assert_eq!(catalog.bit_of("ドロー"), None);
+    }
+
+    #[test]
+    fn undefined_bits_are_reported_per_field() {
+        let catalog = FeatureCatalog::from_names([(BitField::Bits1, 0, "ドロー")]);
+        assert_eq!(catalog.undefined_bits(BitField::Bits1, &[0b1011]), [1, 3]);
+        assert_eq!(catalog.undefined_bits(BitField::Bits2, &[0b1]), [0]);
+        assert_eq!(describe_undefined_bits(&[1, 3]), "bits 1, 3 have no feature definition");
+        assert_eq!(describe_undefined_bits(&[0]), "bit 0 has no feature definition");
+    }
+
+    #[tokio::test]
+    async fn load_reads_the_definitions() {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO feature_definition (name, bit_field, bit_index)
+             VALUES ('ドロー', 'bits1', 0), ('バースト', 'burst', 2), ('拡張', 'extended', 70)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let catalog = FeatureCatalog::load(&pool).await.unwrap();
+        assert_eq!(catalog.name(BitField::Burst, 2), Some("バースト"));
+        assert_eq!(catalog.decode(&[1, 0, 0, 1 << 6], 0b100), ["ドロー", "バースト", "拡張"]);
+    }
+}
@@ -1,6 +1,36 @@
 use anyhow::Result;
 use sqlx::{SqlitePool, migrate::MigrateDatabase};
-use tracing::info;
+use tracing::{error, info, warn};
+
+use crate::pronunciation;
+
+/// Every timestamp column. All of them must hold the form written by `timestamp::format`.
+const TIMESTAMP_COLUMNS: [(&str, &str); 24] = [
+    ("card_feature_override", "created_at"),
+    ("card_feature_override", "updated_at"),
+    ("feature_confirmation", "confirmed_at"),
+    ("rule_pattern", "created_at"),
+    ("rule_pattern", "updated_at"),
+    ("feature_definition", "created_at"),
+    ("feature_definition", "updated_at"),
+    ("sync_metadata", "synced_at"),
+    ("api_keys", "created_at"),
+    ("api_keys", "last_used_at"),
+    ("card_feature_override_history", "changed_at"),
+    ("rule_pattern_history", "changed_at"),
+    ("feature_confirmation_history", "changed_at"),
+    ("feature_definition_history", "changed_at"),
+    ("change_log", "changed_at"),
+    ("idempotency_key", "created_at"),
+    ("card", "created_at"),
+    ("card", "updated_at"),
+    ("card_history", "changed_at"),
+    ("ruleset_version", "first_seen_at"),
+    ("ruleset_release", "created_at"),
+    ("stale_confirmation", "detected_at"),
+    ("stale_confirmation", "confirmed_at"),
+    ("stale_check", "checked_at"),
+];
 
 #[derive(Clone)]
 pub struct Database {
@@ -19,13 +49,128 @@ impl Database {
         Ok(Self { pool })
     }
 
+    /// Wraps an already migrated pool, such as a single-connection in-memory one
+    #[cfg(test)]
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
     pub async fn migrate(&self) -> Result<()> {
         info!("Running database migrations...");
         sqlx::migrate!("./migrations").run(&self.pool).await?;
         Ok(())
     }
 
+    /// Fails if any timestamp is not in the canonical form, since pulls compare them as text.
+    /// Migration 006 converts everything it can parse; anything left needs fixing by hand.
+    pub async fn check_timestamps(&self) -> Result<()> {
+        let mut inconsistent = 0;
+
+        for (table, column) in TIMESTAMP_COLUMNS {
+            // Table and column names come from the constant above, never from input
+            let (count, example): (i64, Option<String>) = sqlx::query_as(&format!(
+                "SELECT COUNT(*), MIN({column}) FROM {table}
+                 WHERE {column} IS NOT strftime('%Y-%m-%dT%H:%M:%fZ', {column})"
+            ))
+            .fetch_one(&self.pool)
+            .await?;
+
+            if count > 0 {
+                error!(
+                    "{}.{} has {} timestamps not in YYYY-MM-DDTHH:MM:SS.sssZ form (e.g. {:?})",
+                    table,
+                    column,
+                    count,
+                    example.unwrap_or_default()
+                );
+                inconsistent += count;
+            }
+        }
+
+        if inconsistent > 0 {
+            anyhow::bail!("Refusing to start: {} stored timestamps are not in canonical form", inconsistent);
+        }
+        Ok(())
+    }
+
+    /// Warns about pronunciation keys stored before normalization was enforced. Such rows can
+    /// no longer be addressed through the API until `admin-cli pronunciation dedupe` fixes them.
+    pub async fn check_pronunciations(&self) -> Result<()> {
+        for table in ["card_feature_override", "feature_confirmation", "card"] {
+            let keys: Vec<String> = sqlx::query_scalar(&format!("SELECT pronunciation FROM {table}"))
+                .fetch_all(&self.pool)
+                .await?;
+            let unnormalized: Vec<&String> = keys
+                .iter()
+                .filter(|key| pronunciation::normalize(key).as_ref() != Ok(*key))
+                .collect();
+
+            if let Some(example) = unnormalized.first() {
+                warn!(
+                    "{} has {} pronunciations not in normalized form (e.g. {:?}); run `admin-cli pronunciation dedupe`",
+                    table,
+                    unnormalized.len(),
+                    example
+                );
+            }
+        }
+        Ok(())
+    }
+
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::borrow::Cow;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn migration_converts_old_timestamps_and_check_refuses_the_rest() {
+        let pool = test_pool().await;
+        let mut before_canonical = sqlx::migrate!("./migrations");
+        before_canonical.migrations = Cow::Owned(
+            before_canonical.migrations.iter().filter(|migration| migration.version < 6).cloned().collect(),
+        );
+        before_canonical.run(&pool).await.unwrap();
+
+        // Written by the old datetime('now') defaults and by chrono's RFC3339 with an offset
+        sqlx::query(
+            "INSERT INTO card_feature_override
+             (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at)
+             VALUES ('ア', 0, 0, 0, '2024-01-01 12:00:00', '2024-01-02T03:04:05.6+00:00')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        let (created_at, updated_at): (String, String) =
+            sqlx::query_as("SELECT created_at, updated_at FROM card_feature_override")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(created_at, "2024-01-01T12:00:00.000Z");
+        assert_eq!(updated_at, "2024-01-02T03:04:05.600Z");
+
+        let db = Database::from_pool(pool);
+        db.check_timestamps().await.unwrap();
+
+        sqlx::query("UPDATE card_feature_override SET updated_at = 'yesterday'")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        assert!(db.check_timestamps().await.is_err());
+    }
+}
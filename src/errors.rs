@@ -0,0 +1,73 @@
+use prost::Message;
+use tonic::{Code, Status};
+
+use crate::server::proto::PushErrorCode;
+
+pub mod rpc {
+    tonic::include_proto!("google.rpc");
+}
+
+const BAD_REQUEST_TYPE_URL: &str = "type.googleapis.com/google.rpc.BadRequest";
+
+/// A value in a request that failed validation. Carried inside `anyhow::Error` so callers
+/// can tell bad input apart from database failures.
+#[derive(Debug)]
+pub(crate) struct ValidationError {
+    pub field: String,
+    pub description: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.description)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub(crate) fn validation_error(field: impl Into<String>, description: impl Into<String>) -> anyhow::Error {
+    ValidationError {
+        field: field.into(),
+        description: description.into(),
+    }
+    .into()
+}
+
+/// INVALID_ARGUMENT with a google.rpc.BadRequest naming the offending field.
+pub(crate) fn invalid_argument(field: impl Into<String>, description: impl Into<String>) -> Status {
+    let field = field.into();
+    let description = description.into();
+    let message = format!("{}: {}", field, description);
+
+    let bad_request = rpc::BadRequest {
+        field_violations: vec![rpc::bad_request::FieldViolation { field, description }],
+    };
+    let status = rpc::Status {
+        code: Code::InvalidArgument as i32,
+        message: message.clone(),
+        details: vec![prost_types::Any {
+            type_url: BAD_REQUEST_TYPE_URL.to_string(),
+            value: bad_request.encode_to_vec(),
+        }],
+    };
+
+    Status::with_details(Code::InvalidArgument, message, status.encode_to_vec().into())
+}
+
+/// Maps a handler error to a status: validation failures become INVALID_ARGUMENT with
+/// details, anything else is reported as a database error.
+pub(crate) fn to_status(error: anyhow::Error) -> Status {
+    match error.downcast::<ValidationError>() {
+        Ok(e) => invalid_argument(e.field, e.description),
+        Err(e) => Status::internal(format!("Database error: {}", e)),
+    }
+}
+
+/// Classifies why an item could not be applied: the error code, the offending field and
+/// whether retrying the same item can succeed.
+pub(crate) fn push_error(error: &anyhow::Error) -> (PushErrorCode, Option<String>, bool) {
+    match error.downcast_ref::<ValidationError>() {
+        Some(e) => (PushErrorCode::InvalidField, Some(e.field.clone()), false),
+        None => (PushErrorCode::Internal, None, true),
+    }
+}
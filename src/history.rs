@@ -0,0 +1,1140 @@
+use anyhow::Result;
+use sqlx::{SqliteConnection, SqliteExecutor, SqlitePool};
+use tracing::info;
+
+use crate::timestamp;
+
+/// Override columns tracked by the change history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverrideValues {
+    pub fixed_bits1: i64,
+    pub fixed_bits2: i64,
+    pub fixed_burst_bits: i64,
+    pub note: Option<String>,
+    /// Words 2 and up of the bitset, see [`crate::bitset`]
+    pub extra_bits: Vec<u8>,
+}
+
+/// Rule pattern columns tracked by the change history. Rules are keyed by (keyword, pattern).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleValues {
+    pub feature_name: String,
+    pub is_enabled: bool,
+}
+
+/// Confirmation columns tracked by the change history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfirmationValues {
+    pub confirmed_by: String,
+    pub rule_version: Option<String>,
+    pub feature_bits1: i64,
+    pub feature_bits2: i64,
+    pub burst_bits: i64,
+    /// Words 2 and up of the bitset, see [`crate::bitset`]
+    pub extra_bits: Vec<u8>,
+}
+
+/// Feature definition columns tracked by the change history. Definitions are keyed by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionValues {
+    pub bit_field: String,
+    pub bit_index: i64,
+    pub description: Option<String>,
+    pub category: Option<String>,
+}
+
+/// Card columns tracked by the change history. Cards are keyed by pronunciation.
+/// name, card_number and set_code are unknown for cards migrated from bare card texts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardValues {
+    pub name: Option<String>,
+    pub card_number: Option<String>,
+    pub set_code: Option<String>,
+    pub card_text: String,
+    pub burst_text: Option<String>,
+}
+
+/// A row reconstructed from history as it was at a point in time.
+#[derive(Debug, Clone)]
+pub struct Snapshot<K, V> {
+    pub key: K,
+    pub values: V,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// One entry of change_log: a history version placed in the global revision order.
+#[derive(Debug, Clone)]
+pub struct ChangeRecord {
+    pub revision: i64,
+    pub version: i64,
+    pub action: String,
+    pub actor: String,
+    pub changed_at: String,
+    pub payload: ChangePayload,
+}
+
+#[derive(Debug, Clone)]
+pub enum ChangePayload {
+    FeatureOverride {
+        pronunciation: String,
+        old: Option<OverrideValues>,
+        new: Option<OverrideValues>,
+    },
+    RulePattern {
+        keyword: String,
+        pattern: String,
+        old: Option<RuleValues>,
+        new: Option<RuleValues>,
+    },
+    ConfirmedFeature {
+        pronunciation: String,
+        old: Option<ConfirmationValues>,
+        new: Option<ConfirmationValues>,
+    },
+    FeatureDefinition {
+        name: String,
+        old: Option<DefinitionValues>,
+        new: Option<DefinitionValues>,
+    },
+    Card {
+        pronunciation: String,
+        old: Option<CardValues>,
+        new: Option<CardValues>,
+    },
+}
+
+impl ChangePayload {
+    /// The data_type name used by change_log and sync_metadata.
+    pub fn data_type(&self) -> &'static str {
+        match self {
+            ChangePayload::FeatureOverride { .. } => "feature_override",
+            ChangePayload::RulePattern { .. } => "rule_pattern",
+            ChangePayload::ConfirmedFeature { .. } => "confirmed_feature",
+            ChangePayload::FeatureDefinition { .. } => "feature_definition",
+            ChangePayload::Card { .. } => "card",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OverrideHistoryEntry {
+    pub version: i64,
+    pub pronunciation: String,
+    pub action: String,
+    pub actor: String,
+    pub changed_at: String,
+    pub old_values: Option<OverrideValues>,
+    pub new_values: Option<OverrideValues>,
+    pub reverted_from: Option<i64>,
+}
+
+pub async fn current_override(
+    conn: &mut SqliteConnection,
+    pronunciation: &str,
+) -> Result<Option<OverrideValues>> {
+    let row = sqlx::query_as!(
+        OverrideValues,
+        "SELECT fixed_bits1, fixed_bits2, fixed_burst_bits, note, extra_bits
+         FROM card_feature_override WHERE pronunciation = ?",
+        pronunciation
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row)
+}
+
+/// Appends a history version for a change from `old` to `new`.
+/// Returns `None` without writing when the values did not change.
+pub async fn record_override_change(
+    conn: &mut SqliteConnection,
+    pronunciation: &str,
+    actor: &str,
+    old: Option<&OverrideValues>,
+    new: Option<&OverrideValues>,
+    reverted_from: Option<i64>,
+) -> Result<Option<ChangeRecord>> {
+    let Some(action) = change_action(old, new) else {
+        return Ok(None);
+    };
+    let changed_at = timestamp::now();
+    let (old_bits1, old_bits2, old_burst_bits, old_note, old_extra_bits) = split_values(old);
+    let (new_bits1, new_bits2, new_burst_bits, new_note, new_extra_bits) = split_values(new);
+
+    let version = sqlx::query!(
+        "INSERT INTO card_feature_override_history
+         (pronunciation, action, actor, changed_at,
+          old_fixed_bits1, old_fixed_bits2, old_fixed_burst_bits, old_note, old_extra_bits,
+          new_fixed_bits1, new_fixed_bits2, new_fixed_burst_bits, new_note, new_extra_bits,
+          reverted_from)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        pronunciation,
+        action,
+        actor,
+        changed_at,
+        old_bits1,
+        old_bits2,
+        old_burst_bits,
+        old_note,
+        old_extra_bits,
+        new_bits1,
+        new_bits2,
+        new_burst_bits,
+        new_note,
+        new_extra_bits,
+        reverted_from
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let payload = ChangePayload::FeatureOverride {
+        pronunciation: pronunciation.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+    log_change(conn, version, action, actor, changed_at, payload).await.map(Some)
+}
+
+/// Returns the history of a pronunciation, newest version first.
+pub async fn override_history(
+    pool: &SqlitePool,
+    pronunciation: &str,
+    limit: i64,
+) -> Result<Vec<OverrideHistoryEntry>> {
+    let rows = sqlx::query!(
+        "SELECT version AS \"version!\", pronunciation, action, actor, changed_at,
+                old_fixed_bits1, old_fixed_bits2, old_fixed_burst_bits, old_note, old_extra_bits,
+                new_fixed_bits1, new_fixed_bits2, new_fixed_burst_bits, new_note, new_extra_bits,
+                reverted_from
+         FROM card_feature_override_history
+         WHERE pronunciation = ?
+         ORDER BY version DESC
+         LIMIT ?",
+        pronunciation,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let entries = rows
+        .into_iter()
+        .map(|row| OverrideHistoryEntry {
+            version: row.version,
+            pronunciation: row.pronunciation,
+            action: row.action,
+            actor: row.actor,
+            changed_at: row.changed_at,
+            old_values: history_values(
+                row.old_fixed_bits1,
+                row.old_fixed_bits2,
+                row.old_fixed_burst_bits,
+                row.old_note,
+                row.old_extra_bits,
+            ),
+            new_values: history_values(
+                row.new_fixed_bits1,
+                row.new_fixed_bits2,
+                row.new_fixed_burst_bits,
+                row.new_note,
+                row.new_extra_bits,
+            ),
+            reverted_from: row.reverted_from,
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// Deletes an override and records the deletion. Returns `None` if it did not exist.
+pub async fn delete_override(
+    pool: &SqlitePool,
+    pronunciation: &str,
+    actor: &str,
+) -> Result<Option<ChangeRecord>> {
+    let mut tx = pool.begin().await?;
+
+    let Some(old) = current_override(&mut tx, pronunciation).await? else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        "DELETE FROM card_feature_override WHERE pronunciation = ?",
+        pronunciation
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let change = record_override_change(&mut tx, pronunciation, actor, Some(&old), None, None).await?;
+    tx.commit().await?;
+
+    info!("Feature override deleted by {}: {}", actor, pronunciation);
+    Ok(change)
+}
+
+/// Restores a pronunciation to the state recorded by `version`.
+/// Returns the recorded change, or `None` when the current state already matches.
+pub async fn revert_override(
+    pool: &SqlitePool,
+    pronunciation: &str,
+    version: i64,
+    actor: &str,
+) -> Result<Option<ChangeRecord>> {
+    let mut tx = pool.begin().await?;
+
+    let target = sqlx::query!(
+        "SELECT new_fixed_bits1, new_fixed_bits2, new_fixed_burst_bits, new_note, new_extra_bits
+         FROM card_feature_override_history
+         WHERE version = ? AND pronunciation = ?",
+        version,
+        pronunciation
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("Version {} not found for {}", version, pronunciation))?;
+
+    let target = history_values(
+        target.new_fixed_bits1,
+        target.new_fixed_bits2,
+        target.new_fixed_burst_bits,
+        target.new_note,
+        target.new_extra_bits,
+    );
+    let current = current_override(&mut tx, pronunciation).await?;
+
+    match &target {
+        Some(values) => {
+            let now = timestamp::now();
+            sqlx::query!(
+                "INSERT INTO card_feature_override
+                 (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note, extra_bits)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                 ON CONFLICT(pronunciation) DO UPDATE SET
+                     fixed_bits1 = excluded.fixed_bits1,
+                     fixed_bits2 = excluded.fixed_bits2,
+                     fixed_burst_bits = excluded.fixed_burst_bits,
+                     updated_at = excluded.updated_at,
+                     note = excluded.note,
+                     extra_bits = excluded.extra_bits",
+                pronunciation,
+                values.fixed_bits1,
+                values.fixed_bits2,
+                values.fixed_burst_bits,
+                now,
+                now,
+                values.note,
+                values.extra_bits
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM card_feature_override WHERE pronunciation = ?",
+                pronunciation
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    let change = record_override_change(
+        &mut tx,
+        pronunciation,
+        actor,
+        current.as_ref(),
+        target.as_ref(),
+        Some(version),
+    )
+    .await?;
+    tx.commit().await?;
+
+    info!("Feature override {} reverted to version {} by {}", pronunciation, version, actor);
+    Ok(change)
+}
+
+pub async fn current_rule(
+    conn: &mut SqliteConnection,
+    keyword: &str,
+    pattern: &str,
+) -> Result<Option<RuleValues>> {
+    let row = sqlx::query_as!(
+        RuleValues,
+        "SELECT feature_name, is_enabled AS \"is_enabled: bool\"
+         FROM rule_pattern WHERE keyword = ? AND pattern = ?",
+        keyword,
+        pattern
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn record_rule_change(
+    conn: &mut SqliteConnection,
+    keyword: &str,
+    pattern: &str,
+    actor: &str,
+    old: Option<&RuleValues>,
+    new: Option<&RuleValues>,
+) -> Result<Option<ChangeRecord>> {
+    let Some(action) = change_action(old, new) else {
+        return Ok(None);
+    };
+    let changed_at = timestamp::now();
+    let old_feature_name = old.map(|v| v.feature_name.clone());
+    let old_is_enabled = old.map(|v| v.is_enabled);
+    let new_feature_name = new.map(|v| v.feature_name.clone());
+    let new_is_enabled = new.map(|v| v.is_enabled);
+
+    let version = sqlx::query!(
+        "INSERT INTO rule_pattern_history
+         (keyword, pattern, action, actor, changed_at,
+          old_feature_name, old_is_enabled, new_feature_name, new_is_enabled)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        keyword,
+        pattern,
+        action,
+        actor,
+        changed_at,
+        old_feature_name,
+        old_is_enabled,
+        new_feature_name,
+        new_is_enabled
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let payload = ChangePayload::RulePattern {
+        keyword: keyword.to_string(),
+        pattern: pattern.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+    log_change(conn, version, action, actor, changed_at, payload).await.map(Some)
+}
+
+pub async fn current_confirmation(
+    conn: &mut SqliteConnection,
+    pronunciation: &str,
+) -> Result<Option<ConfirmationValues>> {
+    let row = sqlx::query_as!(
+        ConfirmationValues,
+        "SELECT confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits, extra_bits
+         FROM feature_confirmation WHERE pronunciation = ?",
+        pronunciation
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn record_confirmation_change(
+    conn: &mut SqliteConnection,
+    pronunciation: &str,
+    actor: &str,
+    old: Option<&ConfirmationValues>,
+    new: Option<&ConfirmationValues>,
+) -> Result<Option<ChangeRecord>> {
+    let Some(action) = change_action(old, new) else {
+        return Ok(None);
+    };
+    let changed_at = timestamp::now();
+    let old_confirmed_by = old.map(|v| v.confirmed_by.clone());
+    let old_rule_version = old.and_then(|v| v.rule_version.clone());
+    let old_bits1 = old.map(|v| v.feature_bits1);
+    let old_bits2 = old.map(|v| v.feature_bits2);
+    let old_burst_bits = old.map(|v| v.burst_bits);
+    let old_extra_bits = old.map(|v| v.extra_bits.clone());
+    let new_confirmed_by = new.map(|v| v.confirmed_by.clone());
+    let new_rule_version = new.and_then(|v| v.rule_version.clone());
+    let new_bits1 = new.map(|v| v.feature_bits1);
+    let new_bits2 = new.map(|v| v.feature_bits2);
+    let new_burst_bits = new.map(|v| v.burst_bits);
+    let new_extra_bits = new.map(|v| v.extra_bits.clone());
+
+    let version = sqlx::query!(
+        "INSERT INTO feature_confirmation_history
+         (pronunciation, action, actor, changed_at,
+          old_confirmed_by, old_rule_version, old_feature_bits1, old_feature_bits2, old_burst_bits, old_extra_bits,
+          new_confirmed_by, new_rule_version, new_feature_bits1, new_feature_bits2, new_burst_bits, new_extra_bits)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        pronunciation,
+        action,
+        actor,
+        changed_at,
+        old_confirmed_by,
+        old_rule_version,
+        old_bits1,
+        old_bits2,
+        old_burst_bits,
+        old_extra_bits,
+        new_confirmed_by,
+        new_rule_version,
+        new_bits1,
+        new_bits2,
+        new_burst_bits,
+        new_extra_bits
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let payload = ChangePayload::ConfirmedFeature {
+        pronunciation: pronunciation.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+    log_change(conn, version, action, actor, changed_at, payload).await.map(Some)
+}
+
+pub async fn current_definition(conn: &mut SqliteConnection, name: &str) -> Result<Option<DefinitionValues>> {
+    let row = sqlx::query_as!(
+        DefinitionValues,
+        "SELECT bit_field, bit_index, description, category FROM feature_definition WHERE name = ?",
+        name
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn record_definition_change(
+    conn: &mut SqliteConnection,
+    name: &str,
+    actor: &str,
+    old: Option<&DefinitionValues>,
+    new: Option<&DefinitionValues>,
+) -> Result<Option<ChangeRecord>> {
+    let Some(action) = change_action(old, new) else {
+        return Ok(None);
+    };
+    let changed_at = timestamp::now();
+    let old_bit_field = old.map(|v| v.bit_field.clone());
+    let old_bit_index = old.map(|v| v.bit_index);
+    let old_description = old.and_then(|v| v.description.clone());
+    let old_category = old.and_then(|v| v.category.clone());
+    let new_bit_field = new.map(|v| v.bit_field.clone());
+    let new_bit_index = new.map(|v| v.bit_index);
+    let new_description = new.and_then(|v| v.description.clone());
+    let new_category = new.and_then(|v| v.category.clone());
+
+    let version = sqlx::query!(
+        "INSERT INTO feature_definition_history
+         (name, action, actor, changed_at,
+          old_bit_field, old_bit_index, old_description, old_category,
+          new_bit_field, new_bit_index, new_description, new_category)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        name,
+        action,
+        actor,
+        changed_at,
+        old_bit_field,
+        old_bit_index,
+        old_description,
+        old_category,
+        new_bit_field,
+        new_bit_index,
+        new_description,
+        new_category
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let payload = ChangePayload::FeatureDefinition {
+        name: name.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+    log_change(conn, version, action, actor, changed_at, payload).await.map(Some)
+}
+
+pub async fn current_card(conn: &mut SqliteConnection, pronunciation: &str) -> Result<Option<CardValues>> {
+    let row = sqlx::query_as!(
+        CardValues,
+        "SELECT name, card_number, set_code, card_text, burst_text FROM card WHERE pronunciation = ?",
+        pronunciation
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn record_card_change(
+    conn: &mut SqliteConnection,
+    pronunciation: &str,
+    actor: &str,
+    old: Option<&CardValues>,
+    new: Option<&CardValues>,
+) -> Result<Option<ChangeRecord>> {
+    let Some(action) = change_action(old, new) else {
+        return Ok(None);
+    };
+    let changed_at = timestamp::now();
+    let old_name = old.and_then(|v| v.name.clone());
+    let old_card_number = old.and_then(|v| v.card_number.clone());
+    let old_set_code = old.and_then(|v| v.set_code.clone());
+    let old_card_text = old.map(|v| v.card_text.clone());
+    let old_burst_text = old.and_then(|v| v.burst_text.clone());
+    let new_name = new.and_then(|v| v.name.clone());
+    let new_card_number = new.and_then(|v| v.card_number.clone());
+    let new_set_code = new.and_then(|v| v.set_code.clone());
+    let new_card_text = new.map(|v| v.card_text.clone());
+    let new_burst_text = new.and_then(|v| v.burst_text.clone());
+
+    let version = sqlx::query!(
+        "INSERT INTO card_history
+         (pronunciation, action, actor, changed_at,
+          old_name, old_card_number, old_set_code, old_card_text, old_burst_text,
+          new_name, new_card_number, new_set_code, new_card_text, new_burst_text)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        pronunciation,
+        action,
+        actor,
+        changed_at,
+        old_name,
+        old_card_number,
+        old_set_code,
+        old_card_text,
+        old_burst_text,
+        new_name,
+        new_card_number,
+        new_set_code,
+        new_card_text,
+        new_burst_text
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let payload = ChangePayload::Card {
+        pronunciation: pronunciation.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+    log_change(conn, version, action, actor, changed_at, payload).await.map(Some)
+}
+
+/// Identifies one row of a synced table.
+pub enum ChangeKey<'a> {
+    FeatureOverride(&'a str),
+    RulePattern(&'a str, &'a str),
+    ConfirmedFeature(&'a str),
+    FeatureDefinition(&'a str),
+    Card(&'a str),
+}
+
+/// Returns true when the row was changed at a revision greater than `after`.
+pub async fn changed_since(conn: &mut SqliteConnection, key: ChangeKey<'_>, after: i64) -> Result<bool> {
+    let changed = match key {
+        ChangeKey::FeatureOverride(pronunciation) => sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM change_log l
+                JOIN card_feature_override_history h ON h.version = l.history_version
+                WHERE l.data_type = 'feature_override' AND l.revision > ? AND h.pronunciation = ?
+            ) AS "changed!: bool""#,
+            after,
+            pronunciation
+        )
+        .fetch_one(&mut *conn)
+        .await?,
+        ChangeKey::RulePattern(keyword, pattern) => sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM change_log l
+                JOIN rule_pattern_history h ON h.version = l.history_version
+                WHERE l.data_type = 'rule_pattern' AND l.revision > ? AND h.keyword = ? AND h.pattern = ?
+            ) AS "changed!: bool""#,
+            after,
+            keyword,
+            pattern
+        )
+        .fetch_one(&mut *conn)
+        .await?,
+        ChangeKey::ConfirmedFeature(pronunciation) => sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM change_log l
+                JOIN feature_confirmation_history h ON h.version = l.history_version
+                WHERE l.data_type = 'confirmed_feature' AND l.revision > ? AND h.pronunciation = ?
+            ) AS "changed!: bool""#,
+            after,
+            pronunciation
+        )
+        .fetch_one(&mut *conn)
+        .await?,
+        ChangeKey::FeatureDefinition(name) => sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM change_log l
+                JOIN feature_definition_history h ON h.version = l.history_version
+                WHERE l.data_type = 'feature_definition' AND l.revision > ? AND h.name = ?
+            ) AS "changed!: bool""#,
+            after,
+            name
+        )
+        .fetch_one(&mut *conn)
+        .await?,
+        ChangeKey::Card(pronunciation) => sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM change_log l
+                JOIN card_history h ON h.version = l.history_version
+                WHERE l.data_type = 'card' AND l.revision > ? AND h.pronunciation = ?
+            ) AS "changed!: bool""#,
+            after,
+            pronunciation
+        )
+        .fetch_one(&mut *conn)
+        .await?,
+    };
+
+    Ok(changed)
+}
+
+/// Returns the latest revision in change_log, or 0 when nothing has changed yet.
+pub async fn latest_revision(pool: &SqlitePool) -> Result<i64> {
+    let revision = sqlx::query_scalar!(r#"SELECT COALESCE(MAX(revision), 0) AS "revision!: i64" FROM change_log"#)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(revision)
+}
+
+/// Returns the latest revision that changed a rule pattern or feature definition, or 0.
+pub async fn ruleset_revision<'e>(executor: impl SqliteExecutor<'e>) -> Result<i64> {
+    let revision = sqlx::query_scalar!(
+        r#"SELECT COALESCE(MAX(revision), 0) AS "revision!: i64" FROM change_log
+           WHERE data_type IN ('rule_pattern', 'feature_definition')"#
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(revision)
+}
+
+/// Returns up to `limit` changes with a revision greater than `after`, oldest first.
+pub async fn changes_since(pool: &SqlitePool, after: i64, limit: i64) -> Result<Vec<ChangeRecord>> {
+    let overrides = sqlx::query!(
+        r#"
+        SELECT l.revision AS "revision!", h.version AS "version!", h.action, h.actor, h.changed_at,
+               h.pronunciation,
+               h.old_fixed_bits1, h.old_fixed_bits2, h.old_fixed_burst_bits, h.old_note, h.old_extra_bits,
+               h.new_fixed_bits1, h.new_fixed_bits2, h.new_fixed_burst_bits, h.new_note, h.new_extra_bits
+        FROM change_log l
+        JOIN card_feature_override_history h ON h.version = l.history_version
+        WHERE l.data_type = 'feature_override' AND l.revision > ?
+        ORDER BY l.revision ASC
+        LIMIT ?
+        "#,
+        after,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let rules = sqlx::query!(
+        r#"
+        SELECT l.revision AS "revision!", h.version AS "version!", h.action, h.actor, h.changed_at,
+               h.keyword, h.pattern,
+               h.old_feature_name, h.old_is_enabled AS "old_is_enabled: bool",
+               h.new_feature_name, h.new_is_enabled AS "new_is_enabled: bool"
+        FROM change_log l
+        JOIN rule_pattern_history h ON h.version = l.history_version
+        WHERE l.data_type = 'rule_pattern' AND l.revision > ?
+        ORDER BY l.revision ASC
+        LIMIT ?
+        "#,
+        after,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let confirmations = sqlx::query!(
+        r#"
+        SELECT l.revision AS "revision!", h.version AS "version!", h.action, h.actor, h.changed_at,
+               h.pronunciation,
+               h.old_confirmed_by, h.old_rule_version, h.old_feature_bits1, h.old_feature_bits2, h.old_burst_bits,
+               h.old_extra_bits,
+               h.new_confirmed_by, h.new_rule_version, h.new_feature_bits1, h.new_feature_bits2, h.new_burst_bits,
+               h.new_extra_bits
+        FROM change_log l
+        JOIN feature_confirmation_history h ON h.version = l.history_version
+        WHERE l.data_type = 'confirmed_feature' AND l.revision > ?
+        ORDER BY l.revision ASC
+        LIMIT ?
+        "#,
+        after,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let definitions = sqlx::query!(
+        r#"
+        SELECT l.revision AS "revision!", h.version AS "version!", h.action, h.actor, h.changed_at,
+               h.name,
+               h.old_bit_field, h.old_bit_index, h.old_description, h.old_category,
+               h.new_bit_field, h.new_bit_index, h.new_description, h.new_category
+        FROM change_log l
+        JOIN feature_definition_history h ON h.version = l.history_version
+        WHERE l.data_type = 'feature_definition' AND l.revision > ?
+        ORDER BY l.revision ASC
+        LIMIT ?
+        "#,
+        after,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let cards = sqlx::query!(
+        r#"
+        SELECT l.revision AS "revision!", h.version AS "version!", h.action, h.actor, h.changed_at,
+               h.pronunciation,
+               h.old_name, h.old_card_number, h.old_set_code, h.old_card_text, h.old_burst_text,
+               h.new_name, h.new_card_number, h.new_set_code, h.new_card_text, h.new_burst_text
+        FROM change_log l
+        JOIN card_history h ON h.version = l.history_version
+        WHERE l.data_type = 'card' AND l.revision > ?
+        ORDER BY l.revision ASC
+        LIMIT ?
+        "#,
+        after,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut changes: Vec<ChangeRecord> = Vec::with_capacity(
+        overrides.len() + rules.len() + confirmations.len() + definitions.len() + cards.len(),
+    );
+
+    changes.extend(overrides.into_iter().map(|row| ChangeRecord {
+        revision: row.revision,
+        version: row.version,
+        action: row.action,
+        actor: row.actor,
+        changed_at: row.changed_at,
+        payload: ChangePayload::FeatureOverride {
+            pronunciation: row.pronunciation,
+            old: history_values(
+                row.old_fixed_bits1,
+                row.old_fixed_bits2,
+                row.old_fixed_burst_bits,
+                row.old_note,
+                row.old_extra_bits,
+            ),
+            new: history_values(
+                row.new_fixed_bits1,
+                row.new_fixed_bits2,
+                row.new_fixed_burst_bits,
+                row.new_note,
+                row.new_extra_bits,
+            ),
+        },
+    }));
+
+    changes.extend(rules.into_iter().map(|row| ChangeRecord {
+        revision: row.revision,
+        version: row.version,
+        action: row.action,
+        actor: row.actor,
+        changed_at: row.changed_at,
+        payload: ChangePayload::RulePattern {
+            keyword: row.keyword,
+            pattern: row.pattern,
+            old: rule_values(row.old_feature_name, row.old_is_enabled),
+            new: rule_values(row.new_feature_name, row.new_is_enabled),
+        },
+    }));
+
+    changes.extend(confirmations.into_iter().map(|row| ChangeRecord {
+        revision: row.revision,
+        version: row.version,
+        action: row.action,
+        actor: row.actor,
+        changed_at: row.changed_at,
+        payload: ChangePayload::ConfirmedFeature {
+            pronunciation: row.pronunciation,
+            old: confirmation_values(
+                row.old_confirmed_by,
+                row.old_rule_version,
+                row.old_feature_bits1,
+                row.old_feature_bits2,
+                row.old_burst_bits,
+                row.old_extra_bits,
+            ),
+            new: confirmation_values(
+                row.new_confirmed_by,
+                row.new_rule_version,
+                row.new_feature_bits1,
+                row.new_feature_bits2,
+                row.new_burst_bits,
+                row.new_extra_bits,
+            ),
+        },
+    }));
+
+    changes.extend(definitions.into_iter().map(|row| ChangeRecord {
+        revision: row.revision,
+        version: row.version,
+        action: row.action,
+        actor: row.actor,
+        changed_at: row.changed_at,
+        payload: ChangePayload::FeatureDefinition {
+            name: row.name,
+            old: definition_values(row.old_bit_field, row.old_bit_index, row.old_description, row.old_category),
+            new: definition_values(row.new_bit_field, row.new_bit_index, row.new_description, row.new_category),
+        },
+    }));
+
+    changes.extend(cards.into_iter().map(|row| ChangeRecord {
+        revision: row.revision,
+        version: row.version,
+        action: row.action,
+        actor: row.actor,
+        changed_at: row.changed_at,
+        payload: ChangePayload::Card {
+            pronunciation: row.pronunciation,
+            old: card_values(
+                row.old_name,
+                row.old_card_number,
+                row.old_set_code,
+                row.old_card_text,
+                row.old_burst_text,
+            ),
+            new: card_values(
+                row.new_name,
+                row.new_card_number,
+                row.new_set_code,
+                row.new_card_text,
+                row.new_burst_text,
+            ),
+        },
+    }));
+
+    // Each query returned its own first `limit` rows, so the merged prefix is exact.
+    changes.sort_by_key(|change| change.revision);
+    changes.truncate(limit.max(0) as usize);
+
+    Ok(changes)
+}
+
+async fn log_change(
+    conn: &mut SqliteConnection,
+    version: i64,
+    action: &str,
+    actor: &str,
+    changed_at: String,
+    payload: ChangePayload,
+) -> Result<ChangeRecord> {
+    let data_type = payload.data_type();
+
+    let revision = sqlx::query!(
+        "INSERT INTO change_log (data_type, history_version, changed_at) VALUES (?, ?, ?)",
+        data_type,
+        version,
+        changed_at
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    Ok(ChangeRecord {
+        revision,
+        version,
+        action: action.to_string(),
+        actor: actor.to_string(),
+        changed_at,
+        payload,
+    })
+}
+
+fn change_action<T: PartialEq>(old: Option<&T>, new: Option<&T>) -> Option<&'static str> {
+    match (old, new) {
+        (None, Some(_)) => Some("create"),
+        (Some(old), Some(new)) if old != new => Some("update"),
+        (Some(_), None) => Some("delete"),
+        _ => None,
+    }
+}
+
+/// Versions recorded before extra_bits existed have NULL there, meaning no extended bits.
+fn history_values(
+    fixed_bits1: Option<i64>,
+    fixed_bits2: Option<i64>,
+    fixed_burst_bits: Option<i64>,
+    note: Option<String>,
+    extra_bits: Option<Vec<u8>>,
+) -> Option<OverrideValues> {
+    Some(OverrideValues {
+        fixed_bits1: fixed_bits1?,
+        fixed_bits2: fixed_bits2?,
+        fixed_burst_bits: fixed_burst_bits?,
+        note,
+        extra_bits: extra_bits.unwrap_or_default(),
+    })
+}
+
+type SplitValues = (Option<i64>, Option<i64>, Option<i64>, Option<String>, Option<Vec<u8>>);
+
+fn split_values(values: Option<&OverrideValues>) -> SplitValues {
+    match values {
+        Some(v) => (
+            Some(v.fixed_bits1),
+            Some(v.fixed_bits2),
+            Some(v.fixed_burst_bits),
+            v.note.clone(),
+            Some(v.extra_bits.clone()),
+        ),
+        None => (None, None, None, None, None),
+    }
+}
+
+fn rule_values(feature_name: Option<String>, is_enabled: Option<bool>) -> Option<RuleValues> {
+    Some(RuleValues {
+        feature_name: feature_name?,
+        is_enabled: is_enabled?,
+    })
+}
+
+fn confirmation_values(
+    confirmed_by: Option<String>,
+    rule_version: Option<String>,
+    feature_bits1: Option<i64>,
+    feature_bits2: Option<i64>,
+    burst_bits: Option<i64>,
+    extra_bits: Option<Vec<u8>>,
+) -> Option<ConfirmationValues> {
+    Some(ConfirmationValues {
+        confirmed_by: confirmed_by?,
+        rule_version,
+        feature_bits1: feature_bits1?,
+        feature_bits2: feature_bits2?,
+        burst_bits: burst_bits?,
+        extra_bits: extra_bits.unwrap_or_default(),
+    })
+}
+
+fn definition_values(
+    bit_field: Option<String>,
+    bit_index: Option<i64>,
+    description: Option<String>,
+    category: Option<String>,
+) -> Option<DefinitionValues> {
+    Some(DefinitionValues {
+        bit_field: bit_field?,
+        bit_index: bit_index?,
+        description,
+        category,
+    })
+}
+
+fn card_values(
+    name: Option<String>,
+    card_number: Option<String>,
+    set_code: Option<String>,
+    card_text: Option<String>,
+    burst_text: Option<String>,
+) -> Option<CardValues> {
+    // card_text is set on every version that has a card
+    Some(CardValues {
+        name,
+        card_number,
+        set_code,
+        card_text: card_text?,
+        burst_text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    /// Writes an override the way the server does and returns its history version
+    async fn put_override(pool: &SqlitePool, pronunciation: &str, bits: i64) -> i64 {
+        let mut tx = pool.begin().await.unwrap();
+        let old = current_override(&mut tx, pronunciation).await.unwrap();
+        let new = OverrideValues {
+            fixed_bits1: bits,
+            fixed_bits2: 0,
+            fixed_burst_bits: 0,
+            note: None,
+            extra_bits: Vec::new(),
+        };
+        sqlx::query(
+            "INSERT INTO card_feature_override
+             (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at)
+             VALUES (?, ?, 0, 0, '1970-01-01T00:16:40.000Z', ?)
+             ON CONFLICT(pronunciation) DO UPDATE SET
+                 fixed_bits1 = excluded.fixed_bits1,
+                 updated_at = excluded.updated_at",
+        )
+        .bind(pronunciation)
+        .bind(bits)
+        .bind(timestamp::now())
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        let change = record_override_change(&mut tx, pronunciation, "test", old.as_ref(), Some(&new), None).await;
+        tx.commit().await.unwrap();
+        change.unwrap().unwrap().version
+    }
+
+    async fn stored(pool: &SqlitePool, pronunciation: &str) -> (i64, String) {
+        sqlx::query_as("SELECT fixed_bits1, created_at FROM card_feature_override WHERE pronunciation = ?")
+            .bind(pronunciation)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn revert_restores_values_and_keeps_created_at() {
+        let pool = test_pool().await;
+        let first = put_override(&pool, "ア", 1).await;
+        put_override(&pool, "ア", 2).await;
+        assert_eq!(stored(&pool, "ア").await, (2, "1970-01-01T00:16:40.000Z".to_string()));
+
+        let change = revert_override(&pool, "ア", first, "reverter").await.unwrap().unwrap();
+        assert_eq!(change.action, "update");
+        assert_eq!(stored(&pool, "ア").await, (1, "1970-01-01T00:16:40.000Z".to_string()));
+
+        let history = override_history(&pool, "ア", 10).await.unwrap();
+        assert_eq!(history[0].reverted_from, Some(first));
+        assert_eq!(history[0].actor, "reverter");
+
+        // Already in that state: nothing to record
+        assert!(revert_override(&pool, "ア", first, "reverter").await.unwrap().is_none());
+        assert_eq!(override_history(&pool, "ア", 10).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn revert_to_a_deletion_deletes() {
+        let pool = test_pool().await;
+        put_override(&pool, "ア", 1).await;
+        let deleted = delete_override(&pool, "ア", "test").await.unwrap().unwrap().version;
+        put_override(&pool, "ア", 2).await;
+
+        revert_override(&pool, "ア", deleted, "test").await.unwrap().unwrap();
+        assert!(current_override(&mut pool.acquire().await.unwrap(), "ア").await.unwrap().is_none());
+        assert!(revert_override(&pool, "イ", deleted, "test").await.is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use prost::Message;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use tonic::{metadata::MetadataMap, Status};
+use tracing::warn;
+
+use crate::errors::invalid_argument;
+use crate::timestamp;
+
+/// Metadata key clients set to make a push or confirm RPC safe to retry
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const MAX_KEY_LENGTH: usize = 255;
+/// How long stored responses are kept when IDEMPOTENCY_RETENTION_HOURS is not set
+const DEFAULT_RETENTION_HOURS: i64 = 24;
+/// A claim this old without a response belongs to a request that died mid-way
+const ABANDONED_AFTER_MINUTES: i64 = 10;
+
+/// Stored outcomes of requests sent with an idempotency-key, scoped per client and RPC.
+#[derive(Clone)]
+pub(crate) struct IdempotencyStore {
+    pool: SqlitePool,
+    retention: chrono::Duration,
+}
+
+/// What to do with a request after looking up its idempotency-key.
+pub(crate) enum Claim {
+    /// No key was sent; process normally
+    Disabled,
+    /// First use of the key; process and then record the response
+    Claimed(ClaimedKey),
+    /// The key was used before; answer with the stored response
+    Replay(StoredResponse),
+}
+
+impl IdempotencyStore {
+    pub(crate) fn new(pool: SqlitePool) -> Self {
+        let hours = std::env::var("IDEMPOTENCY_RETENTION_HOURS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|hours: &i64| *hours > 0)
+            .unwrap_or(DEFAULT_RETENTION_HOURS);
+
+        Self {
+            pool,
+            retention: chrono::Duration::hours(hours),
+        }
+    }
+
+    /// Looks up the request's idempotency-key and claims it if it is new.
+    pub(crate) async fn claim(&self, metadata: &MetadataMap, client_name: &str, rpc: &'static str) -> Result<Claim, Status> {
+        let Some(value) = metadata.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(Claim::Disabled);
+        };
+        let key = value
+            .to_str()
+            .ok()
+            .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+            .ok_or_else(|| {
+                invalid_argument(
+                    IDEMPOTENCY_KEY_HEADER,
+                    format!("must be 1 to {} printable ASCII characters", MAX_KEY_LENGTH),
+                )
+            })?
+            .to_string();
+
+        let db_error = |e: sqlx::Error| Status::internal(format!("Database error: {}", e));
+        let now = chrono::Utc::now();
+        let now_str = timestamp::format(&now);
+        let expired_before = timestamp::format(&(now - self.retention));
+        let abandoned_before = timestamp::format(&(now - chrono::Duration::minutes(ABANDONED_AFTER_MINUTES)));
+
+        sqlx::query!(
+            "DELETE FROM idempotency_key
+             WHERE created_at < ? OR (response IS NULL AND created_at < ?)",
+            expired_before,
+            abandoned_before
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        let inserted = sqlx::query!(
+            "INSERT INTO idempotency_key (client_name, rpc, idempotency_key, created_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT (client_name, rpc, idempotency_key) DO NOTHING",
+            client_name,
+            rpc,
+            key,
+            now_str
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?
+        .rows_affected();
+
+        if inserted == 1 {
+            return Ok(Claim::Claimed(ClaimedKey {
+                pool: self.pool.clone(),
+                client_name: client_name.to_string(),
+                rpc,
+                key,
+                completed: false,
+            }));
+        }
+
+        let stored = sqlx::query!(
+            "SELECT request_hash, response FROM idempotency_key
+             WHERE client_name = ? AND rpc = ? AND idempotency_key = ?",
+            client_name,
+            rpc,
+            key
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        match stored {
+            Some(row) => match (row.request_hash, row.response) {
+                (Some(request_hash), Some(response)) => Ok(Claim::Replay(StoredResponse { request_hash, response })),
+                _ => Err(Status::aborted(
+                    "A request with this idempotency-key is still in progress; retry later",
+                )),
+            },
+            // Purged between the insert and the select; let the client retry
+            None => Err(Status::aborted("idempotency-key expired while claiming it; retry")),
+        }
+    }
+}
+
+impl Claim {
+    /// Records the response for replays. Only call it with a final outcome: a response a retry
+    /// could change, like a transient database error, should drop the claim instead, which
+    /// releases the key. Failing to record is logged rather than returned, since the request
+    /// itself has already been applied.
+    pub(crate) async fn complete<R: Message>(self, request_hash: RequestHash, response: &R) {
+        if let Claim::Claimed(mut claimed) = self {
+            let request_hash = request_hash.finish();
+            let encoded = response.encode_to_vec();
+            let result = sqlx::query!(
+                "UPDATE idempotency_key SET request_hash = ?, response = ?
+                 WHERE client_name = ? AND rpc = ? AND idempotency_key = ?",
+                request_hash,
+                encoded,
+                claimed.client_name,
+                claimed.rpc,
+                claimed.key
+            )
+            .execute(&claimed.pool)
+            .await;
+
+            match result {
+                Ok(_) => claimed.completed = true,
+                Err(e) => warn!("Failed to store response for idempotency-key {}: {}", claimed.key, e),
+            }
+        }
+    }
+}
+
+/// A key claimed by the current request. Dropping it without completing releases the key,
+/// so a request that failed part-way can be retried with the same key.
+pub(crate) struct ClaimedKey {
+    pool: SqlitePool,
+    client_name: String,
+    rpc: &'static str,
+    key: String,
+    completed: bool,
+}
+
+impl Drop for ClaimedKey {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        let pool = self.pool.clone();
+        let (client_name, rpc, key) = (std::mem::take(&mut self.client_name), self.rpc, std::mem::take(&mut self.key));
+        tokio::spawn(async move {
+            let result = sqlx::query!(
+                "DELETE FROM idempotency_key
+                 WHERE client_name = ? AND rpc = ? AND idempotency_key = ? AND response IS NULL",
+                client_name,
+                rpc,
+                key
+            )
+            .execute(&pool)
+            .await;
+            if let Err(e) = result {
+                warn!("Failed to release idempotency-key {}: {}", key, e);
+            }
+        });
+    }
+}
+
+/// The response stored for an idempotency-key.
+pub(crate) struct StoredResponse {
+    request_hash: String,
+    response: Vec<u8>,
+}
+
+impl StoredResponse {
+    /// Returns the stored response if it was produced by the same request.
+    pub(crate) fn reply<R: Message + Default>(self, request_hash: RequestHash) -> Result<R, Status> {
+        if request_hash.finish() != self.request_hash {
+            return Err(invalid_argument(
+                IDEMPOTENCY_KEY_HEADER,
+                "was already used for a different request",
+            ));
+        }
+        R::decode(self.response.as_slice())
+            .map_err(|e| Status::internal(format!("Stored response is unreadable: {}", e)))
+    }
+}
+
+/// SHA-256 over the request messages, to detect a key reused for a different request.
+#[derive(Default)]
+pub(crate) struct RequestHash(Sha256);
+
+impl RequestHash {
+    pub(crate) fn of<M: Message>(message: &M) -> Self {
+        let mut hash = Self::default();
+        hash.update(message);
+        hash
+    }
+
+    pub(crate) fn update<M: Message>(&mut self, message: &M) {
+        self.0.update(message.encode_length_delimited_to_vec());
+    }
+
+    fn finish(self) -> String {
+        format!("{:x}", self.0.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::proto::{ConfirmRequest, ConfirmResponse};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tonic::Code;
+
+    async fn store() -> IdempotencyStore {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        IdempotencyStore {
+            pool,
+            retention: chrono::Duration::hours(DEFAULT_RETENTION_HOURS),
+        }
+    }
+
+    fn metadata(key: &str) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(IDEMPOTENCY_KEY_HEADER, key.parse().unwrap());
+        metadata
+    }
+
+    fn request(pronunciation: &str) -> ConfirmRequest {
+        ConfirmRequest {
+            pronunciation: pronunciation.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn claim(store: &IdempotencyStore, key: &str) -> Result<Claim, Status> {
+        store.claim(&metadata(key), "client", "ConfirmFeatures").await
+    }
+
+    #[tokio::test]
+    async fn replays_the_stored_response() {
+        let store = store().await;
+        let response = ConfirmResponse {
+            success: true,
+            error: None,
+            warnings: vec!["warning".to_string()],
+        };
+        let claimed = claim(&store, "key").await.unwrap();
+        assert!(matches!(claimed, Claim::Claimed(_)));
+        claimed.complete(RequestHash::of(&request("ア")), &response).await;
+
+        let Claim::Replay(stored) = claim(&store, "key").await.unwrap() else {
+            panic!("expected a replay");
+        };
+        let replayed: ConfirmResponse = stored.reply(RequestHash::of(&request("ア"))).unwrap();
+        assert_eq!(replayed, response);
+
+        // Keys are scoped per RPC
+        let other_rpc = store.claim(&metadata("key"), "client", "UnconfirmFeature").await.unwrap();
+        assert!(matches!(other_rpc, Claim::Claimed(_)));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_different_request_under_the_same_key() {
+        let store = store().await;
+        let claimed = claim(&store, "key").await.unwrap();
+        claimed.complete(RequestHash::of(&request("ア")), &ConfirmResponse::default()).await;
+
+        let Claim::Replay(stored) = claim(&store, "key").await.unwrap() else {
+            panic!("expected a replay");
+        };
+        let status = stored.reply::<ConfirmResponse>(RequestHash::of(&request("イ"))).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn released_key_can_be_claimed_again() {
+        let store = store().await;
+        let claimed = claim(&store, "key").await.unwrap();
+
+        // Held by a request still running
+        assert_eq!(claim(&store, "key").await.err().map(|status| status.code()), Some(Code::Aborted));
+
+        // Dropped without a response, as after a transient failure; the release runs in a task
+        drop(claimed);
+        for _ in 0..100 {
+            match claim(&store, "key").await {
+                Ok(Claim::Claimed(_)) => return,
+                Err(status) if status.code() == Code::Aborted => {
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await
+                }
+                _ => panic!("expected the released key to be claimed"),
+            }
+        }
+        panic!("the key was never released");
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_key_are_not_tracked() {
+        let store = store().await;
+        let claim = store.claim(&MetadataMap::new(), "client", "ConfirmFeatures").await.unwrap();
+        assert!(matches!(claim, Claim::Disabled));
+
+        let status = store.claim(&metadata(&"k".repeat(MAX_KEY_LENGTH + 1)), "client", "ConfirmFeatures").await;
+        assert_eq!(status.err().map(|status| status.code()), Some(Code::InvalidArgument));
+    }
+}
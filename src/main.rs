@@ -1,9 +1,28 @@
+// tonic::Status is the error type of every handler; boxing it is not worth the noise.
+#![allow(clippy::result_large_err)]
+
 use anyhow::Result;
 use tracing::info;
 
+// Shared with admin-cli, which uses the parts the server does not.
+#[allow(dead_code)]
 mod auth;
+mod bitset;
+mod catalog;
 mod database;
+mod errors;
+mod history;
+mod idempotency;
+mod pronunciation;
+mod pull;
+mod push;
+mod report;
+mod rules;
+mod ruleset;
 mod server;
+mod staleness;
+mod sync;
+mod timestamp;
 
 use database::Database;
 use server::AdminServer;
@@ -20,6 +39,12 @@ async fn main() -> Result<()> {
     
     let db = Database::new(&database_url).await?;
     db.migrate().await?;
+    db.check_timestamps().await?;
+    db.check_pronunciations().await?;
+
+    // Records the ruleset as known even if it changed outside the server
+    let version = ruleset::record_current(&mut *db.pool().acquire().await?).await?;
+    info!("Ruleset version: {}", version);
     
     let server = AdminServer::new(db);
     server.serve().await?;
@@ -0,0 +1,143 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest pronunciation accepted, in characters after normalization
+pub const MAX_PRONUNCIATION_CHARS: usize = 200;
+
+/// Returns the canonical form of a pronunciation key, or why it cannot be a key.
+///
+/// - NFKC: half-width katakana become full-width, full-width ASCII becomes half-width
+/// - Hiragana folds to katakana, so ひらがな and ヒラガナ are the same card
+/// - Whitespace is trimmed and inner runs collapse to one ASCII space
+///
+/// Normalizing a normalized key returns it unchanged.
+pub fn normalize(raw: &str) -> Result<String, String> {
+    let folded: String = raw.nfkc().map(fold_kana).collect();
+    let normalized = folded.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if normalized.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if normalized.chars().any(char::is_control) {
+        return Err("must not contain control characters".to_string());
+    }
+    if normalized.chars().count() > MAX_PRONUNCIATION_CHARS {
+        return Err(format!("must be at most {} characters", MAX_PRONUNCIATION_CHARS));
+    }
+
+    Ok(normalized)
+}
+
+/// Returns the canonical form of a pronunciation prefix, or why it cannot match keys reliably.
+///
+/// Applies the same folding as [`normalize`] but keeps leading and trailing whitespace as one
+/// space, since "アイ " is a different prefix from "アイ". NFKC is not prefix-stable: a
+/// half-width kana that takes a voicing mark (ｶ of ｶﾞ) normalizes differently depending on
+/// what follows it, so a prefix ending in one is rejected. A full-width prefix followed by a
+/// separate combining voicing mark in the key has the same problem and is not detected.
+pub fn normalize_prefix(raw: &str) -> Result<String, String> {
+    if raw.chars().last().is_some_and(takes_voicing_mark) {
+        return Err("must not end in a half-width kana that a voicing mark can follow".to_string());
+    }
+
+    let folded: String = raw.nfkc().map(fold_kana).collect();
+    let mut normalized = String::with_capacity(folded.len());
+    for c in folded.chars() {
+        if c.is_whitespace() {
+            if !normalized.ends_with(' ') {
+                normalized.push(' ');
+            }
+        } else {
+            normalized.push(c);
+        }
+    }
+
+    if normalized.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if normalized.chars().any(char::is_control) {
+        return Err("must not contain control characters".to_string());
+    }
+    if normalized.chars().count() > MAX_PRONUNCIATION_CHARS {
+        return Err(format!("must be at most {} characters", MAX_PRONUNCIATION_CHARS));
+    }
+
+    Ok(normalized)
+}
+
+/// Replaces the value with its canonical form when it is a valid key. Invalid values are left
+/// as they are so validation can report them.
+pub fn normalize_in_place(pronunciation: &mut String) {
+    if let Ok(normalized) = normalize(pronunciation) {
+        *pronunciation = normalized;
+    }
+}
+
+/// Whether a half-width katakana composes with a following ﾞ or ﾟ under NFKC.
+fn takes_voicing_mark(c: char) -> bool {
+    ('\u{FF66}'..='\u{FF9D}').contains(&c)
+        && ['\u{FF9E}', '\u{FF9F}'].iter().any(|mark| [c, *mark].iter().collect::<String>().nfkc().count() == 1)
+}
+
+/// Maps a hiragana character to its katakana counterpart.
+fn fold_kana(c: char) -> char {
+    match c {
+        // ぁ..ゖ and the iteration marks ゝゞ sit 0x60 below ァ..ヶ and ヽヾ
+        '\u{3041}'..='\u{3096}' | '\u{309D}'..='\u{309E}' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_width_kana_composes_with_voicing_marks() {
+        assert_eq!(normalize("ｶﾞｲｱ").unwrap(), "ガイア");
+        assert_eq!(normalize("ﾊﾟﾝ").unwrap(), "パン");
+    }
+
+    #[test]
+    fn hiragana_folds_to_katakana() {
+        assert_eq!(normalize("ひらがな").unwrap(), "ヒラガナ");
+        assert_eq!(normalize("ゔぁ").unwrap(), "ヴァ");
+        assert_eq!(normalize("いすゞ").unwrap(), "イスヾ");
+        assert_eq!(normalize("こゝろ").unwrap(), "コヽロ");
+    }
+
+    #[test]
+    fn whitespace_is_trimmed_and_collapsed() {
+        assert_eq!(normalize("  アイ \t\u{3000} ウ  ").unwrap(), "アイ ウ");
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert!(normalize(" \u{3000}").is_err());
+        assert!(normalize("アイ\u{0}ウ").is_err());
+        assert!(normalize(&"ア".repeat(MAX_PRONUNCIATION_CHARS)).is_ok());
+        assert!(normalize(&"ア".repeat(MAX_PRONUNCIATION_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn normalize_is_idempotent() {
+        for raw in ["ｶﾞｲｱ", "ひらがな ゝ", "ＡＢＣ　１２３", " テスト  カード "] {
+            let once = normalize(raw).unwrap();
+            assert_eq!(normalize(&once).unwrap(), once);
+        }
+    }
+
+    #[test]
+    fn prefix_keeps_edge_whitespace() {
+        assert_eq!(normalize_prefix("あい\u{3000}").unwrap(), "アイ ");
+        assert_eq!(normalize_prefix("ｱ  ｲ").unwrap(), "ア イ");
+        assert!(normalize_prefix("").is_err());
+    }
+
+    #[test]
+    fn prefix_rejects_half_width_kana_before_a_voicing_mark() {
+        assert!(normalize_prefix("ｶ").is_err());
+        assert!(normalize_prefix("ｱｲｳ").is_err());
+        assert_eq!(normalize_prefix("ｶﾞ").unwrap(), "ガ");
+        assert_eq!(normalize_prefix("ｱｲﾝ").unwrap(), "アイン");
+    }
+}
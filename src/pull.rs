@@ -0,0 +1,942 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteRow, QueryBuilder, Row, Sqlite, SqlitePool};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{metadata::MetadataMap, Code, Status};
+
+use crate::bitset;
+use crate::server::proto::{BitMask, FeatureBitsMask, FeatureBitset, PullRequest};
+use crate::errors::{self, invalid_argument};
+use crate::server::{pronunciation_key, pronunciation_prefix, timestamp_from_proto};
+use crate::timestamp;
+
+/// Rows read from the database per query while streaming a pull
+pub(crate) const PULL_CHUNK_SIZE: i64 = 256;
+/// Largest `limit` a single pull may ask for; page through anything bigger
+pub(crate) const MAX_PULL_LIMIT: i64 = 100_000;
+/// Messages buffered per pull before the sender waits for the client
+const PULL_CHANNEL_CAPACITY: usize = 128;
+/// Pronunciations one pull may list explicitly
+pub(crate) const MAX_PULL_PRONUNCIATIONS: usize = 1000;
+
+/// Trailer carrying the page_token for the next page when a pull stops at its limit
+pub(crate) const NEXT_PAGE_TOKEN_TRAILER: &str = "next-page-token";
+/// Bumped whenever the token layout changes so stale tokens are rejected instead of misread
+const PAGE_TOKEN_VERSION: u8 = 5;
+
+/// Reconstructs card_feature_override at `as_of` from history, with the live table's columns.
+/// `created_at` is the latest create at or before the chosen version, and `pull_revision` is
+/// the version's place in change_log.
+const FEATURE_OVERRIDE_AS_OF: (&str, &str) = (
+    "SELECT h.pronunciation,
+            h.new_fixed_bits1 AS fixed_bits1,
+            h.new_fixed_bits2 AS fixed_bits2,
+            h.new_fixed_burst_bits AS fixed_burst_bits,
+            h.new_note AS note,
+            COALESCE(h.new_extra_bits, X'') AS extra_bits,
+            COALESCE((SELECT c.changed_at FROM card_feature_override_history c
+                      WHERE c.pronunciation = h.pronunciation AND c.action = 'create' AND c.version <= h.version
+                      ORDER BY c.version DESC LIMIT 1), h.changed_at) AS created_at,
+            h.changed_at AS updated_at,
+            l.revision AS pull_revision
+     FROM card_feature_override_history h
+     JOIN change_log l ON l.data_type = 'feature_override' AND l.history_version = h.version
+     WHERE h.action != 'delete'
+       AND h.version IN (
+             SELECT MAX(version) FROM card_feature_override_history
+             WHERE changed_at <= ",
+    " GROUP BY pronunciation)",
+);
+
+/// Reconstructs rule_pattern at `as_of`. See [`FEATURE_OVERRIDE_AS_OF`].
+const RULE_PATTERN_AS_OF: (&str, &str) = (
+    "SELECT h.keyword,
+            h.pattern,
+            h.new_feature_name AS feature_name,
+            h.new_is_enabled AS is_enabled,
+            COALESCE((SELECT c.changed_at FROM rule_pattern_history c
+                      WHERE c.keyword = h.keyword AND c.pattern = h.pattern
+                        AND c.action = 'create' AND c.version <= h.version
+                      ORDER BY c.version DESC LIMIT 1), h.changed_at) AS created_at,
+            h.changed_at AS updated_at,
+            l.revision AS pull_revision
+     FROM rule_pattern_history h
+     JOIN change_log l ON l.data_type = 'rule_pattern' AND l.history_version = h.version
+     WHERE h.action != 'delete'
+       AND h.version IN (
+             SELECT MAX(version) FROM rule_pattern_history
+             WHERE changed_at <= ",
+    " GROUP BY keyword, pattern)",
+);
+
+/// Reconstructs feature_confirmation at `as_of`. See [`FEATURE_OVERRIDE_AS_OF`].
+const CONFIRMED_FEATURE_AS_OF: (&str, &str) = (
+    "SELECT h.pronunciation,
+            h.new_confirmed_by AS confirmed_by,
+            h.new_rule_version AS rule_version,
+            h.new_feature_bits1 AS feature_bits1,
+            h.new_feature_bits2 AS feature_bits2,
+            h.new_burst_bits AS burst_bits,
+            COALESCE(h.new_extra_bits, X'') AS extra_bits,
+            h.changed_at AS confirmed_at,
+            l.revision AS pull_revision
+     FROM feature_confirmation_history h
+     JOIN change_log l ON l.data_type = 'confirmed_feature' AND l.history_version = h.version
+     WHERE h.action != 'delete'
+       AND h.version IN (
+             SELECT MAX(version) FROM feature_confirmation_history
+             WHERE changed_at <= ",
+    " GROUP BY pronunciation)",
+);
+
+/// Reconstructs feature_definition at `as_of`. See [`FEATURE_OVERRIDE_AS_OF`].
+const FEATURE_DEFINITION_AS_OF: (&str, &str) = (
+    "SELECT h.name,
+            h.new_bit_field AS bit_field,
+            h.new_bit_index AS bit_index,
+            h.new_description AS description,
+            h.new_category AS category,
+            COALESCE((SELECT c.changed_at FROM feature_definition_history c
+                      WHERE c.name = h.name AND c.action = 'create' AND c.version <= h.version
+                      ORDER BY c.version DESC LIMIT 1), h.changed_at) AS created_at,
+            h.changed_at AS updated_at,
+            l.revision AS pull_revision
+     FROM feature_definition_history h
+     JOIN change_log l ON l.data_type = 'feature_definition' AND l.history_version = h.version
+     WHERE h.action != 'delete'
+       AND h.version IN (
+             SELECT MAX(version) FROM feature_definition_history
+             WHERE changed_at <= ",
+    " GROUP BY name)",
+);
+
+/// Reconstructs card at `as_of`. See [`FEATURE_OVERRIDE_AS_OF`].
+const CARD_AS_OF: (&str, &str) = (
+    "SELECT h.pronunciation,
+            h.new_name AS name,
+            h.new_card_number AS card_number,
+            h.new_set_code AS set_code,
+            h.new_card_text AS card_text,
+            h.new_burst_text AS burst_text,
+            COALESCE((SELECT c.changed_at FROM card_history c
+                      WHERE c.pronunciation = h.pronunciation AND c.action = 'create' AND c.version <= h.version
+                      ORDER BY c.version DESC LIMIT 1), h.changed_at) AS created_at,
+            h.changed_at AS updated_at,
+            l.revision AS pull_revision
+     FROM card_history h
+     JOIN change_log l ON l.data_type = 'card' AND l.history_version = h.version
+     WHERE h.action != 'delete'
+       AND h.version IN (
+             SELECT MAX(version) FROM card_history
+             WHERE changed_at <= ",
+    " GROUP BY pronunciation)",
+);
+
+/// The synced tables a pull can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) enum PullTable {
+    FeatureOverride,
+    RulePattern,
+    ConfirmedFeature,
+    FeatureDefinition,
+    Card,
+}
+
+impl PullTable {
+    fn live_table(self) -> &'static str {
+        match self {
+            PullTable::FeatureOverride => "card_feature_override",
+            PullTable::RulePattern => "rule_pattern",
+            PullTable::ConfirmedFeature => "feature_confirmation",
+            PullTable::FeatureDefinition => "feature_definition",
+            PullTable::Card => "card",
+        }
+    }
+
+    /// History table and change_log data_type of the table
+    fn history(self) -> (&'static str, &'static str) {
+        match self {
+            PullTable::FeatureOverride => ("card_feature_override_history", "feature_override"),
+            PullTable::RulePattern => ("rule_pattern_history", "rule_pattern"),
+            PullTable::ConfirmedFeature => ("feature_confirmation_history", "confirmed_feature"),
+            PullTable::FeatureDefinition => ("feature_definition_history", "feature_definition"),
+            PullTable::Card => ("card_history", "card"),
+        }
+    }
+
+    /// The live rows, each with `pull_revision`: the change_log revision of its latest history
+    /// version. Every write records history, so a row written while a client is paging moves
+    /// past the client's position instead of landing behind it.
+    fn live_source(self) -> String {
+        let (history, data_type) = self.history();
+        let same_key = |left: &str, right: &str| {
+            self.key_columns()
+                .iter()
+                .map(|column| format!("{left}.{column} = {right}.{column}"))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        };
+        format!(
+            "SELECT t.*, l.revision AS pull_revision
+             FROM change_log l
+             JOIN {history} h ON h.version = l.history_version
+             JOIN {live} t ON {join}
+             WHERE l.data_type = '{data_type}'
+               AND h.version = (SELECT MAX(m.version) FROM {history} m WHERE {latest})",
+            live = self.live_table(),
+            join = same_key("t", "h"),
+            latest = same_key("m", "h"),
+        )
+    }
+
+    fn as_of_source(self) -> (&'static str, &'static str) {
+        match self {
+            PullTable::FeatureOverride => FEATURE_OVERRIDE_AS_OF,
+            PullTable::RulePattern => RULE_PATTERN_AS_OF,
+            PullTable::ConfirmedFeature => CONFIRMED_FEATURE_AS_OF,
+            PullTable::FeatureDefinition => FEATURE_DEFINITION_AS_OF,
+            PullTable::Card => CARD_AS_OF,
+        }
+    }
+
+    /// Column `since` compares against
+    fn time_column(self) -> &'static str {
+        match self {
+            PullTable::FeatureOverride | PullTable::RulePattern | PullTable::FeatureDefinition | PullTable::Card => {
+                "updated_at"
+            }
+            PullTable::ConfirmedFeature => "confirmed_at",
+        }
+    }
+
+    /// Natural key, shared by the table and its history
+    fn key_columns(self) -> &'static [&'static str] {
+        match self {
+            PullTable::FeatureOverride | PullTable::ConfirmedFeature | PullTable::Card => &["pronunciation"],
+            PullTable::RulePattern => &["keyword", "pattern"],
+            PullTable::FeatureDefinition => &["name"],
+        }
+    }
+}
+
+/// The override bit fields a pull can filter on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) enum BitColumn {
+    FixedBits1,
+    FixedBits2,
+    FixedBurstBits,
+}
+
+impl BitColumn {
+    fn name(self) -> &'static str {
+        match self {
+            BitColumn::FixedBits1 => "fixed_bits1",
+            BitColumn::FixedBits2 => "fixed_bits2",
+            BitColumn::FixedBurstBits => "fixed_burst_bits",
+        }
+    }
+}
+
+/// A condition narrowing the rows a pull returns. Each variant renders to a parameterized
+/// predicate over the columns every source of a [`PullTable`] exposes.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) enum PullFilter {
+    /// Rows whose timestamp is after this RFC3339 time
+    ChangedSince(String),
+    /// Rows for exactly these pronunciations
+    Pronunciations(Vec<String>),
+    /// Rows whose pronunciation starts with this text
+    PronunciationPrefix(String),
+    /// Rows whose note contains this text
+    NoteContains(String),
+    /// Rows with at least one of the mask's bits set
+    AnyBits(BitColumn, i64),
+    /// Rows with all of the mask's bits set
+    AllBits(BitColumn, i64),
+    /// Rows with at least one of the mask's bits set in the whole bitset
+    AnyFeatureBits(Vec<u64>),
+    /// Rows with all of the mask's bits set in the whole bitset
+    AllFeatureBits(Vec<u64>),
+}
+
+impl PullFilter {
+    /// The PullRequest field the filter came from, for error details
+    fn field(&self) -> String {
+        match self {
+            PullFilter::ChangedSince(_) => "since".to_string(),
+            PullFilter::Pronunciations(_) => "pronunciations".to_string(),
+            PullFilter::PronunciationPrefix(_) => "pronunciation_prefix".to_string(),
+            PullFilter::NoteContains(_) => "note_contains".to_string(),
+            PullFilter::AnyBits(column, _) => format!("{}.any", column.name()),
+            PullFilter::AllBits(column, _) => format!("{}.all", column.name()),
+            PullFilter::AnyFeatureBits(_) => "feature_bits.any".to_string(),
+            PullFilter::AllFeatureBits(_) => "feature_bits.all".to_string(),
+        }
+    }
+
+    /// Whether the table has the columns this filter reads
+    fn applies_to(&self, table: PullTable) -> bool {
+        match self {
+            PullFilter::ChangedSince(_) => true,
+            PullFilter::Pronunciations(_) | PullFilter::PronunciationPrefix(_) => {
+                matches!(table, PullTable::FeatureOverride | PullTable::ConfirmedFeature | PullTable::Card)
+            }
+            PullFilter::NoteContains(_)
+            | PullFilter::AnyBits(..)
+            | PullFilter::AllBits(..)
+            | PullFilter::AnyFeatureBits(_)
+            | PullFilter::AllFeatureBits(_) => table == PullTable::FeatureOverride,
+        }
+    }
+
+    fn push_sql(&self, table: PullTable, builder: &mut QueryBuilder<'_, Sqlite>) {
+        match self {
+            PullFilter::ChangedSince(since) => {
+                builder
+                    .push(format_args!("src.{} > ", table.time_column()))
+                    .push_bind(since.clone());
+            }
+            PullFilter::Pronunciations(pronunciations) => {
+                builder.push("src.pronunciation IN (");
+                let mut values = builder.separated(", ");
+                for pronunciation in pronunciations {
+                    values.push_bind(pronunciation.clone());
+                }
+                builder.push(")");
+            }
+            PullFilter::PronunciationPrefix(prefix) => {
+                // substr rather than LIKE: no wildcards to escape, and case-sensitive like the key
+                builder
+                    .push("substr(src.pronunciation, 1, ")
+                    .push_bind(prefix.chars().count() as i64)
+                    .push(") = ")
+                    .push_bind(prefix.clone());
+            }
+            PullFilter::NoteContains(text) => {
+                builder.push("instr(src.note, ").push_bind(text.clone()).push(") > 0");
+            }
+            PullFilter::AnyBits(column, mask) => {
+                builder
+                    .push(format_args!("(src.{} & ", column.name()))
+                    .push_bind(*mask)
+                    .push(") != 0");
+            }
+            PullFilter::AllBits(column, mask) => {
+                builder
+                    .push(format_args!("(src.{} & ", column.name()))
+                    .push_bind(*mask)
+                    .push(") = ")
+                    .push_bind(*mask);
+            }
+            PullFilter::AnyFeatureBits(mask) => {
+                builder.push("(");
+                for (i, (value, part)) in feature_bits_parts(mask).into_iter().enumerate() {
+                    if i > 0 {
+                        builder.push(" OR ");
+                    }
+                    builder.push(format_args!("({} & ", value)).push_bind(part).push(") != 0");
+                }
+                builder.push(")");
+            }
+            PullFilter::AllFeatureBits(mask) => {
+                builder.push("(1");
+                for (value, part) in feature_bits_parts(mask) {
+                    builder
+                        .push(format_args!(" AND ({} & ", value))
+                        .push_bind(part)
+                        .push(") = ")
+                        .push_bind(part);
+                }
+                builder.push(")");
+            }
+        }
+    }
+}
+
+/// Splits a whole-bitset mask into (SQL value, mask) pairs, skipping zero parts. Words 0 and 1
+/// are the bits1 / bits2 columns. SQLite cannot read an integer out of a blob, so each extended
+/// word is matched one hex digit of `hex(extra_bits)` at a time; digits past its end read as 0.
+fn feature_bits_parts(mask: &[u64]) -> Vec<(String, i64)> {
+    let mut parts = Vec::new();
+    for (word, column) in mask.iter().zip(["src.fixed_bits1", "src.fixed_bits2"]) {
+        if *word != 0 {
+            parts.push((column.to_string(), *word as i64));
+        }
+    }
+    let extra = bitset::encode_extra(mask);
+    for (index, byte) in extra.iter().enumerate() {
+        // The high digit of byte k comes first in the hex text, at position 2k + 1
+        for (position, digit) in [(2 * index + 1, byte >> 4), (2 * index + 2, byte & 0xF)] {
+            if digit != 0 {
+                let value = format!(
+                    "(instr('0123456789ABCDEF', substr(hex(src.extra_bits), {}, 1)) - 1)",
+                    position
+                );
+                parts.push((value, i64::from(digit)));
+            }
+        }
+    }
+    parts
+}
+
+fn check_mask_width(field: &str, words: &[u64]) -> Result<(), Status> {
+    if words.len() > bitset::MAX_WORDS {
+        return Err(invalid_argument(field, format!("must have at most {} words", bitset::MAX_WORDS)));
+    }
+    Ok(())
+}
+
+/// A pull over one table, live or reconstructed at `as_of`, with its filters.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PullQuery {
+    table: PullTable,
+    as_of: Option<String>,
+    filters: Vec<PullFilter>,
+}
+
+impl PullQuery {
+    pub(crate) fn new(table: PullTable) -> Self {
+        Self { table, as_of: None, filters: Vec::new() }
+    }
+
+    /// Reads the table as it was at this RFC3339 time instead of its current contents.
+    pub(crate) fn at(mut self, as_of: Option<String>) -> Self {
+        self.as_of = as_of;
+        self
+    }
+
+    pub(crate) fn filter(mut self, filter: PullFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Builds the query for up to `limit` rows after revision `after`, in revision order.
+    fn build(&self, after: Option<i64>, limit: i64) -> QueryBuilder<'static, Sqlite> {
+        let mut builder = QueryBuilder::new("SELECT src.* FROM (");
+        match &self.as_of {
+            Some(as_of) => {
+                let (head, tail) = self.table.as_of_source();
+                builder.push(head).push_bind(as_of.clone()).push(tail);
+            }
+            None => {
+                builder.push(self.table.live_source());
+            }
+        }
+        builder.push(") AS src WHERE 1 = 1");
+
+        for filter in &self.filters {
+            builder.push(" AND ");
+            filter.push_sql(self.table, &mut builder);
+        }
+
+        if let Some(after) = after {
+            builder.push(" AND src.pull_revision > ").push_bind(after);
+        }
+
+        builder.push(" ORDER BY src.pull_revision LIMIT ").push_bind(limit);
+
+        builder
+    }
+
+    /// SHA-256 of the query's serialized form, hex encoded
+    fn fingerprint(&self) -> String {
+        let serialized = serde_json::to_vec(self).unwrap_or_default();
+        format!("{:x}", Sha256::digest(serialized))
+    }
+
+    async fn fetch_chunk(
+        &self,
+        pool: &SqlitePool,
+        after: Option<i64>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<(i64, SqliteRow)>> {
+        let rows = self.build(after, limit).build().fetch_all(pool).await?;
+
+        Ok(rows.into_iter().map(|row| (row.get("pull_revision"), row)).collect())
+    }
+}
+
+/// The validated parts of a PullRequest shared by every pull RPC.
+pub(crate) struct PullParams {
+    as_of: Option<String>,
+    filters: Vec<PullFilter>,
+    pub(crate) limit: Option<i64>,
+}
+
+impl PullParams {
+    pub(crate) fn from_request(request: &PullRequest) -> Result<Self, Status> {
+        let since = request
+            .since
+            .as_ref()
+            .map(|ts| timestamp_from_proto(Some(ts), "since"))
+            .transpose()
+            .map_err(errors::to_status)?;
+        let as_of = request
+            .as_of
+            .as_ref()
+            .map(|ts| timestamp_from_proto(Some(ts), "as_of"))
+            .transpose()
+            .map_err(errors::to_status)?;
+        if let (Some(since), Some(as_of)) = (since, as_of) {
+            if since > as_of {
+                return Err(invalid_argument("since", "must not be after as_of"));
+            }
+        }
+
+        let limit = match request.limit {
+            None => None,
+            Some(limit) if limit < 0 => return Err(invalid_argument("limit", "must not be negative")),
+            Some(limit) if i64::from(limit) > MAX_PULL_LIMIT => {
+                return Err(invalid_argument(
+                    "limit",
+                    format!("must not exceed {}; use page_token to read further", MAX_PULL_LIMIT),
+                ))
+            }
+            Some(limit) => Some(i64::from(limit)),
+        };
+
+        let mut filters = Vec::new();
+        if let Some(since) = &since {
+            filters.push(PullFilter::ChangedSince(timestamp::format(since)));
+        }
+        if request.pronunciations.len() > MAX_PULL_PRONUNCIATIONS {
+            return Err(invalid_argument(
+                "pronunciations",
+                format!("at most {} pronunciations per pull", MAX_PULL_PRONUNCIATIONS),
+            ));
+        }
+        if !request.pronunciations.is_empty() {
+            let keys = request
+                .pronunciations
+                .iter()
+                .map(|raw| pronunciation_key("pronunciations", raw))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(errors::to_status)?;
+            filters.push(PullFilter::Pronunciations(keys));
+        }
+        if let Some(prefix) = &request.pronunciation_prefix {
+            let prefix = pronunciation_prefix("pronunciation_prefix", prefix).map_err(errors::to_status)?;
+            filters.push(PullFilter::PronunciationPrefix(prefix));
+        }
+        if let Some(text) = &request.note_contains {
+            if text.is_empty() {
+                return Err(invalid_argument("note_contains", "must not be empty"));
+            }
+            filters.push(PullFilter::NoteContains(text.clone()));
+        }
+        let bit_masks = [
+            (BitColumn::FixedBits1, &request.fixed_bits1),
+            (BitColumn::FixedBits2, &request.fixed_bits2),
+            (BitColumn::FixedBurstBits, &request.fixed_burst_bits),
+        ];
+        if let Some(FeatureBitsMask { any, all }) = &request.feature_bits {
+            if let Some(FeatureBitset { words }) = any {
+                check_mask_width("feature_bits.any", words)?;
+                if words.iter().all(|word| *word == 0) {
+                    return Err(invalid_argument("feature_bits.any", "must have a bit set"));
+                }
+                filters.push(PullFilter::AnyFeatureBits(words.clone()));
+            }
+            if let Some(FeatureBitset { words }) = all {
+                check_mask_width("feature_bits.all", words)?;
+                filters.push(PullFilter::AllFeatureBits(words.clone()));
+            }
+        }
+        for (column, mask) in bit_masks {
+            if let Some(BitMask { any, all }) = mask {
+                if let Some(any) = any {
+                    if *any == 0 {
+                        return Err(invalid_argument(format!("{}.any", column.name()), "must not be 0"));
+                    }
+                    filters.push(PullFilter::AnyBits(column, *any));
+                }
+                if let Some(all) = all {
+                    filters.push(PullFilter::AllBits(column, *all));
+                }
+            }
+        }
+
+        Ok(Self {
+            as_of: as_of.as_ref().map(timestamp::format),
+            filters,
+            limit,
+        })
+    }
+
+    /// Builds the query these parameters describe for one table, rejecting filters on
+    /// columns the table does not have.
+    pub(crate) fn query(&self, table: PullTable) -> Result<PullQuery, Status> {
+        let mut query = PullQuery::new(table).at(self.as_of.clone());
+        for filter in &self.filters {
+            if !filter.applies_to(table) {
+                return Err(invalid_argument(filter.field(), "is not supported by this pull"));
+            }
+            query = query.filter(filter.clone());
+        }
+        Ok(query)
+    }
+}
+
+/// Decoded form of a page_token: the pull_revision of the last row sent, and a hash of the
+/// whole query so a token cannot be replayed against a different table, `as_of` or set of
+/// filters. The hash keeps the token small however many pronunciations the filters list.
+#[derive(Serialize, Deserialize)]
+struct PageToken {
+    version: u8,
+    query: String,
+    after: i64,
+}
+
+/// A pull query and the revision it resumes after.
+pub(crate) struct Page {
+    query: PullQuery,
+    after: Option<i64>,
+}
+
+impl Page {
+    /// Starts at the beginning, or after the position in `page_token` if one is given.
+    pub(crate) fn start(query: PullQuery, page_token: Option<&str>) -> Result<Self, Status> {
+        let mut page = Self { query, after: None };
+
+        if let Some(page_token) = page_token.filter(|token| !token.is_empty()) {
+            let token: PageToken = URL_SAFE_NO_PAD
+                .decode(page_token)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .ok_or_else(|| invalid_argument("page_token", "is not a token returned by this server"))?;
+
+            if token.version != PAGE_TOKEN_VERSION || token.query != page.query.fingerprint() {
+                return Err(invalid_argument(
+                    "page_token",
+                    "does not match this request; repeat the parameters of the first page",
+                ));
+            }
+            page.after = Some(token.after);
+        }
+
+        Ok(page)
+    }
+
+    fn next_page_token(&self, after: i64) -> Option<String> {
+        let token = PageToken {
+            version: PAGE_TOKEN_VERSION,
+            query: self.query.fingerprint(),
+            after,
+        };
+        serde_json::to_vec(&token).ok().map(|bytes| URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+/// Streams a pull to the client one chunk at a time, converting rows with `convert`.
+///
+/// The next chunk is only queried once the previous one has been handed to the channel, so a
+/// slow client holds at most one chunk plus the channel buffer in memory. The task stops as
+/// soon as the client goes away, abandoning any query in flight.
+///
+/// When rows remain after `limit` of them have been sent, the stream ends with an OK status
+/// whose trailers carry the page_token to continue from. The streams carry table rows with no
+/// room for the token, so it goes out as `Err(Status)` with `Code::Ok`: tonic writes an item
+/// error as the closing status and its metadata as trailers, and clients see a normal end of
+/// stream. Pinned by `next_page_token_reaches_the_client_as_a_trailer` in server.rs.
+pub(crate) fn stream_pull<T, F>(
+    pool: SqlitePool,
+    page: Page,
+    limit: Option<i64>,
+    convert: F,
+) -> ReceiverStream<Result<T, Status>>
+where
+    T: Send + 'static,
+    F: Fn(&SqliteRow) -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(PULL_CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        let mut position = page.after;
+        let mut remaining = limit;
+
+        'chunks: loop {
+            // On the chunk that reaches the limit, one extra row tells whether another page exists
+            let chunk_size = match remaining {
+                Some(remaining) if remaining < PULL_CHUNK_SIZE => remaining + 1,
+                _ => PULL_CHUNK_SIZE,
+            };
+
+            let rows = tokio::select! {
+                _ = tx.closed() => return,
+                rows = page.query.fetch_chunk(&pool, position, chunk_size) => rows,
+            };
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => {
+                    let _ = tx.send(Err(Status::internal(format!("Database error: {}", e)))).await;
+                    return;
+                }
+            };
+
+            let exhausted = (rows.len() as i64) < chunk_size;
+            for (row_position, row) in rows {
+                if remaining == Some(0) {
+                    break 'chunks;
+                }
+                if tx.send(Ok(convert(&row))).await.is_err() {
+                    return;
+                }
+                position = Some(row_position);
+                if let Some(remaining) = remaining.as_mut() {
+                    *remaining -= 1;
+                }
+            }
+            if exhausted {
+                return;
+            }
+        }
+
+        // Stopped at the limit: hand the client a token for the rest
+        if let Some(token) = position.and_then(|position| page.next_page_token(position)) {
+            let mut trailers = MetadataMap::new();
+            if let Ok(value) = token.parse() {
+                trailers.insert(NEXT_PAGE_TOKEN_TRAILER, value);
+                // Not an error: this becomes the stream's OK status, see above
+                let _ = tx.send(Err(Status::with_metadata(Code::Ok, "", trailers))).await;
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::apply_feature_override;
+    use crate::server::proto::FeatureOverride;
+    use prost_types::Timestamp;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tokio_stream::StreamExt;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn put_override(pool: &SqlitePool, pronunciation: &str, words: Vec<u64>) {
+        let feature_override = FeatureOverride {
+            pronunciation: pronunciation.to_string(),
+            feature_bits: Some(FeatureBitset { words }),
+            ..Default::default()
+        };
+        let mut conn = pool.acquire().await.unwrap();
+        apply_feature_override(&mut conn, &feature_override, "test").await.unwrap();
+    }
+
+    async fn pull_overrides(pool: &SqlitePool, request: PullRequest) -> Vec<String> {
+        let query = PullParams::from_request(&request).unwrap().query(PullTable::FeatureOverride).unwrap();
+        let rows = query.fetch_chunk(pool, None, MAX_PULL_LIMIT).await.unwrap();
+        rows.iter().map(|(_, row)| row.get("pronunciation")).collect()
+    }
+
+    #[tokio::test]
+    async fn as_of_reconstructs_across_a_delete() {
+        let pool = test_pool().await;
+        put_override(&pool, "ア", vec![1]).await;
+        put_override(&pool, "イ", vec![1]).await;
+        crate::history::delete_override(&pool, "ア", "test").await.unwrap();
+        put_override(&pool, "ア", vec![2]).await;
+
+        // Spread the four versions over three days
+        for (version, changed_at) in [
+            (1, "2024-01-01T00:00:00.000Z"),
+            (2, "2024-01-01T00:00:00.000Z"),
+            (3, "2024-01-02T00:00:00.000Z"),
+            (4, "2024-01-03T00:00:00.000Z"),
+        ] {
+            sqlx::query("UPDATE card_feature_override_history SET changed_at = ? WHERE version = ?")
+                .bind(changed_at)
+                .bind(version)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let pull_at = |as_of: &str| {
+            let query = PullQuery::new(PullTable::FeatureOverride).at(Some(as_of.to_string()));
+            let pool = pool.clone();
+            async move {
+                let rows = query.fetch_chunk(&pool, None, MAX_PULL_LIMIT).await.unwrap();
+                rows.iter()
+                    .map(|(_, row)| {
+                        let pronunciation: String = row.get("pronunciation");
+                        let bits1: i64 = row.get("fixed_bits1");
+                        let created_at: String = row.get("created_at");
+                        (pronunciation, bits1, created_at)
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        let row = |key: &str, bits1, created_at: &str| (key.to_string(), bits1, created_at.to_string());
+
+        assert!(pull_at("2023-12-31T00:00:00.000Z").await.is_empty());
+        assert_eq!(
+            pull_at("2024-01-01T12:00:00.000Z").await,
+            [row("ア", 1, "2024-01-01T00:00:00.000Z"), row("イ", 1, "2024-01-01T00:00:00.000Z")]
+        );
+        assert_eq!(pull_at("2024-01-02T12:00:00.000Z").await, [row("イ", 1, "2024-01-01T00:00:00.000Z")]);
+        // Created again after the delete, so created_at is the second create
+        assert_eq!(
+            pull_at("2024-01-03T00:00:00.000Z").await,
+            [row("イ", 1, "2024-01-01T00:00:00.000Z"), row("ア", 2, "2024-01-03T00:00:00.000Z")]
+        );
+    }
+
+    fn feature_bits(any: Option<Vec<u64>>, all: Option<Vec<u64>>) -> PullRequest {
+        PullRequest {
+            feature_bits: Some(FeatureBitsMask {
+                any: any.map(|words| FeatureBitset { words }),
+                all: all.map(|words| FeatureBitset { words }),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn feature_bits_mask_reads_extended_words() {
+        let pool = test_pool().await;
+        put_override(&pool, "ア", vec![1]).await;
+        put_override(&pool, "イ", vec![0, 0, 1]).await;
+        put_override(&pool, "ウ", vec![0, 0, 0xA50, 0, 1 << 63]).await;
+        put_override(&pool, "エ", vec![3, 0, 0xF0]).await;
+
+        assert_eq!(pull_overrides(&pool, feature_bits(Some(vec![0, 0, 1]), None)).await, ["イ"]);
+        assert_eq!(pull_overrides(&pool, feature_bits(Some(vec![0, 0, 0, 0, 1 << 63]), None)).await, ["ウ"]);
+        assert_eq!(pull_overrides(&pool, feature_bits(Some(vec![2, 0, 0x10]), None)).await, ["ウ", "エ"]);
+        assert_eq!(pull_overrides(&pool, feature_bits(None, Some(vec![0, 0, 0xA50]))).await, ["ウ"]);
+        assert!(pull_overrides(&pool, feature_bits(None, Some(vec![0, 0, 0xA51]))).await.is_empty());
+        assert_eq!(pull_overrides(&pool, feature_bits(None, Some(vec![1, 0, 0x30]))).await, ["エ"]);
+        assert_eq!(pull_overrides(&pool, feature_bits(None, Some(vec![]))).await.len(), 4);
+    }
+
+    #[test]
+    fn feature_bits_mask_is_validated() {
+        let invalid = |request: PullRequest| PullParams::from_request(&request).err().map(|status| status.code());
+        assert_eq!(invalid(feature_bits(Some(vec![0, 0, 0]), None)), Some(Code::InvalidArgument));
+        assert_eq!(invalid(feature_bits(None, Some(vec![0; bitset::MAX_WORDS + 1]))), Some(Code::InvalidArgument));
+        assert_eq!(invalid(feature_bits(None, Some(vec![0; bitset::MAX_WORDS]))), None);
+    }
+
+    fn rejected_field(request: PullRequest) -> Option<String> {
+        PullParams::from_request(&request).err().map(|status| {
+            assert_eq!(status.code(), Code::InvalidArgument);
+            status.message().split(':').next().unwrap_or_default().to_string()
+        })
+    }
+
+    fn at(seconds: i64, nanos: i32) -> Option<Timestamp> {
+        Some(Timestamp { seconds, nanos })
+    }
+
+    #[test]
+    fn timestamps_must_be_in_range() {
+        let since = |seconds, nanos| PullRequest { since: at(seconds, nanos), ..Default::default() };
+        let as_of = |seconds, nanos| PullRequest { as_of: at(seconds, nanos), ..Default::default() };
+
+        assert_eq!(rejected_field(since(253_402_300_799, 999_999_999)), None);
+        assert_eq!(rejected_field(as_of(-62_135_596_800, 0)), None);
+        assert_eq!(rejected_field(since(253_402_300_800, 0)).as_deref(), Some("since"));
+        assert_eq!(rejected_field(as_of(-62_135_596_801, 0)).as_deref(), Some("as_of"));
+        assert_eq!(rejected_field(since(0, -1)).as_deref(), Some("since"));
+        assert_eq!(rejected_field(as_of(0, 1_000_000_000)).as_deref(), Some("as_of"));
+    }
+
+    #[test]
+    fn since_must_not_be_after_as_of() {
+        assert_eq!(rejected_field(PullRequest { since: at(100, 0), as_of: at(100, 0), ..Default::default() }), None);
+        assert_eq!(
+            rejected_field(PullRequest { since: at(100, 1), as_of: at(100, 0), ..Default::default() }).as_deref(),
+            Some("since")
+        );
+    }
+
+    #[test]
+    fn limit_is_capped() {
+        let limit = |limit: i64| PullRequest { limit: Some(i32::try_from(limit).unwrap()), ..Default::default() };
+        assert_eq!(PullParams::from_request(&limit(MAX_PULL_LIMIT)).unwrap().limit, Some(MAX_PULL_LIMIT));
+        assert_eq!(PullParams::from_request(&limit(0)).unwrap().limit, Some(0));
+        assert_eq!(rejected_field(limit(MAX_PULL_LIMIT + 1)).as_deref(), Some("limit"));
+        assert_eq!(rejected_field(limit(-1)).as_deref(), Some("limit"));
+        assert_eq!(PullParams::from_request(&PullRequest::default()).unwrap().limit, None);
+    }
+
+    #[test]
+    fn page_token_round_trips() {
+        let query = PullQuery::new(PullTable::FeatureOverride).filter(PullFilter::NoteContains("x".to_string()));
+        let token = Page::start(query.clone(), None).unwrap().next_page_token(42).unwrap();
+
+        assert_eq!(Page::start(query.clone(), Some(&token)).unwrap().after, Some(42));
+        assert_eq!(Page::start(query, Some("")).unwrap().after, None);
+    }
+
+    #[test]
+    fn page_token_rejects_another_query() {
+        let query = PullQuery::new(PullTable::FeatureOverride).filter(PullFilter::NoteContains("x".to_string()));
+        let token = Page::start(query.clone(), None).unwrap().next_page_token(42).unwrap();
+        let rejected = |query: PullQuery, token: &str| {
+            Page::start(query, Some(token)).err().map(|status| status.code()) == Some(Code::InvalidArgument)
+        };
+
+        let other_note = PullQuery::new(PullTable::FeatureOverride).filter(PullFilter::NoteContains("y".to_string()));
+        assert!(rejected(other_note, &token));
+        assert!(rejected(PullQuery::new(PullTable::ConfirmedFeature), &token));
+        assert!(rejected(query.clone().at(Some("2024-01-01T00:00:00Z".to_string())), &token));
+        assert!(rejected(query.clone(), "not a token"));
+
+        let old_version = PageToken {
+            version: PAGE_TOKEN_VERSION - 1,
+            query: query.fingerprint(),
+            after: 42,
+        };
+        assert!(rejected(query, &URL_SAFE_NO_PAD.encode(serde_json::to_vec(&old_version).unwrap())));
+    }
+
+    async fn pull_page(pool: &SqlitePool, page: Page, limit: i64) -> (Vec<String>, Option<String>) {
+        let mut stream = stream_pull(pool.clone(), page, Some(limit), |row| row.get::<String, _>("pronunciation"));
+        let mut rows = Vec::new();
+        let mut token = None;
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(pronunciation) => rows.push(pronunciation),
+                Err(status) => {
+                    assert_eq!(status.code(), Code::Ok);
+                    token = status.metadata().get(NEXT_PAGE_TOKEN_TRAILER).map(|v| v.to_str().unwrap().to_string());
+                }
+            }
+        }
+        (rows, token)
+    }
+
+    #[tokio::test]
+    async fn pages_continue_from_the_token() {
+        let pool = test_pool().await;
+        for pronunciation in ["ア", "イ", "ウ"] {
+            put_override(&pool, pronunciation, vec![1]).await;
+        }
+        let query = PullQuery::new(PullTable::FeatureOverride);
+
+        let (first, token) = pull_page(&pool, Page::start(query.clone(), None).unwrap(), 2).await;
+        assert_eq!(first, ["ア", "イ"]);
+        let token = token.expect("a token for the rest");
+
+        // A row rewritten between pages moves past the token's position
+        put_override(&pool, "ア", vec![2]).await;
+        let (rest, token) = pull_page(&pool, Page::start(query, Some(&token)).unwrap(), 2).await;
+        assert_eq!(rest, ["ウ", "ア"]);
+        assert_eq!(token, None);
+    }
+}
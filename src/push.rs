@@ -0,0 +1,581 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection};
+
+use crate::errors::push_error;
+use crate::history::{self, CardValues, ChangeRecord, OverrideValues, RuleValues};
+use crate::server::proto::*;
+use crate::server::{card_values, override_values, AppliedChange};
+
+/// Items written per transaction by PushFeatureOverrides / PushRulePatterns / PushCards.
+/// 500 rows of 8 columns stay well below SQLite's bound parameter limit.
+pub(crate) const PUSH_CHUNK_SIZE: usize = 500;
+
+/// A pushed item that passed validation, with its timestamps in storage form.
+pub(crate) struct StampedItem<T> {
+    pub item: T,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+enum PushEntry<T> {
+    /// Key, item and the warnings to report if it is written
+    Valid(String, StampedItem<T>, Vec<String>),
+    /// An item that will not be written: rejected, or in conflict with another item
+    Decided(PushItemResult),
+}
+
+/// Items of a push waiting to be written, kept in arrival order so results are reported in
+/// the order the client sent them.
+pub(crate) struct PushChunk<T> {
+    entries: Vec<PushEntry<T>>,
+    keys: HashSet<String>,
+}
+
+impl<T> PushChunk<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            keys: HashSet::new(),
+        }
+    }
+
+    /// True when the chunk is full and must be written before another item joins it.
+    pub(crate) fn is_full(&self) -> bool {
+        self.keys.len() >= PUSH_CHUNK_SIZE
+    }
+
+    /// Adds an item to write. The caller reports a repeated key with [`Self::duplicate`]
+    /// instead, so keys are unique within a chunk.
+    pub(crate) fn push(&mut self, key: String, item: StampedItem<T>, warnings: Vec<String>) {
+        debug_assert!(!self.keys.contains(&key), "duplicate key in push chunk");
+        self.keys.insert(key.clone());
+        self.entries.push(PushEntry::Valid(key, item, warnings));
+    }
+
+    pub(crate) fn reject(&mut self, key: String, error: &anyhow::Error) {
+        self.entries.push(PushEntry::Decided(rejected_item(key, error)));
+    }
+
+    /// Records an item whose key appeared earlier in the same push. Only the first one is written.
+    pub(crate) fn duplicate(&mut self, key: String, reason: &str) {
+        self.entries.push(PushEntry::Decided(conflict_item(
+            key,
+            PushErrorCode::DuplicateItem,
+            reason.to_string(),
+        )));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The valid items, in order, to hand to a chunk writer.
+    pub(crate) fn items(&self) -> Vec<&StampedItem<T>> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                PushEntry::Valid(_, item, _) => Some(item),
+                PushEntry::Decided(_) => None,
+            })
+            .collect()
+    }
+
+    /// Empties the chunk and returns one result per item, with the change to publish for
+    /// each item that was written. When the write failed, every valid item is rejected.
+    pub(crate) fn finish(
+        &mut self,
+        applied: Result<Vec<AppliedChange>, anyhow::Error>,
+    ) -> Vec<(PushItemResult, Option<ChangeRecord>)> {
+        self.keys.clear();
+        let entries = std::mem::take(&mut self.entries);
+
+        match applied {
+            Ok(applied) => {
+                let mut applied = applied.into_iter();
+                entries
+                    .into_iter()
+                    .map(|entry| match entry {
+                        PushEntry::Decided(result) => (result, None),
+                        PushEntry::Valid(key, _, warnings) => {
+                            let applied = applied.next().expect("one applied change per chunk item");
+                            let result = PushItemResult {
+                                warnings,
+                                ..applied_result(key, &applied)
+                            };
+                            (result, applied.change)
+                        }
+                    })
+                    .collect()
+            }
+            Err(e) => entries
+                .into_iter()
+                .map(|entry| match entry {
+                    PushEntry::Decided(result) => (result, None),
+                    PushEntry::Valid(key, _, _) => (rejected_item(key, &e), None),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Writes a chunk of overrides with one lookup and one upsert, then records history for the
+/// rows whose values changed. An existing override keeps its created_at. Items older than the
+/// stored row are left out of the upsert and reported as stale. Keys must be unique within
+/// the chunk.
+pub(crate) async fn apply_feature_override_chunk(
+    conn: &mut SqliteConnection,
+    items: &[&StampedItem<FeatureOverride>],
+    actor: &str,
+) -> Result<Vec<AppliedChange>, anyhow::Error> {
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut lookup = QueryBuilder::<Sqlite>::new(
+        "SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, note, extra_bits, updated_at
+         FROM card_feature_override WHERE pronunciation IN (",
+    );
+    let mut keys = lookup.separated(", ");
+    for stamped in items {
+        keys.push_bind(&stamped.item.pronunciation);
+    }
+    lookup.push(")");
+
+    let mut stored_updated_at = HashMap::new();
+    let existing: HashMap<String, OverrideValues> = lookup
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| {
+            let values = OverrideValues {
+                fixed_bits1: row.get("fixed_bits1"),
+                fixed_bits2: row.get("fixed_bits2"),
+                fixed_burst_bits: row.get("fixed_burst_bits"),
+                note: row.get("note"),
+                extra_bits: row.get("extra_bits"),
+            };
+            stored_updated_at.insert(row.get("pronunciation"), row.get::<String, _>("updated_at"));
+            (row.get("pronunciation"), values)
+        })
+        .collect();
+
+    let stale = stale_reasons(items, &stored_updated_at, |item| item.pronunciation.clone());
+    let new_values: Vec<OverrideValues> = items
+        .iter()
+        .map(|stamped| {
+            let stored = existing.get(&stamped.item.pronunciation);
+            override_values(&stamped.item, stored.map(|old| old.extra_bits.as_slice()))
+        })
+        .collect();
+
+    // Rows identical to what is stored, or older than it, are skipped by the WHERE clause, so
+    // RETURNING lists exactly the rows that were inserted or updated.
+    let mut upsert = QueryBuilder::<Sqlite>::new(
+        "INSERT INTO card_feature_override
+         (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note, extra_bits) ",
+    );
+    upsert.push_values(items.iter().zip(&new_values), |mut row, (stamped, values)| {
+        row.push_bind(&stamped.item.pronunciation)
+            .push_bind(values.fixed_bits1)
+            .push_bind(values.fixed_bits2)
+            .push_bind(values.fixed_burst_bits)
+            .push_bind(&stamped.created_at)
+            .push_bind(&stamped.updated_at)
+            .push_bind(&values.note)
+            .push_bind(&values.extra_bits);
+    });
+    upsert.push(
+        " ON CONFLICT(pronunciation) DO UPDATE SET
+             fixed_bits1 = excluded.fixed_bits1,
+             fixed_bits2 = excluded.fixed_bits2,
+             fixed_burst_bits = excluded.fixed_burst_bits,
+             updated_at = excluded.updated_at,
+             note = excluded.note,
+             extra_bits = excluded.extra_bits
+         WHERE updated_at <= excluded.updated_at
+           AND (fixed_bits1, fixed_bits2, fixed_burst_bits, updated_at, note, extra_bits)
+             IS NOT (excluded.fixed_bits1, excluded.fixed_bits2, excluded.fixed_burst_bits,
+                     excluded.updated_at, excluded.note, excluded.extra_bits)
+         RETURNING pronunciation",
+    );
+
+    let written: HashSet<String> = upsert
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("pronunciation"))
+        .collect();
+
+    let mut applied = Vec::with_capacity(items.len());
+    for ((stamped, new_values), stale) in items.iter().zip(&new_values).zip(stale) {
+        let item = &stamped.item;
+        let old = existing.get(&item.pronunciation);
+        if let Some(reason) = stale {
+            applied.push(AppliedChange::stale(reason));
+            continue;
+        }
+        if !written.contains(&item.pronunciation) {
+            applied.push(AppliedChange::new(true, None));
+            continue;
+        }
+
+        let change =
+            history::record_override_change(conn, &item.pronunciation, actor, old, Some(new_values), None).await?;
+        applied.push(AppliedChange::new(old.is_some(), change));
+    }
+
+    Ok(applied)
+}
+
+/// Writes a chunk of rule patterns like [`apply_feature_override_chunk`]. An existing rule
+/// keeps its created_at. (keyword, pattern) pairs must be unique within the chunk.
+pub(crate) async fn apply_rule_pattern_chunk(
+    conn: &mut SqliteConnection,
+    items: &[&StampedItem<RulePattern>],
+    actor: &str,
+) -> Result<Vec<AppliedChange>, anyhow::Error> {
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut lookup = QueryBuilder::<Sqlite>::new(
+        "SELECT keyword, pattern, feature_name, is_enabled, updated_at
+         FROM rule_pattern WHERE (keyword, pattern) IN (VALUES ",
+    );
+    let mut keys = lookup.separated(", ");
+    for stamped in items {
+        keys.push("(")
+            .push_bind_unseparated(&stamped.item.keyword)
+            .push_unseparated(", ")
+            .push_bind_unseparated(&stamped.item.pattern)
+            .push_unseparated(")");
+    }
+    lookup.push(")");
+
+    let mut stored_updated_at = HashMap::new();
+    let existing: HashMap<(String, String), RuleValues> = lookup
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| {
+            let values = RuleValues {
+                feature_name: row.get("feature_name"),
+                is_enabled: row.get("is_enabled"),
+            };
+            let key = (row.get("keyword"), row.get("pattern"));
+            stored_updated_at.insert(key.clone(), row.get::<String, _>("updated_at"));
+            (key, values)
+        })
+        .collect();
+    let stale = stale_reasons(items, &stored_updated_at, |item| (item.keyword.clone(), item.pattern.clone()));
+
+    let mut upsert = QueryBuilder::<Sqlite>::new(
+        "INSERT INTO rule_pattern (keyword, pattern, feature_name, is_enabled, created_at, updated_at) ",
+    );
+    upsert.push_values(items, |mut row, stamped| {
+        let item = &stamped.item;
+        row.push_bind(&item.keyword)
+            .push_bind(&item.pattern)
+            .push_bind(&item.feature_name)
+            .push_bind(item.is_enabled)
+            .push_bind(&stamped.created_at)
+            .push_bind(&stamped.updated_at);
+    });
+    upsert.push(
+        " ON CONFLICT(keyword, pattern) DO UPDATE SET
+             feature_name = excluded.feature_name,
+             is_enabled = excluded.is_enabled,
+             updated_at = excluded.updated_at
+         WHERE updated_at <= excluded.updated_at
+           AND (feature_name, is_enabled, updated_at)
+             IS NOT (excluded.feature_name, excluded.is_enabled, excluded.updated_at)
+         RETURNING keyword, pattern",
+    );
+
+    let written: HashSet<(String, String)> = upsert
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| (row.get("keyword"), row.get("pattern")))
+        .collect();
+
+    let mut applied = Vec::with_capacity(items.len());
+    for (stamped, stale) in items.iter().zip(stale) {
+        let item = &stamped.item;
+        let key = (item.keyword.clone(), item.pattern.clone());
+        let old = existing.get(&key);
+        if let Some(reason) = stale {
+            applied.push(AppliedChange::stale(reason));
+            continue;
+        }
+        if !written.contains(&key) {
+            applied.push(AppliedChange::new(true, None));
+            continue;
+        }
+
+        let new_values = RuleValues {
+            feature_name: item.feature_name.clone(),
+            is_enabled: item.is_enabled,
+        };
+        let change =
+            history::record_rule_change(conn, &item.keyword, &item.pattern, actor, old, Some(&new_values)).await?;
+        applied.push(AppliedChange::new(old.is_some(), change));
+    }
+
+    Ok(applied)
+}
+
+/// Writes a chunk of cards like [`apply_feature_override_chunk`]. An existing card keeps its
+/// created_at. Pronunciations must be unique within the chunk.
+pub(crate) async fn apply_card_chunk(
+    conn: &mut SqliteConnection,
+    items: &[&StampedItem<Card>],
+    actor: &str,
+) -> Result<Vec<AppliedChange>, anyhow::Error> {
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut lookup = QueryBuilder::<Sqlite>::new(
+        "SELECT pronunciation, name, card_number, set_code, card_text, burst_text, updated_at
+         FROM card WHERE pronunciation IN (",
+    );
+    let mut keys = lookup.separated(", ");
+    for stamped in items {
+        keys.push_bind(&stamped.item.pronunciation);
+    }
+    lookup.push(")");
+
+    let mut stored_updated_at = HashMap::new();
+    let existing: HashMap<String, CardValues> = lookup
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| {
+            let values = CardValues {
+                name: row.get("name"),
+                card_number: row.get("card_number"),
+                set_code: row.get("set_code"),
+                card_text: row.get("card_text"),
+                burst_text: row.get("burst_text"),
+            };
+            stored_updated_at.insert(row.get("pronunciation"), row.get::<String, _>("updated_at"));
+            (row.get("pronunciation"), values)
+        })
+        .collect();
+    let stale = stale_reasons(items, &stored_updated_at, |item| item.pronunciation.clone());
+
+    let mut upsert = QueryBuilder::<Sqlite>::new(
+        "INSERT INTO card (pronunciation, name, card_number, set_code, card_text, burst_text, created_at, updated_at) ",
+    );
+    upsert.push_values(items, |mut row, stamped| {
+        let item = &stamped.item;
+        row.push_bind(&item.pronunciation)
+            .push_bind(&item.name)
+            .push_bind(&item.card_number)
+            .push_bind(&item.set_code)
+            .push_bind(&item.card_text)
+            .push_bind(&item.burst_text)
+            .push_bind(&stamped.created_at)
+            .push_bind(&stamped.updated_at);
+    });
+    upsert.push(
+        " ON CONFLICT(pronunciation) DO UPDATE SET
+             name = excluded.name,
+             card_number = excluded.card_number,
+             set_code = excluded.set_code,
+             card_text = excluded.card_text,
+             burst_text = excluded.burst_text,
+             updated_at = excluded.updated_at
+         WHERE updated_at <= excluded.updated_at
+           AND (name, card_number, set_code, card_text, burst_text, updated_at)
+             IS NOT (excluded.name, excluded.card_number, excluded.set_code,
+                     excluded.card_text, excluded.burst_text, excluded.updated_at)
+         RETURNING pronunciation",
+    );
+
+    let written: HashSet<String> = upsert
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("pronunciation"))
+        .collect();
+
+    let mut applied = Vec::with_capacity(items.len());
+    for (stamped, stale) in items.iter().zip(stale) {
+        let item = &stamped.item;
+        let old = existing.get(&item.pronunciation);
+        if let Some(reason) = stale {
+            applied.push(AppliedChange::stale(reason));
+            continue;
+        }
+        if !written.contains(&item.pronunciation) {
+            applied.push(AppliedChange::new(true, None));
+            continue;
+        }
+
+        let change =
+            history::record_card_change(conn, &item.pronunciation, actor, old, Some(&card_values(item))).await?;
+        applied.push(AppliedChange::new(old.is_some(), change));
+    }
+
+    Ok(applied)
+}
+
+/// For each item, why it is older than the stored row, if it is. Timestamps are compared in
+/// their canonical storage form, where comparing the text compares the times.
+fn stale_reasons<T, K: Eq + std::hash::Hash>(
+    items: &[&StampedItem<T>],
+    stored_updated_at: &HashMap<K, String>,
+    key: impl Fn(&T) -> K,
+) -> Vec<Option<String>> {
+    items
+        .iter()
+        .map(|stamped| {
+            let stored = stored_updated_at.get(&key(&stamped.item))?;
+            stale_reason(stored, &stamped.updated_at)
+        })
+        .collect()
+}
+
+/// Describes the conflict when the stored row was updated after the pushed item. Both
+/// timestamps are in storage form.
+pub(crate) fn stale_reason(stored: &str, pushed: &str) -> Option<String> {
+    (stored > pushed).then(|| format!("stored row was updated at {}, after the pushed updated_at {}", stored, pushed))
+}
+
+/// The result of a chunk item: its change kind, or the conflict that kept it from being written.
+pub(crate) fn applied_result(key: String, applied: &AppliedChange) -> PushItemResult {
+    match &applied.conflict {
+        Some(reason) => conflict_item(key, PushErrorCode::StaleUpdate, reason.clone()),
+        None => applied_item(key, applied.kind),
+    }
+}
+
+pub(crate) fn applied_item(key: String, kind: ChangeKind) -> PushItemResult {
+    PushItemResult {
+        key,
+        outcome: kind as i32,
+        ..Default::default()
+    }
+}
+
+/// Whether retrying the push could give a different response. Only a final response is stored
+/// for its idempotency-key; otherwise the key is released so a retry runs again.
+pub(crate) fn is_final(response: &PushResponse) -> bool {
+    !response.results.iter().any(|result| result.retryable)
+}
+
+pub(crate) fn rejected_item(key: String, error: &anyhow::Error) -> PushItemResult {
+    let (error_code, field, retryable) = push_error(error);
+    PushItemResult {
+        key,
+        outcome: ChangeKind::Rejected as i32,
+        error_code: error_code as i32,
+        field,
+        message: Some(error.to_string()),
+        retryable,
+        warnings: Vec::new(),
+    }
+}
+
+/// An item left unwritten because it conflicts with the stored row or an earlier item.
+/// Retrying it unchanged gives the same conflict.
+pub(crate) fn conflict_item(key: String, error_code: PushErrorCode, reason: String) -> PushItemResult {
+    PushItemResult {
+        key,
+        outcome: ChangeKind::Conflict as i32,
+        error_code: error_code as i32,
+        message: Some(reason),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::SqlitePool;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    fn stamped(pronunciation: &str, bits: u64, updated_at: &str) -> StampedItem<FeatureOverride> {
+        StampedItem {
+            item: FeatureOverride {
+                pronunciation: pronunciation.to_string(),
+                fixed_bits1: bits as i64,
+                feature_bits: Some(FeatureBitset { words: vec![bits] }),
+                ..Default::default()
+            },
+            created_at: updated_at.to_string(),
+            updated_at: updated_at.to_string(),
+        }
+    }
+
+    async fn apply(pool: &SqlitePool, items: &[StampedItem<FeatureOverride>]) -> Vec<ChangeKind> {
+        let mut conn = pool.acquire().await.unwrap();
+        let items: Vec<_> = items.iter().collect();
+        let applied = apply_feature_override_chunk(&mut conn, &items, "test").await.unwrap();
+        applied.iter().map(|applied| applied.kind).collect()
+    }
+
+    #[tokio::test]
+    async fn override_chunk_skips_stale_and_identical_rows() {
+        const BEFORE: &str = "2023-12-31T00:00:00.000Z";
+        const FIRST: &str = "2024-01-01T00:00:00.000Z";
+        const LATER: &str = "2024-01-02T00:00:00.000Z";
+        let pool = test_pool().await;
+        let created = apply(&pool, &[stamped("ア", 1, FIRST), stamped("イ", 2, FIRST), stamped("エ", 3, FIRST)]).await;
+        assert_eq!(created, [ChangeKind::Created; 3]);
+
+        let applied = apply(
+            &pool,
+            &[stamped("ア", 5, BEFORE), stamped("イ", 2, FIRST), stamped("ウ", 4, LATER), stamped("エ", 6, LATER)],
+        )
+        .await;
+        assert_eq!(applied, [ChangeKind::Conflict, ChangeKind::Unchanged, ChangeKind::Created, ChangeKind::Updated]);
+
+        let rows: Vec<(String, i64, String, String)> = sqlx::query_as(
+            "SELECT pronunciation, fixed_bits1, created_at, updated_at FROM card_feature_override ORDER BY 1",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let row = |key: &str, bits1, created_at: &str, updated_at: &str| {
+            (key.to_string(), bits1, created_at.to_string(), updated_at.to_string())
+        };
+        assert_eq!(
+            rows,
+            [
+                row("ア", 1, FIRST, FIRST),
+                row("イ", 2, FIRST, FIRST),
+                row("ウ", 4, LATER, LATER),
+                row("エ", 6, FIRST, LATER),
+            ]
+        );
+
+        // History only for the rows that were written
+        let versions: Vec<String> =
+            sqlx::query_scalar("SELECT pronunciation FROM card_feature_override_history ORDER BY version")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(versions, ["ア", "イ", "エ", "ウ", "エ"]);
+    }
+}
@@ -0,0 +1,194 @@
+//! Reports comparing the override and the confirmation stored for each pronunciation.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+
+use crate::bitset;
+use crate::catalog::{BitField, FeatureCatalog};
+
+/// A whole bitset and its burst bits
+pub type Bits = (Vec<u64>, i64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscrepancyKind {
+    /// Both exist and their bits differ
+    BitsDiffer,
+    OverrideOnly,
+    ConfirmationOnly,
+}
+
+/// One bit set on one side only. A missing override or confirmation counts as no bits.
+pub struct BitDifference {
+    pub field: BitField,
+    pub index: usize,
+    /// From the feature catalog; None when the bit has no definition
+    pub feature_name: Option<String>,
+    pub in_override: bool,
+    pub in_confirmation: bool,
+}
+
+pub struct Discrepancy {
+    pub pronunciation: String,
+    pub kind: DiscrepancyKind,
+    /// None when the pronunciation has no override
+    pub override_bits: Option<Bits>,
+    pub confirmed_bits: Option<Bits>,
+    /// By field, then bit index
+    pub differences: Vec<BitDifference>,
+}
+
+/// Pronunciations whose override and confirmation disagree, or that have only one of them,
+/// in pronunciation order.
+pub async fn discrepancies(pool: &SqlitePool) -> Result<Vec<Discrepancy>> {
+    let catalog = FeatureCatalog::load(pool).await?;
+
+    let mut pairs: BTreeMap<String, (Option<Bits>, Option<Bits>)> = BTreeMap::new();
+    for row in sqlx::query!(
+        "SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, extra_bits FROM card_feature_override"
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        let bits = bitset::words(row.fixed_bits1, row.fixed_bits2, &row.extra_bits);
+        pairs.entry(row.pronunciation).or_default().0 = Some((bits, row.fixed_burst_bits));
+    }
+    for row in sqlx::query!(
+        "SELECT pronunciation, feature_bits1, feature_bits2, burst_bits, extra_bits FROM feature_confirmation"
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        let bits = bitset::words(row.feature_bits1, row.feature_bits2, &row.extra_bits);
+        pairs.entry(row.pronunciation).or_default().1 = Some((bits, row.burst_bits));
+    }
+
+    Ok(pairs
+        .into_iter()
+        .filter_map(|(pronunciation, (override_bits, confirmed_bits))| {
+            let kind = match (&override_bits, &confirmed_bits) {
+                (Some((words, burst)), Some((confirmed_words, confirmed_burst))) => {
+                    if bitset::equal(words, confirmed_words) && burst == confirmed_burst {
+                        return None;
+                    }
+                    DiscrepancyKind::BitsDiffer
+                }
+                (Some(_), None) => DiscrepancyKind::OverrideOnly,
+                (None, Some(_)) => DiscrepancyKind::ConfirmationOnly,
+                (None, None) => return None,
+            };
+            let differences = differences(&catalog, override_bits.as_ref(), confirmed_bits.as_ref());
+            Some(Discrepancy {
+                pronunciation,
+                kind,
+                override_bits,
+                confirmed_bits,
+                differences,
+            })
+        })
+        .collect())
+}
+
+fn differences(
+    catalog: &FeatureCatalog,
+    override_bits: Option<&Bits>,
+    confirmed_bits: Option<&Bits>,
+) -> Vec<BitDifference> {
+    let (override_words, override_burst) = override_bits.map_or((&[][..], 0), |(words, burst)| (&words[..], *burst));
+    let (confirmed_words, confirmed_burst) =
+        confirmed_bits.map_or((&[][..], 0), |(words, burst)| (&words[..], *burst));
+
+    let width = override_words.len().max(confirmed_words.len()) * 64;
+    let word_bits = (0..width).map(|bit| {
+        let (field, index) = match bit {
+            0..=63 => (BitField::Bits1, bit),
+            64..=127 => (BitField::Bits2, bit - 64),
+            _ => (BitField::Extended, bit - 128),
+        };
+        (field, index, bitset::is_set(override_words, bit), bitset::is_set(confirmed_words, bit))
+    });
+    let burst_bits = (0..64).map(|index| {
+        (
+            BitField::Burst,
+            index,
+            bitset::is_set(&[override_burst as u64], index),
+            bitset::is_set(&[confirmed_burst as u64], index),
+        )
+    });
+
+    let mut differences: Vec<BitDifference> = word_bits
+        .chain(burst_bits)
+        .filter(|(_, _, in_override, in_confirmation)| in_override != in_confirmation)
+        .map(|(field, index, in_override, in_confirmation)| BitDifference {
+            field,
+            index,
+            feature_name: catalog.name(field, index).map(str::to_string),
+            in_override,
+            in_confirmation,
+        })
+        .collect();
+    differences.sort_by_key(|difference| (difference.field, difference.index));
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> FeatureCatalog {
+        FeatureCatalog::from_names([(BitField::Bits1, 0, "ドロー"), (BitField::Extended, 2, "拡張")])
+    }
+
+    fn listed(differences: &[BitDifference]) -> Vec<(BitField, usize, Option<&str>, bool, bool)> {
+        differences
+            .iter()
+            .map(|d| (d.field, d.index, d.feature_name.as_deref(), d.in_override, d.in_confirmation))
+            .collect()
+    }
+
+    #[test]
+    fn override_only_lists_every_override_bit() {
+        let override_bits = (vec![1, 0, 4], 2);
+        assert_eq!(
+            listed(&differences(&catalog(), Some(&override_bits), None)),
+            [
+                (BitField::Bits1, 0, Some("ドロー"), true, false),
+                (BitField::Burst, 1, None, true, false),
+                (BitField::Extended, 2, Some("拡張"), true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn confirmation_only_lists_every_confirmed_bit() {
+        let confirmed_bits = (vec![0, 1 << 5], 0);
+        assert_eq!(
+            listed(&differences(&catalog(), None, Some(&confirmed_bits))),
+            [(BitField::Bits2, 5, None, false, true)]
+        );
+    }
+
+    #[test]
+    fn burst_bits_are_compared() {
+        let override_bits = (vec![1], 1);
+        let confirmed_bits = (vec![1], 3);
+        assert_eq!(
+            listed(&differences(&catalog(), Some(&override_bits), Some(&confirmed_bits))),
+            [(BitField::Burst, 1, None, false, true)]
+        );
+    }
+
+    #[test]
+    fn extended_words_are_compared_across_lengths() {
+        let override_bits = (vec![1, 0, 0, 1], 0);
+        let confirmed_bits = (vec![1], 0);
+        assert_eq!(
+            listed(&differences(&catalog(), Some(&override_bits), Some(&confirmed_bits))),
+            [(BitField::Extended, 64, None, true, false)]
+        );
+
+        let padded = (vec![1, 0, 0], 0);
+        assert!(differences(&catalog(), Some(&padded), Some(&confirmed_bits)).is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::{BTreeSet, HashSet};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::bitset;
+use crate::catalog::{BitField, FeatureCatalog};
+
+/// Compiled size limit of one pattern, so a pathological pattern fails to compile instead of
+/// taking unbounded memory
+const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+/// Compiles a rule pattern the way evaluation runs it.
+pub fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).size_limit(PATTERN_SIZE_LIMIT).build()
+}
+
+/// An enabled rule_pattern row with its compiled pattern.
+pub struct Rule {
+    pub keyword: String,
+    pub pattern: String,
+    pub feature_name: String,
+    regex: Regex,
+}
+
+impl Rule {
+    /// Spans of `text` the rule matches. A rule matches only when its keyword occurs in the
+    /// text; empty matches are ignored.
+    pub fn find(&self, text: &str) -> Vec<Span> {
+        if !text.contains(self.keyword.as_str()) {
+            return Vec::new();
+        }
+        find_spans(&self.regex, text)
+    }
+}
+
+/// A matched part of a text. Offsets count characters (Unicode scalar values); end is exclusive.
+#[derive(Clone)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Non-empty matches of `regex` in `text`.
+pub fn find_spans(regex: &Regex, text: &str) -> Vec<Span> {
+    regex
+        .find_iter(text)
+        .filter(|found| !found.is_empty())
+        .map(|found| Span {
+            start: char_offset(text, found.start()),
+            end: char_offset(text, found.end()),
+            text: found.as_str().to_string(),
+        })
+        .collect()
+}
+
+/// Matches of a candidate pattern in one text.
+pub struct TextResult {
+    pub spans: Vec<Span>,
+    pub elapsed: Duration,
+}
+
+/// Runs `regex` over `texts` in order, stopping once `budget` is spent. With a keyword, only
+/// texts containing it are matched, as in evaluation. Returns one result per text tested.
+pub fn test_pattern(regex: &Regex, keyword: Option<&str>, texts: &[String], budget: Duration) -> Vec<TextResult> {
+    let started = Instant::now();
+    let mut results = Vec::with_capacity(texts.len());
+    for text in texts {
+        if started.elapsed() >= budget {
+            break;
+        }
+        let text_started = Instant::now();
+        let spans = match keyword {
+            Some(keyword) if !text.contains(keyword) => Vec::new(),
+            _ => find_spans(regex, text),
+        };
+        results.push(TextResult {
+            spans,
+            elapsed: text_started.elapsed(),
+        });
+    }
+    results
+}
+
+/// A stored pattern that could not be compiled. Evaluation skips it.
+pub struct InvalidRule {
+    pub keyword: String,
+    pub pattern: String,
+    pub error: String,
+}
+
+/// One span a rule matched.
+#[derive(Clone)]
+pub struct RuleMatch<'a> {
+    pub rule: &'a Rule,
+    pub span: Span,
+}
+
+/// What the rules detect in a text.
+pub struct Evaluation<'a> {
+    pub matches: Vec<RuleMatch<'a>>,
+    /// Bits of the matched features, see [`bitset`]
+    pub words: Vec<u64>,
+    pub burst: i64,
+    /// Matched features that have no feature definition, so no bit
+    pub undefined_features: BTreeSet<String>,
+}
+
+/// The enabled rule patterns, compiled, with the catalog that maps their features to bits.
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+    pub invalid: Vec<InvalidRule>,
+    pub catalog: FeatureCatalog,
+}
+
+impl RuleSet {
+    /// Loads the enabled rules and the catalog. Pass a transaction to read them from the same
+    /// snapshot as other queries.
+    pub async fn load(conn: &mut SqliteConnection) -> Result<Self> {
+        let rows = sqlx::query!(
+            "SELECT keyword, pattern, feature_name FROM rule_pattern
+             WHERE is_enabled = 1 ORDER BY keyword, pattern"
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        let catalog = FeatureCatalog::load(&mut *conn).await?;
+
+        let mut rules = Vec::with_capacity(rows.len());
+        let mut invalid = Vec::new();
+        for row in rows {
+            match compile(&row.pattern) {
+                Ok(regex) => rules.push(Rule {
+                    keyword: row.keyword,
+                    pattern: row.pattern,
+                    feature_name: row.feature_name,
+                    regex,
+                }),
+                Err(e) => invalid.push(InvalidRule {
+                    keyword: row.keyword,
+                    pattern: row.pattern,
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        Ok(Self { rules, invalid, catalog })
+    }
+
+    /// Applies every rule to `text`. Matches are listed by rule, then by position.
+    pub fn evaluate(&self, text: &str) -> Evaluation<'_> {
+        let mut evaluation = Evaluation {
+            matches: Vec::new(),
+            words: vec![0, 0],
+            burst: 0,
+            undefined_features: BTreeSet::new(),
+        };
+
+        for rule in &self.rules {
+            let spans = rule.find(text);
+            if spans.is_empty() {
+                continue;
+            }
+            match self.catalog.bit_of(&rule.feature_name) {
+                Some((BitField::Bits1, index)) => bitset::set(&mut evaluation.words, index),
+                Some((BitField::Bits2, index)) => bitset::set(&mut evaluation.words, 64 + index),
+                Some((BitField::Extended, index)) => bitset::set(&mut evaluation.words, 128 + index),
+                Some((BitField::Burst, index)) => evaluation.burst |= 1_i64 << index,
+                None => {
+                    evaluation.undefined_features.insert(rule.feature_name.clone());
+                }
+            }
+            evaluation
+                .matches
+                .extend(spans.into_iter().map(|span| RuleMatch { rule, span }));
+        }
+
+        evaluation
+    }
+}
+
+/// The text rules are evaluated against for a stored card: its card text, then its life burst
+/// text on a line of its own
+pub struct CardText {
+    pub pronunciation: String,
+    pub text: String,
+}
+
+/// Texts of the stored cards in pronunciation order; all of them when `limit` is None.
+pub async fn load_card_texts(pool: &SqlitePool, limit: Option<i64>) -> Result<Vec<CardText>> {
+    // SQLite treats a negative LIMIT as no limit
+    let limit = limit.unwrap_or(-1);
+    let texts = sqlx::query_as!(
+        CardText,
+        r#"SELECT pronunciation, card_text || COALESCE(char(10) || burst_text, '') AS "text!: String"
+           FROM card ORDER BY pronunciation LIMIT ?"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(texts)
+}
+
+/// Two rules where every text the first matched was matched by the second too.
+pub struct CoMatch<'a> {
+    pub rule: &'a Rule,
+    pub other: &'a Rule,
+    /// Texts the first rule matched
+    pub texts: usize,
+    /// Both matched exactly the same texts. Such a pair is reported once.
+    pub identical: bool,
+}
+
+/// Overlapping matches of two rules with different features in one text.
+pub struct Conflict<'a> {
+    /// Index into the analyzed texts
+    pub text: usize,
+    pub first: RuleMatch<'a>,
+    pub second: RuleMatch<'a>,
+}
+
+/// How the rules of a ruleset overlap on a corpus of texts.
+pub struct Analysis<'a> {
+    /// Texts each rule matched, by rule
+    pub match_counts: Vec<(&'a Rule, usize)>,
+    pub never_matched: Vec<&'a Rule>,
+    pub co_matches: Vec<CoMatch<'a>>,
+    pub conflicts: Vec<Conflict<'a>>,
+}
+
+impl RuleSet {
+    /// Runs every rule over `texts` and reports rules that never match, pairs of rules that
+    /// always match together, and spans claimed by rules for different features. A pair of
+    /// rules conflicts at most once per text.
+    pub fn analyze(&self, texts: &[CardText]) -> Analysis<'_> {
+        let mut matched_texts = vec![BTreeSet::new(); self.rules.len()];
+        let mut conflicts = Vec::new();
+
+        for (text_index, card) in texts.iter().enumerate() {
+            let matches: Vec<(usize, RuleMatch)> = self
+                .rules
+                .iter()
+                .enumerate()
+                .flat_map(|(rule_index, rule)| {
+                    rule.find(&card.text)
+                        .into_iter()
+                        .map(move |span| (rule_index, RuleMatch { rule, span }))
+                })
+                .collect();
+
+            let mut conflicting = HashSet::new();
+            for (i, (rule_index, found)) in matches.iter().enumerate() {
+                matched_texts[*rule_index].insert(text_index);
+                for (other_index, other) in &matches[i + 1..] {
+                    if other_index != rule_index
+                        && other.rule.feature_name != found.rule.feature_name
+                        && other.span.start < found.span.end
+                        && found.span.start < other.span.end
+                        && conflicting.insert((*rule_index.min(other_index), *rule_index.max(other_index)))
+                    {
+                        conflicts.push(Conflict {
+                            text: text_index,
+                            first: found.clone(),
+                            second: other.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut co_matches = Vec::new();
+        for (i, texts) in matched_texts.iter().enumerate() {
+            if texts.is_empty() {
+                continue;
+            }
+            for (j, other_texts) in matched_texts.iter().enumerate() {
+                let identical = texts == other_texts;
+                // Identical pairs are reported from the earlier rule only
+                if i == j || (identical && j < i) || !texts.is_subset(other_texts) {
+                    continue;
+                }
+                co_matches.push(CoMatch {
+                    rule: &self.rules[i],
+                    other: &self.rules[j],
+                    texts: texts.len(),
+                    identical,
+                });
+            }
+        }
+
+        Analysis {
+            match_counts: self.rules.iter().zip(&matched_texts).map(|(rule, texts)| (rule, texts.len())).collect(),
+            never_matched: self
+                .rules
+                .iter()
+                .zip(&matched_texts)
+                .filter(|(_, texts)| texts.is_empty())
+                .map(|(rule, _)| rule)
+                .collect(),
+            co_matches,
+            conflicts,
+        }
+    }
+}
+
+fn char_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset].chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_set(rules: &[(&str, &str, &str)]) -> RuleSet {
+        RuleSet {
+            rules: rules
+                .iter()
+                .map(|(keyword, pattern, feature_name)| Rule {
+                    keyword: keyword.to_string(),
+                    pattern: pattern.to_string(),
+                    feature_name: feature_name.to_string(),
+                    regex: compile(pattern).unwrap(),
+                })
+                .collect(),
+            invalid: Vec::new(),
+            catalog: FeatureCatalog::default(),
+        }
+    }
+
+    fn texts(texts: &[&str]) -> Vec<CardText> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| CardText {
+                pronunciation: index.to_string(),
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    fn patterns<'a>(rules: impl IntoIterator<Item = &'a Rule>) -> Vec<&'a str> {
+        rules.into_iter().map(|rule| rule.pattern.as_str()).collect()
+    }
+
+    #[test]
+    fn reports_rules_that_never_match() {
+        let rules = rule_set(&[("引く", "カードを\\d枚引く", "ドロー"), ("捨てる", "手札を\\d枚捨てる", "ハンデス")]);
+        let analysis = rules.analyze(&texts(&["カードを1枚引く。", "カードを2枚引く。"]));
+
+        assert_eq!(patterns(analysis.never_matched), ["手札を\\d枚捨てる"]);
+        let counts: Vec<usize> = analysis.match_counts.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts, [2, 0]);
+    }
+
+    #[test]
+    fn reports_rules_whose_texts_another_rule_always_matches() {
+        let rules = rule_set(&[
+            ("引く", "2枚引く", "ドロー2"),
+            ("引く", "枚引く", "ドロー"),
+            ("バニッシュ", "バニッシュ", "除去"),
+            ("バニッシュ", "対戦相手のシグニ1体をバニッシュ", "除去"),
+        ]);
+        let analysis = rules.analyze(&texts(&[
+            "カードを2枚引く。",
+            "カードを1枚引く。",
+            "対戦相手のシグニ1体をバニッシュする。",
+        ]));
+
+        let pairs: Vec<_> = analysis
+            .co_matches
+            .iter()
+            .map(|co| (co.rule.pattern.as_str(), co.other.pattern.as_str(), co.texts, co.identical))
+            .collect();
+        // The identical pair is reported once, from the earlier rule
+        assert_eq!(
+            pairs,
+            [
+                ("2枚引く", "枚引く", 1, false),
+                ("バニッシュ", "対戦相手のシグニ1体をバニッシュ", 1, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_overlapping_matches_for_different_features_once_per_text() {
+        let rules = rule_set(&[
+            ("バニッシュ", "シグニ\\d体をバニッシュ", "除去"),
+            ("バニッシュ", "バニッシュする", "バニッシュ"),
+            ("バニッシュ", "バニッシュ", "除去"),
+        ]);
+        let analysis = rules.analyze(&texts(&[
+            "シグニ1体をバニッシュする。シグニ2体をバニッシュする。",
+            "カードを1枚引く。",
+            "シグニ1体をバニッシュ。",
+        ]));
+
+        let conflicts: Vec<_> = analysis
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.text, conflict.first.rule.pattern.as_str(), conflict.second.rule.pattern.as_str()))
+            .collect();
+        // Same-feature overlaps are not conflicts; two overlaps of one pair in a text count once
+        assert_eq!(
+            conflicts,
+            [
+                (0, "シグニ\\d体をバニッシュ", "バニッシュする"),
+                (0, "バニッシュする", "バニッシュ"),
+            ]
+        );
+        assert_eq!(analysis.conflicts[0].first.span.start, 0);
+        assert_eq!(analysis.conflicts[0].second.span.start, 6);
+    }
+}
@@ -0,0 +1,231 @@
+//! Ruleset versions: a content hash over what evaluation depends on (the enabled rule patterns
+//! and the feature definitions) and the release names clients give to versions.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
+
+use crate::history;
+
+/// Length of a version: lowercase hex SHA-256
+pub const VERSION_LEN: usize = 64;
+
+/// A named ruleset version.
+pub struct Release {
+    pub name: String,
+    pub version: String,
+    pub note: Option<String>,
+    pub created_by: String,
+    pub created_at: String,
+}
+
+/// True when `value` has the form of a version rather than a release name.
+pub fn is_version(value: &str) -> bool {
+    value.len() == VERSION_LEN && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Content hash of the current ruleset. Rows are hashed in key order with every value
+/// length-prefixed, so the same rules and definitions always give the same version. Disabled
+/// rules, timestamps and definition descriptions do not affect evaluation and are left out.
+pub async fn current_version(conn: &mut SqliteConnection) -> Result<String> {
+    let rules = sqlx::query!(
+        "SELECT keyword, pattern, feature_name FROM rule_pattern
+         WHERE is_enabled = 1 ORDER BY keyword, pattern"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let definitions = sqlx::query!("SELECT name, bit_field, bit_index FROM feature_definition ORDER BY name")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut hash = Sha256::new();
+    hash_str(&mut hash, "rule_pattern");
+    hash.update((rules.len() as u64).to_le_bytes());
+    for rule in &rules {
+        hash_str(&mut hash, &rule.keyword);
+        hash_str(&mut hash, &rule.pattern);
+        hash_str(&mut hash, &rule.feature_name);
+    }
+    hash_str(&mut hash, "feature_definition");
+    hash.update((definitions.len() as u64).to_le_bytes());
+    for definition in &definitions {
+        hash_str(&mut hash, &definition.name);
+        hash_str(&mut hash, &definition.bit_field);
+        hash.update(definition.bit_index.to_le_bytes());
+    }
+
+    Ok(format!("{:x}", hash.finalize()))
+}
+
+/// Computes the current version and records it as known. Call it in the transaction that
+/// changed rule patterns or feature definitions, before it commits.
+pub async fn record_current(conn: &mut SqliteConnection) -> Result<String> {
+    let version = current_version(conn).await?;
+    let revision = history::ruleset_revision(&mut *conn).await?;
+
+    sqlx::query!(
+        "INSERT INTO ruleset_version (version, revision) VALUES (?, ?) ON CONFLICT(version) DO NOTHING",
+        version,
+        revision
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(version)
+}
+
+/// Resolves a version or release name to a known version, or None when it is neither.
+pub async fn resolve(conn: &mut SqliteConnection, value: &str) -> Result<Option<String>> {
+    let version = sqlx::query_scalar!(
+        r#"SELECT version AS "version!" FROM ruleset_version WHERE version = ?1
+           UNION ALL
+           SELECT version AS "version!" FROM ruleset_release WHERE name = ?1
+           LIMIT 1"#,
+        value
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(version)
+}
+
+pub async fn release(conn: &mut SqliteConnection, name: &str) -> Result<Option<Release>> {
+    let release = sqlx::query_as!(
+        Release,
+        "SELECT name, version, note, created_by, created_at FROM ruleset_release WHERE name = ?",
+        name
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(release)
+}
+
+/// Names `version`, which must be known. Release names are never reassigned.
+pub async fn create_release(
+    conn: &mut SqliteConnection,
+    name: &str,
+    version: &str,
+    note: Option<&str>,
+    actor: &str,
+) -> Result<Release> {
+    let release = sqlx::query_as!(
+        Release,
+        "INSERT INTO ruleset_release (name, version, note, created_by) VALUES (?, ?, ?, ?)
+         RETURNING name, version, note, created_by, created_at",
+        name,
+        version,
+        note,
+        actor
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(release)
+}
+
+/// Every release, oldest first.
+pub async fn releases(conn: &mut SqliteConnection) -> Result<Vec<Release>> {
+    let releases = sqlx::query_as!(
+        Release,
+        "SELECT name, version, note, created_by, created_at FROM ruleset_release ORDER BY created_at, name"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(releases)
+}
+
+fn hash_str(hash: &mut Sha256, value: &str) {
+    hash.update((value.len() as u64).to_le_bytes());
+    hash.update(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::{Connection, SqlitePool};
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn execute(pool: &SqlitePool, sql: &str) {
+        sqlx::query(sql).execute(pool).await.unwrap();
+    }
+
+    async fn version(pool: &SqlitePool) -> String {
+        current_version(&mut pool.acquire().await.unwrap()).await.unwrap()
+    }
+
+    /// A ruleset of two rules and a definition, with the rules inserted in the given order.
+    async fn ruleset(rules: [(&str, &str); 2]) -> SqlitePool {
+        let pool = test_pool().await;
+        for (keyword, pattern) in rules {
+            sqlx::query("INSERT INTO rule_pattern (keyword, pattern, feature_name) VALUES (?, ?, 'ドロー')")
+                .bind(keyword)
+                .bind(pattern)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        execute(
+            &pool,
+            "INSERT INTO feature_definition (name, bit_field, bit_index, created_at, updated_at)
+             VALUES ('ドロー', 'bits1', 0, '2024-01-01T00:00:00.000Z', '2024-01-01T00:00:00.000Z')",
+        )
+        .await;
+        pool
+    }
+
+    #[tokio::test]
+    async fn version_ignores_row_order() {
+        let first = ruleset([("引く", "1枚引く"), ("引く", "2枚引く")]).await;
+        let second = ruleset([("引く", "2枚引く"), ("引く", "1枚引く")]).await;
+
+        let first_version = version(&first).await;
+        assert!(is_version(&first_version));
+        assert_eq!(first_version, version(&second).await);
+    }
+
+    #[tokio::test]
+    async fn version_changes_with_what_evaluation_uses() {
+        let pool = ruleset([("引く", "1枚引く"), ("引く", "2枚引く")]).await;
+        let original = version(&pool).await;
+
+        execute(&pool, "UPDATE feature_definition SET description = 'カードを引く'").await;
+        execute(&pool, "UPDATE rule_pattern SET updated_at = '2025-01-01T00:00:00.000Z'").await;
+        assert_eq!(version(&pool).await, original);
+
+        execute(&pool, "UPDATE rule_pattern SET is_enabled = 0 WHERE pattern = '2枚引く'").await;
+        let disabled = version(&pool).await;
+        assert_ne!(disabled, original);
+
+        execute(&pool, "UPDATE rule_pattern SET is_enabled = 1").await;
+        assert_eq!(version(&pool).await, original);
+
+        execute(&pool, "UPDATE feature_definition SET bit_index = 1").await;
+        assert_ne!(version(&pool).await, original);
+    }
+
+    #[tokio::test]
+    async fn record_current_registers_the_version_once() {
+        let pool = ruleset([("引く", "1枚引く"), ("引く", "2枚引く")]).await;
+        let mut conn = pool.acquire().await.unwrap();
+        let mut tx = conn.begin().await.unwrap();
+        let recorded = record_current(&mut tx).await.unwrap();
+        assert_eq!(record_current(&mut tx).await.unwrap(), recorded);
+        tx.commit().await.unwrap();
+
+        assert_eq!(resolve(&mut conn, &recorded).await.unwrap(), Some(recorded.clone()));
+        assert_eq!(resolve(&mut conn, "v1").await.unwrap(), None);
+    }
+}
@@ -1,11 +1,38 @@
 use anyhow::Result;
-use sqlx::Row;
+use sqlx::{sqlite::SqliteRow, QueryBuilder, Row, Sqlite, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::{Stream, StreamExt};
 use std::env;
 use tonic::{transport::{Server, Identity, ServerTlsConfig}, Request, Response, Status};
 use tracing::{info, warn};
 
-use crate::auth::{AuthService, authenticate_request, require_write_permission};
+use crate::auth::{
+    AuthService, authenticate_api_key, authenticate_request, extract_api_key, require_write_permission,
+};
+use crate::bitset;
+use crate::catalog::{describe_undefined_bits, BitField, BitPolicy, FeatureCatalog};
 use crate::database::Database;
+use crate::errors::{self, invalid_argument, validation_error};
+use crate::idempotency::{Claim, IdempotencyStore, RequestHash};
+use crate::pronunciation;
+use crate::pull::{stream_pull, Page, PullParams, PullTable};
+use crate::push::{
+    applied_item, apply_card_chunk, apply_feature_override_chunk, apply_rule_pattern_chunk, conflict_item, is_final,
+    rejected_item, stale_reason, PushChunk, StampedItem,
+};
+use crate::report;
+use crate::rules::{self, RuleSet};
+use crate::ruleset;
+use crate::staleness;
+use crate::sync::{record_sync_metadata, SyncSession, DATA_TYPES};
+use crate::timestamp;
+use crate::history::{
+    self, CardValues, ChangePayload, ChangeRecord, ConfirmationValues, DefinitionValues,
+    OverrideHistoryEntry as HistoryRow, OverrideValues as HistoryValues, RuleValues, Snapshot,
+};
 
 pub mod proto {
     tonic::include_proto!("admin");
@@ -14,15 +41,83 @@ pub mod proto {
 use proto::admin_sync_server::{AdminSync, AdminSyncServer};
 use proto::*;
 
+/// Number of changes buffered per WatchChanges subscriber before it has to catch up from the database
+const CHANGE_BROADCAST_CAPACITY: usize = 1024;
+/// Changes read from change_log per catch-up query
+const CATCH_UP_BATCH_SIZE: i64 = 500;
+const DEFAULT_HEARTBEAT_SECONDS: u64 = 30;
+/// Pronunciations accepted by one BatchGetFeatureOverrides call
+const MAX_BATCH_GET_SIZE: usize = 1000;
+/// Longest card text EvaluateText accepts, in bytes
+const MAX_EVALUATE_TEXT_BYTES: usize = 64 * 1024;
+/// Sample texts accepted by one TestRulePattern call
+const MAX_TEST_SAMPLE_TEXTS: usize = 1000;
+/// Stored card texts TestRulePattern tests by default, and at most
+const DEFAULT_TEST_CARD_TEXTS: i64 = 1000;
+const MAX_TEST_CARD_TEXTS: i64 = 10_000;
+/// Matching time one TestRulePattern call may spend before it stops and reports truncated
+const PATTERN_TEST_BUDGET: Duration = Duration::from_secs(5);
+/// 0001-01-01T00:00:00Z
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+/// 9999-12-31T23:59:59Z
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+
 pub struct AdminServer {
     db: Database,
     auth: AuthService,
+    changes: broadcast::Sender<ChangeRecord>,
+    idempotency: IdempotencyStore,
+    bit_policy: BitPolicy,
+    /// Compiled rules and the ruleset revision they were loaded at
+    rule_set: Mutex<Option<(i64, Arc<RuleSet>)>>,
 }
 
 impl AdminServer {
     pub fn new(db: Database) -> Self {
         let auth = AuthService::new(db.pool().clone());
-        Self { db, auth }
+        let (changes, _) = broadcast::channel(CHANGE_BROADCAST_CAPACITY);
+        let idempotency = IdempotencyStore::new(db.pool().clone());
+        let bit_policy = BitPolicy::from_env();
+        info!("Bits without a feature definition: {:?}", bit_policy);
+        Self {
+            db,
+            auth,
+            changes,
+            idempotency,
+            bit_policy,
+            rule_set: Mutex::new(None),
+        }
+    }
+
+    /// The enabled rules, compiled again only after a rule pattern or feature definition changes.
+    async fn rule_set(&self) -> Result<Arc<RuleSet>, Status> {
+        let db_error = |e: anyhow::Error| Status::internal(format!("Database error: {}", e));
+
+        let revision = history::ruleset_revision(self.db.pool()).await.map_err(db_error)?;
+        if let Some((loaded_at, rule_set)) = &*self.rule_set.lock().unwrap() {
+            if *loaded_at == revision {
+                return Ok(rule_set.clone());
+            }
+        }
+
+        let mut conn = self.db.pool().acquire().await.map_err(|e| db_error(e.into()))?;
+        let rule_set = Arc::new(RuleSet::load(&mut conn).await.map_err(db_error)?);
+        *self.rule_set.lock().unwrap() = Some((revision, rule_set.clone()));
+        Ok(rule_set)
+    }
+
+    async fn bit_validator(&self) -> Result<BitValidator, Status> {
+        BitValidator::load(self.db.pool(), self.bit_policy)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))
+    }
+
+    /// Notifies WatchChanges subscribers. Call only after the change is committed.
+    fn publish(&self, change: Option<ChangeRecord>) {
+        if let Some(change) = change {
+            // An error only means nobody is watching right now
+            let _ = self.changes.send(change);
+        }
     }
 
     pub async fn serve(self) -> Result<()> {
@@ -49,6 +144,11 @@ impl AdminServer {
             info!("TLS not configured - gRPC server listening on {} (plaintext)", addr);
         }
 
+        match staleness::interval_from_env() {
+            Some(interval) => staleness::spawn_job(self.db.pool().clone(), interval),
+            None => info!("Periodic stale confirmation check disabled"),
+        }
+
         server_builder
             .add_service(AdminSyncServer::new(self))
             .serve(addr)
@@ -78,12 +178,28 @@ impl AdminSync for AdminServer {
         let req = request.into_inner();
         info!("GetSyncStatus request from client: {}", req.client_id);
 
+        let (ruleset_version, ruleset_releases) = async {
+            let mut conn = self.db.pool().acquire().await?;
+            let version = ruleset::current_version(&mut conn).await?;
+            let releases = sqlx::query_scalar!(
+                "SELECT name FROM ruleset_release WHERE version = ? ORDER BY created_at, name",
+                version
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+            Ok::<_, anyhow::Error>((version, releases))
+        }
+        .await
+        .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
         let response = StatusResponse {
             server_time: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
             sync_status: std::collections::HashMap::new(),
             total_feature_overrides: 0,
             total_confirmed_features: 0,
             total_rule_patterns: 0,
+            ruleset_version,
+            ruleset_releases,
         };
 
         Ok(Response::new(response))
@@ -93,186 +209,233 @@ impl AdminSync for AdminServer {
         &self,
         request: Request<tonic::Streaming<FeatureOverride>>,
     ) -> Result<Response<PushResponse>, Status> {
-        let api_key_result = {
-            let metadata = request.metadata();
-            if let Some(api_key_value) = metadata.get("api-key") {
-                api_key_value.to_str()
-                    .map_err(|_| Status::unauthenticated("Invalid API key format"))
-                    .and_then(|key| Ok(key.to_string()))
-            } else {
-                Err(Status::unauthenticated("API key required"))
+        self.push_feature_override_items(request, "PushFeatureOverrides").await
+    }
+
+    async fn pull_feature_overrides(
+        &self,
+        request: Request<PullRequest>,
+    ) -> Result<Response<Self::PullFeatureOverridesStream>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let params = PullParams::from_request(&req)?;
+        let page = Page::start(params.query(PullTable::FeatureOverride)?, req.page_token.as_deref())?;
+        let catalog = self.catalog_for(&req).await?;
+        Ok(Response::new(stream_pull(self.db.pool().clone(), page, params.limit, move |row| {
+            let mut feature_override = feature_override_from_row(row);
+            if let Some(catalog) = &catalog {
+                feature_override.feature_names =
+                    catalog.decode(bitset_words(&feature_override.feature_bits), feature_override.fixed_burst_bits);
             }
-        }?;
+            feature_override
+        })))
+    }
+
+    type PullFeatureOverridesStream = 
+        tokio_stream::wrappers::ReceiverStream<Result<FeatureOverride, Status>>;
 
-        let api_key = self.auth.verify_api_key(&api_key_result).await
-            .map_err(|_| Status::internal("Authentication service error"))?
-            .ok_or_else(|| Status::unauthenticated("Invalid API key"))?;
+    async fn batch_upsert_feature_overrides(
+        &self,
+        request: Request<BatchUpsertFeatureOverridesRequest>,
+    ) -> Result<Response<PushResponse>, Status> {
+        let request = request.map(|req| tokio_stream::iter(req.overrides.into_iter().map(Ok)));
+        self.push_feature_override_items(request, "BatchUpsertFeatureOverrides").await
+    }
 
-        require_write_permission(&api_key)?;
+    async fn batch_get_feature_overrides(
+        &self,
+        request: Request<BatchGetFeatureOverridesRequest>,
+    ) -> Result<Response<BatchGetFeatureOverridesResponse>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
 
-        let mut stream = request.into_inner();
-        let mut items_received = 0;
-        let mut items_updated = 0;
-        let mut items_created = 0;
-        let mut errors = Vec::new();
-
-        while let Some(feature_override) = stream.message().await? {
-            items_received += 1;
-            
-            match self.upsert_feature_override(&feature_override).await {
-                Ok(was_updated) => {
-                    if was_updated {
-                        items_updated += 1;
-                    } else {
-                        items_created += 1;
-                    }
-                }
-                Err(e) => {
-                    errors.push(format!("Error processing {}: {}", feature_override.pronunciation, e));
-                }
-            }
+        if req.pronunciations.len() > MAX_BATCH_GET_SIZE {
+            return Err(invalid_argument(
+                "pronunciations",
+                format!("at most {} pronunciations per request", MAX_BATCH_GET_SIZE),
+            ));
+        }
+        if req.pronunciations.is_empty() {
+            return Ok(Response::new(BatchGetFeatureOverridesResponse::default()));
         }
 
-        info!(
-            "PushFeatureOverrides completed: {} received, {} created, {} updated, {} errors",
-            items_received, items_created, items_updated, errors.len()
-        );
+        let requested = req
+            .pronunciations
+            .into_iter()
+            .map(|raw| pronunciation_key("pronunciations", &raw).map(|key| (raw, key)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(errors::to_status)?;
 
-        let response = PushResponse {
-            items_received,
-            items_updated,
-            items_created,
-            errors,
-        };
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM card_feature_override WHERE pronunciation IN (");
+        let mut keys = query.separated(", ");
+        for (_, key) in &requested {
+            keys.push_bind(key);
+        }
+        query.push(")");
+
+        let mut found: HashMap<String, FeatureOverride> = query
+            .build()
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?
+            .iter()
+            .map(feature_override_from_row)
+            .map(|feature_override| (feature_override.pronunciation.clone(), feature_override))
+            .collect();
+
+        let mut response = BatchGetFeatureOverridesResponse::default();
+        let mut listed = HashSet::new();
+        for (raw, key) in requested {
+            if !listed.insert(key.clone()) {
+                continue;
+            }
+            match found.remove(&key) {
+                Some(feature_override) => response.overrides.push(feature_override),
+                None => response.not_found.push(raw),
+            }
+        }
 
         Ok(Response::new(response))
     }
 
-    async fn pull_feature_overrides(
+    async fn delete_feature_override(
         &self,
-        request: Request<PullRequest>,
-    ) -> Result<Response<Self::PullFeatureOverridesStream>, Status> {
+        request: Request<DeleteOverrideRequest>,
+    ) -> Result<Response<DeleteOverrideResponse>, Status> {
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        require_write_permission(&api_key)?;
+
+        let req = request.into_inner();
+        let pronunciation = pronunciation_key("pronunciation", &req.pronunciation).map_err(errors::to_status)?;
+
+        match history::delete_override(self.db.pool(), &pronunciation, &api_key.client_name).await {
+            Ok(Some(change)) => {
+                self.publish(Some(change));
+                Ok(Response::new(DeleteOverrideResponse {
+                    success: true,
+                    error: None,
+                }))
+            }
+            Ok(None) => Ok(Response::new(DeleteOverrideResponse {
+                success: false,
+                error: Some(format!("No feature override for {}", pronunciation)),
+            })),
+            Err(e) => Ok(Response::new(DeleteOverrideResponse {
+                success: false,
+                error: Some(format!("Failed to delete feature override: {}", e)),
+            })),
+        }
+    }
+
+    async fn get_feature_override_history(
+        &self,
+        request: Request<OverrideHistoryRequest>,
+    ) -> Result<Response<OverrideHistoryResponse>, Status> {
         let _api_key = authenticate_request(&request, &self.auth).await?;
         let req = request.into_inner();
-        
-        let since_clause = if let Some(since) = req.since {
-            let since_time = chrono::DateTime::from_timestamp(since.seconds, since.nanos as u32)
-                .unwrap()
-                .to_rfc3339();
-            format!("WHERE updated_at > '{}'", since_time)
-        } else {
-            String::new()
+
+        let limit = match req.limit {
+            None => 100,
+            Some(limit) if limit > 0 => i64::from(limit),
+            Some(_) => return Err(invalid_argument("limit", "must be positive")),
         };
 
-        let limit_clause = req.limit
-            .map(|l| format!("LIMIT {}", l))
-            .unwrap_or_default();
+        let pronunciation = pronunciation_key("pronunciation", &req.pronunciation).map_err(errors::to_status)?;
+        let entries = history::override_history(self.db.pool(), &pronunciation, limit)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?
+            .into_iter()
+            .map(history_entry_to_proto)
+            .collect();
 
-        let query = format!(
-            "SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note 
-             FROM card_feature_override {} ORDER BY updated_at ASC {}",
-            since_clause, limit_clause
-        );
+        Ok(Response::new(OverrideHistoryResponse { entries }))
+    }
 
-        let rows = sqlx::query(&query)
-            .fetch_all(self.db.pool())
-            .await
-            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+    async fn revert_feature_override(
+        &self,
+        request: Request<RevertOverrideRequest>,
+    ) -> Result<Response<RevertOverrideResponse>, Status> {
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        require_write_permission(&api_key)?;
 
-        let (tx, rx) = tokio::sync::mpsc::channel(128);
-
-        tokio::spawn(async move {
-            for row in rows {
-                let pronunciation: String = row.get("pronunciation");
-                let fixed_bits1: i64 = row.get("fixed_bits1");
-                let fixed_bits2: i64 = row.get("fixed_bits2");
-                let fixed_burst_bits: i64 = row.get("fixed_burst_bits");
-                let created_at: String = row.get("created_at");
-                let updated_at: String = row.get("updated_at");
-                let note: Option<String> = row.get("note");
-
-                let created_at_ts = chrono::DateTime::parse_from_rfc3339(&created_at)
-                    .map(|dt| prost_types::Timestamp {
-                        seconds: dt.timestamp(),
-                        nanos: dt.timestamp_subsec_nanos() as i32,
-                    })
-                    .ok();
+        let req = request.into_inner();
+        let pronunciation = pronunciation_key("pronunciation", &req.pronunciation).map_err(errors::to_status)?;
 
-                let updated_at_ts = chrono::DateTime::parse_from_rfc3339(&updated_at)
-                    .map(|dt| prost_types::Timestamp {
-                        seconds: dt.timestamp(),
-                        nanos: dt.timestamp_subsec_nanos() as i32,
-                    })
-                    .ok();
-
-                let feature_override = FeatureOverride {
-                    pronunciation,
-                    fixed_bits1,
-                    fixed_bits2,
-                    fixed_burst_bits,
-                    created_at: created_at_ts,
-                    updated_at: updated_at_ts,
-                    note,
-                };
-
-                if tx.send(Ok(feature_override)).await.is_err() {
-                    break;
-                }
+        match history::revert_override(self.db.pool(), &pronunciation, req.version, &api_key.client_name).await {
+            Ok(change) => {
+                let new_version = change.as_ref().map(|c| c.version);
+                self.publish(change);
+                Ok(Response::new(RevertOverrideResponse {
+                    success: true,
+                    error: None,
+                    new_version,
+                }))
             }
-        });
-
-        let stream = tokio_stream::wrappers::ReceiverStream::new(rx);
-        Ok(Response::new(stream))
+            Err(e) => Ok(Response::new(RevertOverrideResponse {
+                success: false,
+                error: Some(format!("Failed to revert feature override: {}", e)),
+                new_version: None,
+            })),
+        }
     }
 
-    type PullFeatureOverridesStream = 
-        tokio_stream::wrappers::ReceiverStream<Result<FeatureOverride, Status>>;
-
     async fn confirm_features(
         &self,
         request: Request<ConfirmRequest>,
     ) -> Result<Response<ConfirmResponse>, Status> {
         let api_key = authenticate_request(&request, &self.auth).await?;
         require_write_permission(&api_key)?;
-        
-        let req = request.into_inner();
+        let claim = self.idempotency.claim(request.metadata(), &api_key.client_name, "ConfirmFeatures").await?;
 
-        match sqlx::query!(
-            "INSERT OR REPLACE INTO feature_confirmation 
-             (pronunciation, confirmed_at, confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits)
-             VALUES (?, datetime('now'), ?, ?, ?, ?, ?)",
-            req.pronunciation,
-            api_key.client_name,
-            req.rule_version,
-            req.feature_bits1,
-            req.feature_bits2,
-            req.burst_bits
-        )
-        .execute(self.db.pool())
-        .await
-        {
-            Ok(_) => {
+        let mut req = request.into_inner();
+        let request_hash = RequestHash::of(&req);
+        if let Claim::Replay(stored) = claim {
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        pronunciation::normalize_in_place(&mut req.pronunciation);
+        let response = match self.upsert_confirmation(&req, &api_key.client_name).await {
+            Ok(warnings) => {
                 info!("Features confirmed for pronunciation: {}", req.pronunciation);
-                Ok(Response::new(ConfirmResponse {
+                ConfirmResponse {
                     success: true,
                     error: None,
-                }))
+                    warnings,
+                }
             }
             Err(e) => {
+                // Not stored, so a retry with the same key runs again
                 let error_msg = format!("Failed to confirm features: {}", e);
-                Ok(Response::new(ConfirmResponse {
+                return Ok(Response::new(ConfirmResponse {
                     success: false,
                     error: Some(error_msg),
-                }))
+                    warnings: Vec::new(),
+                }));
             }
-        }
+        };
+
+        claim.complete(request_hash, &response).await;
+        Ok(Response::new(response))
     }
 
     async fn get_confirmed_features(
         &self,
-        _request: Request<()>,
+        request: Request<PullRequest>,
     ) -> Result<Response<Self::GetConfirmedFeaturesStream>, Status> {
-        Err(Status::unimplemented("Not implemented yet"))
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let params = PullParams::from_request(&req)?;
+        let page = Page::start(params.query(PullTable::ConfirmedFeature)?, req.page_token.as_deref())?;
+        let catalog = self.catalog_for(&req).await?;
+        Ok(Response::new(stream_pull(self.db.pool().clone(), page, params.limit, move |row| {
+            let mut confirmed = confirmed_feature_from_row(row);
+            if let Some(catalog) = &catalog {
+                confirmed.feature_names = catalog.decode(bitset_words(&confirmed.feature_bits), confirmed.burst_bits);
+            }
+            confirmed
+        })))
     }
 
     type GetConfirmedFeaturesStream = 
@@ -280,75 +443,2863 @@ impl AdminSync for AdminServer {
 
     async fn unconfirm_feature(
         &self,
-        _request: Request<UnconfirmRequest>,
+        request: Request<UnconfirmRequest>,
     ) -> Result<Response<UnconfirmResponse>, Status> {
-        Err(Status::unimplemented("Not implemented yet"))
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        require_write_permission(&api_key)?;
+        let pronunciation =
+            pronunciation_key("pronunciation", &request.get_ref().pronunciation).map_err(errors::to_status)?;
+        let claim = self.idempotency.claim(request.metadata(), &api_key.client_name, "UnconfirmFeature").await?;
+
+        let req = request.into_inner();
+        let request_hash = RequestHash::of(&req);
+        if let Claim::Replay(stored) = claim {
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        let response = match self.delete_confirmation(&pronunciation, &api_key.client_name).await {
+            Ok(true) => {
+                info!("Confirmation removed for pronunciation: {}", pronunciation);
+                UnconfirmResponse {
+                    success: true,
+                    error: None,
+                }
+            }
+            Ok(false) => UnconfirmResponse {
+                success: false,
+                error: Some(format!("No confirmation for {}", pronunciation)),
+            },
+            Err(e) => {
+                // Not stored, so a retry with the same key runs again
+                return Ok(Response::new(UnconfirmResponse {
+                    success: false,
+                    error: Some(format!("Failed to unconfirm feature: {}", e)),
+                }));
+            }
+        };
+
+        claim.complete(request_hash, &response).await;
+        Ok(Response::new(response))
     }
 
     async fn push_rule_patterns(
         &self,
-        _request: Request<tonic::Streaming<RulePattern>>,
+        request: Request<tonic::Streaming<RulePattern>>,
+    ) -> Result<Response<PushResponse>, Status> {
+        self.push_rule_pattern_items(request, "PushRulePatterns").await
+    }
+
+    async fn batch_upsert_rule_patterns(
+        &self,
+        request: Request<BatchUpsertRulePatternsRequest>,
     ) -> Result<Response<PushResponse>, Status> {
-        Err(Status::unimplemented("Not implemented yet"))
+        let request = request.map(|req| tokio_stream::iter(req.rule_patterns.into_iter().map(Ok)));
+        self.push_rule_pattern_items(request, "BatchUpsertRulePatterns").await
     }
 
     async fn pull_rule_patterns(
         &self,
-        _request: Request<PullRequest>,
+        request: Request<PullRequest>,
     ) -> Result<Response<Self::PullRulePatternsStream>, Status> {
-        Err(Status::unimplemented("Not implemented yet"))
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let params = PullParams::from_request(&req)?;
+        let page = Page::start(params.query(PullTable::RulePattern)?, req.page_token.as_deref())?;
+        Ok(Response::new(stream_pull(self.db.pool().clone(), page, params.limit, rule_pattern_from_row)))
     }
 
     type PullRulePatternsStream = 
         tokio_stream::wrappers::ReceiverStream<Result<RulePattern, Status>>;
 
-    async fn record_sync(
+    async fn push_feature_definitions(
         &self,
-        _request: Request<SyncRecord>,
-    ) -> Result<Response<()>, Status> {
-        Ok(Response::new(()))
+        request: Request<tonic::Streaming<FeatureDefinition>>,
+    ) -> Result<Response<PushResponse>, Status> {
+        let api_key = extract_api_key(&request)?;
+        let api_key = authenticate_api_key(&api_key, &self.auth).await?;
+        require_write_permission(&api_key)?;
+
+        let dry_run = is_dry_run(&request);
+        // A dry run changes nothing, so there is nothing to protect from replays
+        let claim = if dry_run {
+            Claim::Disabled
+        } else {
+            self.idempotency.claim(request.metadata(), &api_key.client_name, "PushFeatureDefinitions").await?
+        };
+        let mut items = request.into_inner();
+        let mut request_hash = RequestHash::default();
+
+        if let Claim::Replay(stored) = claim {
+            while let Some(definition) = items.next().await.transpose()? {
+                request_hash.update(&definition);
+            }
+            info!("PushFeatureDefinitions replayed a stored response for client: {}", api_key.client_name);
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        let mut response = PushResponse {
+            dry_run,
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+
+        // The catalog is small and each definition is checked against the bit positions
+        // written before it, so definitions are written one transaction each.
+        while let Some(definition) = items.next().await.transpose()? {
+            request_hash.update(&definition);
+            response.items_received += 1;
+            let key = definition.name.clone();
+
+            if dry_run {
+                match self.preview_feature_definition(&definition, &mut seen).await {
+                    Ok((entry, error_code)) => record_diff_entry(&mut response, entry, error_code, Vec::new()),
+                    Err(e) => record_item_result(&mut response, rejected_item(key, &e)),
+                }
+                continue;
+            }
+
+            match self.upsert_feature_definition(&definition, &mut seen, &api_key.client_name).await {
+                Ok(result) => record_item_result(&mut response, result),
+                Err(e) => record_item_result(&mut response, rejected_item(key, &e)),
+            }
+        }
+
+        info!(
+            "PushFeatureDefinitions completed{}: {} received, {} created, {} updated, {} unchanged, {} conflicting, {} errors",
+            if dry_run { " (dry run)" } else { "" },
+            response.items_received,
+            response.items_created,
+            response.items_updated,
+            response.items_unchanged,
+            response.items_conflicting,
+            response.errors.len()
+        );
+
+        if is_final(&response) {
+            claim.complete(request_hash, &response).await;
+        }
+        Ok(Response::new(response))
     }
-}
 
-impl AdminServer {
-    async fn upsert_feature_override(&self, feature_override: &FeatureOverride) -> Result<bool, anyhow::Error> {
-        let created_at = feature_override.created_at
-            .as_ref()
-            .map(|ts| chrono::DateTime::from_timestamp(ts.seconds, ts.nanos as u32).unwrap())
-            .unwrap_or_else(chrono::Utc::now);
-        
-        let updated_at = feature_override.updated_at
-            .as_ref()
-            .map(|ts| chrono::DateTime::from_timestamp(ts.seconds, ts.nanos as u32).unwrap())
-            .unwrap_or_else(chrono::Utc::now);
+    async fn pull_feature_definitions(
+        &self,
+        request: Request<PullRequest>,
+    ) -> Result<Response<Self::PullFeatureDefinitionsStream>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
 
-        let existing = sqlx::query!(
-            "SELECT pronunciation FROM card_feature_override WHERE pronunciation = ?",
-            feature_override.pronunciation
-        )
-        .fetch_optional(self.db.pool())
-        .await?;
+        let params = PullParams::from_request(&req)?;
+        let page = Page::start(params.query(PullTable::FeatureDefinition)?, req.page_token.as_deref())?;
+        Ok(Response::new(stream_pull(self.db.pool().clone(), page, params.limit, feature_definition_from_row)))
+    }
 
-        let was_updated = existing.is_some();
+    type PullFeatureDefinitionsStream =
+        tokio_stream::wrappers::ReceiverStream<Result<FeatureDefinition, Status>>;
 
-        let created_at_str = created_at.to_rfc3339();
-        let updated_at_str = updated_at.to_rfc3339();
+    async fn push_cards(
+        &self,
+        request: Request<tonic::Streaming<Card>>,
+    ) -> Result<Response<PushResponse>, Status> {
+        let api_key = extract_api_key(&request)?;
+        let api_key = authenticate_api_key(&api_key, &self.auth).await?;
+        require_write_permission(&api_key)?;
 
-        sqlx::query!(
-            "INSERT OR REPLACE INTO card_feature_override 
-             (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note)
-             VALUES (?, ?, ?, ?, ?, ?, ?)",
-            feature_override.pronunciation,
-            feature_override.fixed_bits1,
-            feature_override.fixed_bits2,
-            feature_override.fixed_burst_bits,
-            created_at_str,
-            updated_at_str,
-            feature_override.note
-        )
-        .execute(self.db.pool())
-        .await?;
+        let dry_run = is_dry_run(&request);
+        // A dry run changes nothing, so there is nothing to protect from replays
+        let claim = if dry_run {
+            Claim::Disabled
+        } else {
+            self.idempotency.claim(request.metadata(), &api_key.client_name, "PushCards").await?
+        };
+        let mut items = request.into_inner();
+        let mut request_hash = RequestHash::default();
+
+        if let Claim::Replay(stored) = claim {
+            while let Some(card) = items.next().await.transpose()? {
+                request_hash.update(&card);
+            }
+            info!("PushCards replayed a stored response for client: {}", api_key.client_name);
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        let mut response = PushResponse {
+            dry_run,
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let mut chunk = PushChunk::new();
+
+        while let Some(mut card) = items.next().await.transpose()? {
+            request_hash.update(&card);
+            response.items_received += 1;
+            pronunciation::normalize_in_place(&mut card.pronunciation);
+            let key = card.pronunciation.clone();
 
-        Ok(was_updated)
+            if dry_run {
+                match self.preview_card(&card, &mut seen).await {
+                    Ok((entry, error_code)) => record_diff_entry(&mut response, entry, error_code, Vec::new()),
+                    Err(e) => record_item_result(&mut response, rejected_item(key, &e)),
+                }
+                continue;
+            }
+
+            if chunk.is_full() {
+                self.flush_cards(&mut chunk, &mut response, &api_key.client_name).await;
+            }
+            match validate_card(&card) {
+                Ok(_) if !seen.insert(key.clone()) => {
+                    chunk.duplicate(key, "pronunciation appears more than once in this push")
+                }
+                Ok(timestamps) => chunk.push(key, stamped(card, timestamps), Vec::new()),
+                Err(e) => chunk.reject(key, &e),
+            }
+        }
+        self.flush_cards(&mut chunk, &mut response, &api_key.client_name).await;
+
+        info!(
+            "PushCards completed{}: {} received, {} created, {} updated, {} unchanged, {} conflicting, {} errors",
+            if dry_run { " (dry run)" } else { "" },
+            response.items_received,
+            response.items_created,
+            response.items_updated,
+            response.items_unchanged,
+            response.items_conflicting,
+            response.errors.len()
+        );
+
+        if is_final(&response) {
+            claim.complete(request_hash, &response).await;
+        }
+        Ok(Response::new(response))
+    }
+
+    async fn pull_cards(
+        &self,
+        request: Request<PullRequest>,
+    ) -> Result<Response<Self::PullCardsStream>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let params = PullParams::from_request(&req)?;
+        let page = Page::start(params.query(PullTable::Card)?, req.page_token.as_deref())?;
+        Ok(Response::new(stream_pull(self.db.pool().clone(), page, params.limit, card_from_row)))
+    }
+
+    type PullCardsStream =
+        tokio_stream::wrappers::ReceiverStream<Result<Card, Status>>;
+
+    async fn evaluate_text(
+        &self,
+        request: Request<EvaluateTextRequest>,
+    ) -> Result<Response<EvaluateTextResponse>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        if req.text.is_empty() {
+            return Err(invalid_argument("text", "must not be empty"));
+        }
+        if req.text.len() > MAX_EVALUATE_TEXT_BYTES {
+            return Err(invalid_argument(
+                "text",
+                format!("must be at most {} bytes", MAX_EVALUATE_TEXT_BYTES),
+            ));
+        }
+        let pronunciation = req
+            .pronunciation
+            .as_deref()
+            .map(|raw| pronunciation_key("pronunciation", raw))
+            .transpose()
+            .map_err(errors::to_status)?;
+
+        let rule_set = self.rule_set().await?;
+        let evaluation = rule_set.evaluate(&req.text);
+
+        let stored = match &pronunciation {
+            Some(key) => sqlx::query("SELECT * FROM card_feature_override WHERE pronunciation = ?")
+                .bind(key)
+                .fetch_optional(self.db.pool())
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?
+                .map(|row| feature_override_from_row(&row)),
+            None => None,
+        };
+        let (words, burst) = match &stored {
+            Some(feature_override) => (
+                bitset_words(&feature_override.feature_bits).to_vec(),
+                feature_override.fixed_burst_bits,
+            ),
+            None => (evaluation.words.clone(), evaluation.burst),
+        };
+
+        let response = EvaluateTextResponse {
+            feature_names: rule_set.catalog.decode(&words, burst),
+            feature_bits1: words[0] as i64,
+            feature_bits2: words[1] as i64,
+            burst_bits: burst,
+            feature_bits: Some(FeatureBitset { words }),
+            override_applied: stored.is_some(),
+            detected_bits: Some(FeatureBitset {
+                words: evaluation.words,
+            }),
+            detected_burst_bits: evaluation.burst,
+            matches: evaluation.matches.into_iter().map(rule_match_to_proto).collect(),
+            undefined_features: evaluation.undefined_features.into_iter().collect(),
+            invalid_patterns: invalid_patterns_to_proto(&rule_set),
+        };
+
+        Ok(Response::new(response))
     }
-}
\ No newline at end of file
+
+    async fn test_rule_pattern(
+        &self,
+        request: Request<TestRulePatternRequest>,
+    ) -> Result<Response<TestRulePatternResponse>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        if req.pattern.is_empty() {
+            return Err(invalid_argument("pattern", "must not be empty"));
+        }
+        if req.keyword.as_ref().is_some_and(|keyword| keyword.trim().is_empty()) {
+            return Err(invalid_argument("keyword", "must not be empty when set"));
+        }
+        if req.sample_texts.len() > MAX_TEST_SAMPLE_TEXTS {
+            return Err(invalid_argument(
+                "sample_texts",
+                format!("at most {} texts per call", MAX_TEST_SAMPLE_TEXTS),
+            ));
+        }
+        if let Some(index) = req.sample_texts.iter().position(|text| text.len() > MAX_EVALUATE_TEXT_BYTES) {
+            return Err(invalid_argument(
+                "sample_texts",
+                format!("text {} is longer than {} bytes", index, MAX_EVALUATE_TEXT_BYTES),
+            ));
+        }
+        let limit = match req.limit {
+            None => DEFAULT_TEST_CARD_TEXTS,
+            Some(limit) if limit > 0 => i64::from(limit).min(MAX_TEST_CARD_TEXTS),
+            Some(_) => return Err(invalid_argument("limit", "must be positive")),
+        };
+
+        let compile_started = Instant::now();
+        let compiled = rules::compile(&req.pattern);
+        let compile_micros = compile_started.elapsed().as_micros() as i64;
+        let regex = match compiled {
+            Ok(regex) => regex,
+            Err(e) => {
+                return Ok(Response::new(TestRulePatternResponse {
+                    valid: false,
+                    error: Some(e.to_string()),
+                    compile_micros,
+                    ..Default::default()
+                }))
+            }
+        };
+
+        // Stored texts come with their pronunciation; sample texts are identified by index
+        let (pronunciations, texts) = if req.sample_texts.is_empty() {
+            rules::load_card_texts(self.db.pool(), Some(limit))
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?
+                .into_iter()
+                .map(|card| (Some(card.pronunciation), card.text))
+                .unzip()
+        } else {
+            (vec![None; req.sample_texts.len()], req.sample_texts)
+        };
+
+        // Matching is CPU-bound, so it runs off the async workers
+        let keyword = req.keyword;
+        let results = tokio::task::spawn_blocking(move || {
+            rules::test_pattern(&regex, keyword.as_deref(), &texts, PATTERN_TEST_BUDGET)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Pattern test failed: {}", e)))?;
+
+        let mut response = TestRulePatternResponse {
+            valid: true,
+            compile_micros,
+            texts_tested: results.len() as i32,
+            truncated: results.len() < pronunciations.len(),
+            ..Default::default()
+        };
+        for (index, (result, pronunciation)) in results.into_iter().zip(pronunciations).enumerate() {
+            let elapsed_micros = result.elapsed.as_micros() as i64;
+            response.total_micros += elapsed_micros;
+            response.max_micros = response.max_micros.max(elapsed_micros);
+            if !result.spans.is_empty() {
+                response.texts_matched += 1;
+            } else if pronunciation.is_some() {
+                continue;
+            }
+            response.results.push(PatternTestResult {
+                sample_index: if pronunciation.is_some() { 0 } else { index as i32 },
+                pronunciation,
+                matches: result
+                    .spans
+                    .into_iter()
+                    .map(|span| TextSpan {
+                        start: span.start as i32,
+                        end: span.end as i32,
+                        text: span.text,
+                    })
+                    .collect(),
+                elapsed_micros,
+            });
+        }
+
+        Ok(Response::new(response))
+    }
+
+    async fn analyze_rule_patterns(
+        &self,
+        request: Request<AnalyzeRulePatternsRequest>,
+    ) -> Result<Response<AnalyzeRulePatternsResponse>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let limit = match req.limit {
+            None => None,
+            Some(limit) if limit > 0 => Some(i64::from(limit)),
+            Some(_) => return Err(invalid_argument("limit", "must be positive")),
+        };
+
+        let rule_set = self.rule_set().await?;
+        let texts = rules::load_card_texts(self.db.pool(), limit)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        // Every rule runs over every text, so this runs off the async workers
+        let response = tokio::task::spawn_blocking(move || {
+            let analysis = rule_set.analyze(&texts);
+            AnalyzeRulePatternsResponse {
+                texts_analyzed: texts.len() as i32,
+                coverage: analysis
+                    .match_counts
+                    .iter()
+                    .map(|(rule, texts_matched)| RuleCoverage {
+                        rule: Some(rule_ref(rule)),
+                        texts_matched: *texts_matched as i32,
+                    })
+                    .collect(),
+                never_matched: analysis.never_matched.iter().map(|rule| rule_ref(rule)).collect(),
+                co_matching: analysis
+                    .co_matches
+                    .iter()
+                    .map(|co_match| CoMatchingRules {
+                        rule: Some(rule_ref(co_match.rule)),
+                        other: Some(rule_ref(co_match.other)),
+                        texts: co_match.texts as i32,
+                        identical: co_match.identical,
+                    })
+                    .collect(),
+                conflicts: analysis
+                    .conflicts
+                    .into_iter()
+                    .map(|conflict| FeatureConflict {
+                        pronunciation: texts[conflict.text].pronunciation.clone(),
+                        first: Some(rule_match_to_proto(conflict.first)),
+                        second: Some(rule_match_to_proto(conflict.second)),
+                    })
+                    .collect(),
+                invalid_patterns: invalid_patterns_to_proto(&rule_set),
+            }
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Rule analysis failed: {}", e)))?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn tag_ruleset_release(
+        &self,
+        request: Request<TagRulesetReleaseRequest>,
+    ) -> Result<Response<RulesetRelease>, Status> {
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        require_write_permission(&api_key)?;
+        let req = request.into_inner();
+
+        if req.name.trim().is_empty() {
+            return Err(invalid_argument("name", "must not be empty"));
+        }
+        if req.name.trim() != req.name {
+            return Err(invalid_argument("name", "must not start or end with whitespace"));
+        }
+        if ruleset::is_version(&req.name) {
+            return Err(invalid_argument("name", "must not have the form of a version"));
+        }
+
+        let db_error = |e: anyhow::Error| Status::internal(format!("Database error: {}", e));
+        let mut tx = self.db.pool().begin().await.map_err(|e| db_error(e.into()))?;
+
+        let version = match req.version.as_deref() {
+            Some(value) => ruleset::resolve(&mut tx, value)
+                .await
+                .map_err(db_error)?
+                .ok_or_else(|| invalid_argument("version", "must be a known ruleset version or release name"))?,
+            None => ruleset::record_current(&mut tx).await.map_err(db_error)?,
+        };
+
+        let release = match ruleset::release(&mut tx, &req.name).await.map_err(db_error)? {
+            Some(existing) if existing.version == version => existing,
+            Some(existing) => {
+                return Err(Status::already_exists(format!(
+                    "release {} already names version {}",
+                    existing.name, existing.version
+                )))
+            }
+            None => {
+                let release =
+                    ruleset::create_release(&mut tx, &req.name, &version, req.note.as_deref(), &api_key.client_name)
+                        .await
+                        .map_err(db_error)?;
+                tx.commit().await.map_err(|e| db_error(e.into()))?;
+                info!("Ruleset release {} tagged as {} by {}", release.name, release.version, release.created_by);
+                release
+            }
+        };
+
+        Ok(Response::new(release_to_proto(release)))
+    }
+
+    async fn list_ruleset_releases(
+        &self,
+        request: Request<ListRulesetReleasesRequest>,
+    ) -> Result<Response<ListRulesetReleasesResponse>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+
+        let response = async {
+            let mut conn = self.db.pool().acquire().await?;
+            Ok::<_, anyhow::Error>(ListRulesetReleasesResponse {
+                current_version: ruleset::current_version(&mut conn).await?,
+                releases: ruleset::releases(&mut conn)
+                    .await?
+                    .into_iter()
+                    .map(release_to_proto)
+                    .collect(),
+            })
+        }
+        .await
+        .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn check_stale_confirmations(
+        &self,
+        request: Request<CheckStaleConfirmationsRequest>,
+    ) -> Result<Response<StaleCheckSummary>, Status> {
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        require_write_permission(&api_key)?;
+
+        let summary = staleness::check(self.db.pool())
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+        info!(
+            "Stale confirmation check by {}: {} of {} confirmations stale",
+            api_key.client_name, summary.stale, summary.checked
+        );
+
+        Ok(Response::new(stale_check_to_proto(summary)))
+    }
+
+    async fn list_stale_confirmations(
+        &self,
+        request: Request<ListStaleConfirmationsRequest>,
+    ) -> Result<Response<ListStaleConfirmationsResponse>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let limit = match req.limit {
+            None => 100,
+            Some(limit) if limit > 0 => i64::from(limit),
+            Some(_) => return Err(invalid_argument("limit", "must be positive")),
+        };
+
+        let (last_check, stale, catalog) = async {
+            let last_check = staleness::last_check(self.db.pool()).await?;
+            let stale = staleness::stale_confirmations(self.db.pool(), limit).await?;
+            let catalog = FeatureCatalog::load(self.db.pool()).await?;
+            Ok::<_, anyhow::Error>((last_check, stale, catalog))
+        }
+        .await
+        .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        let stale = stale
+            .into_iter()
+            .map(|stale| {
+                let confirmed = catalog.decode(&stale.confirmed_words, stale.confirmed_burst);
+                let computed = catalog.decode(&stale.computed_words, stale.computed_burst);
+                StaleConfirmation {
+                    added_features: computed.iter().filter(|name| !confirmed.contains(name)).cloned().collect(),
+                    removed_features: confirmed.iter().filter(|name| !computed.contains(name)).cloned().collect(),
+                    pronunciation: stale.pronunciation,
+                    confirmed_at: timestamp_to_proto(&stale.confirmed_at),
+                    confirmed_by: stale.confirmed_by,
+                    rule_version: stale.rule_version,
+                    confirmed_bits: Some(FeatureBitset {
+                        words: stale.confirmed_words,
+                    }),
+                    confirmed_burst_bits: stale.confirmed_burst,
+                    computed_bits: Some(FeatureBitset {
+                        words: stale.computed_words,
+                    }),
+                    computed_burst_bits: stale.computed_burst,
+                    override_applied: stale.override_applied,
+                    detected_bits: Some(FeatureBitset {
+                        words: stale.detected_words,
+                    }),
+                    detected_burst_bits: stale.detected_burst,
+                    detected_at: timestamp_to_proto(&stale.detected_at),
+                    ruleset_version: stale.ruleset_version,
+                }
+            })
+            .collect();
+
+        Ok(Response::new(ListStaleConfirmationsResponse {
+            last_check: last_check.map(stale_check_to_proto),
+            stale,
+        }))
+    }
+
+    async fn get_discrepancy_report(
+        &self,
+        request: Request<DiscrepancyReportRequest>,
+    ) -> Result<Response<DiscrepancyReport>, Status> {
+        let _api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        let limit = match req.limit {
+            None => 1000,
+            Some(limit) if limit > 0 => limit as usize,
+            Some(_) => return Err(invalid_argument("limit", "must be positive")),
+        };
+        let kinds = req
+            .kinds()
+            .map(|kind| match kind {
+                DiscrepancyKind::BitsDiffer => Ok(report::DiscrepancyKind::BitsDiffer),
+                DiscrepancyKind::OverrideOnly => Ok(report::DiscrepancyKind::OverrideOnly),
+                DiscrepancyKind::ConfirmationOnly => Ok(report::DiscrepancyKind::ConfirmationOnly),
+                DiscrepancyKind::Unspecified => Err(invalid_argument("kinds", "must not contain UNSPECIFIED")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let discrepancies = report::discrepancies(self.db.pool())
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        let mut response = DiscrepancyReport::default();
+        for discrepancy in discrepancies {
+            match discrepancy.kind {
+                report::DiscrepancyKind::BitsDiffer => response.bits_differ += 1,
+                report::DiscrepancyKind::OverrideOnly => response.override_only += 1,
+                report::DiscrepancyKind::ConfirmationOnly => response.confirmation_only += 1,
+            }
+            if response.discrepancies.len() < limit && (kinds.is_empty() || kinds.contains(&discrepancy.kind)) {
+                response.discrepancies.push(discrepancy_to_proto(discrepancy));
+            }
+        }
+
+        Ok(Response::new(response))
+    }
+
+    async fn watch_changes(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchChangesStream>, Status> {
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        let req = request.into_inner();
+
+        for data_type in &req.data_types {
+            if !DATA_TYPES.contains(&data_type.as_str()) {
+                return Err(invalid_argument("data_types", format!("unknown data_type {}", data_type)));
+            }
+        }
+        let heartbeat = match req.heartbeat_seconds {
+            None => DEFAULT_HEARTBEAT_SECONDS,
+            Some(seconds) if seconds > 0 => seconds as u64,
+            Some(_) => return Err(invalid_argument("heartbeat_seconds", "must be positive")),
+        };
+
+        // Subscribe before reading the backlog so nothing committed in between is missed.
+        let changes = self.changes.subscribe();
+        let last_revision = match req.from_revision {
+            Some(revision) => revision,
+            None => history::latest_revision(self.db.pool())
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?,
+        };
+
+        info!(
+            "WatchChanges subscription from client: {} (after revision {})",
+            api_key.client_name, last_revision
+        );
+
+        let watcher = ChangeWatcher {
+            pool: self.db.pool().clone(),
+            data_types: req.data_types.into_iter().collect(),
+            last_revision,
+        };
+        let (tx, rx) = mpsc::channel(128);
+        tokio::spawn(watcher.run(changes, tx, std::time::Duration::from_secs(heartbeat)));
+
+        Ok(Response::new(tokio_stream::wrappers::ReceiverStream::new(rx)))
+    }
+
+    type WatchChangesStream =
+        tokio_stream::wrappers::ReceiverStream<Result<ChangeEvent, Status>>;
+
+    async fn sync(
+        &self,
+        request: Request<tonic::Streaming<SyncClientMessage>>,
+    ) -> Result<Response<Self::SyncStream>, Status> {
+        let api_key = extract_api_key(&request)?;
+        let api_key = authenticate_api_key(&api_key, &self.auth).await?;
+
+        let session = SyncSession {
+            pool: self.db.pool().clone(),
+            changes: self.changes.clone(),
+            api_key,
+            bit_policy: self.bit_policy,
+        };
+        let (tx, rx) = mpsc::channel(128);
+        tokio::spawn(session.run(request.into_inner(), tx));
+
+        Ok(Response::new(tokio_stream::wrappers::ReceiverStream::new(rx)))
+    }
+
+    type SyncStream =
+        tokio_stream::wrappers::ReceiverStream<Result<SyncServerMessage, Status>>;
+
+    async fn record_sync(
+        &self,
+        request: Request<SyncRecord>,
+    ) -> Result<Response<()>, Status> {
+        let api_key = authenticate_request(&request, &self.auth).await?;
+        let claim = self.idempotency.claim(request.metadata(), &api_key.client_name, "RecordSync").await?;
+        let req = request.into_inner();
+        let request_hash = RequestHash::of(&req);
+        if let Claim::Replay(stored) = claim {
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        if req.sync_type != "push" && req.sync_type != "pull" {
+            return Err(invalid_argument("sync_type", "must be 'push' or 'pull'"));
+        }
+        if !DATA_TYPES.contains(&req.data_type.as_str()) {
+            return Err(invalid_argument("data_type", format!("unknown data_type {}", req.data_type)));
+        }
+        let client_id = if req.client_id.is_empty() {
+            api_key.client_name
+        } else {
+            req.client_id
+        };
+
+        record_sync_metadata(
+            self.db.pool(),
+            &client_id,
+            &req.sync_type,
+            &req.data_type,
+            i64::from(req.items_count),
+        )
+        .await
+        .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        claim.complete(request_hash, &()).await;
+        Ok(Response::new(()))
+    }
+}
+
+/// Per-subscriber state of a WatchChanges stream.
+struct ChangeWatcher {
+    pool: sqlx::SqlitePool,
+    data_types: HashSet<String>,
+    last_revision: i64,
+}
+
+impl ChangeWatcher {
+    async fn run(
+        mut self,
+        mut changes: broadcast::Receiver<ChangeRecord>,
+        tx: mpsc::Sender<Result<ChangeEvent, Status>>,
+        heartbeat: std::time::Duration,
+    ) {
+        if let Err(status) = self.catch_up(&tx).await {
+            let _ = tx.send(Err(status)).await;
+            return;
+        }
+
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + heartbeat, heartbeat);
+
+        loop {
+            let sent = tokio::select! {
+                _ = tx.closed() => break,
+                _ = ticker.tick() => self.send_heartbeat(&tx).await,
+                received = changes.recv() => match received {
+                    Ok(change) => self.receive(&tx, change).await,
+                    // Fell behind the broadcast buffer; the database has everything we missed.
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("WatchChanges subscriber lagged by {} changes, catching up", skipped);
+                        self.catch_up(&tx).await
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+
+            if let Err(status) = sent {
+                let _ = tx.send(Err(status)).await;
+                break;
+            }
+            if tx.is_closed() {
+                break;
+            }
+        }
+    }
+
+    async fn catch_up(&mut self, tx: &mpsc::Sender<Result<ChangeEvent, Status>>) -> Result<(), Status> {
+        loop {
+            let batch = history::changes_since(&self.pool, self.last_revision, CATCH_UP_BATCH_SIZE)
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+            let done = (batch.len() as i64) < CATCH_UP_BATCH_SIZE;
+
+            for change in batch {
+                self.send_change(tx, change).await?;
+            }
+            if done || tx.is_closed() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handles a change from the broadcast. Writers publish after they commit, so broadcasts can
+    /// arrive out of revision order; a gap means an earlier change is still on its way, and the
+    /// database already has it.
+    async fn receive(
+        &mut self,
+        tx: &mpsc::Sender<Result<ChangeEvent, Status>>,
+        change: ChangeRecord,
+    ) -> Result<(), Status> {
+        if change.revision > self.last_revision + 1 {
+            self.catch_up(tx).await?;
+        }
+        self.send_change(tx, change).await
+    }
+
+    async fn send_change(
+        &mut self,
+        tx: &mpsc::Sender<Result<ChangeEvent, Status>>,
+        change: ChangeRecord,
+    ) -> Result<(), Status> {
+        // Changes already delivered by the catch-up are also in the broadcast buffer.
+        if change.revision <= self.last_revision {
+            return Ok(());
+        }
+        self.last_revision = change.revision;
+
+        if !self.data_types.is_empty() && !self.data_types.contains(change.payload.data_type()) {
+            return Ok(());
+        }
+        // A closed channel is handled by the caller.
+        let _ = tx.send(Ok(change_event_to_proto(change))).await;
+        Ok(())
+    }
+
+    /// A heartbeat is not a change, so its `revision` stays 0 and a client tracking the resume
+    /// cursor never picks it up; the current revision is only in `Heartbeat.latest_revision`.
+    async fn send_heartbeat(&self, tx: &mpsc::Sender<Result<ChangeEvent, Status>>) -> Result<(), Status> {
+        let event = ChangeEvent {
+            changed_at: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+            payload: Some(change_event::Payload::Heartbeat(Heartbeat {
+                server_time: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+                latest_revision: self.last_revision,
+            })),
+            ..Default::default()
+        };
+        let _ = tx.send(Ok(event)).await;
+        Ok(())
+    }
+}
+
+impl AdminServer {
+    /// Shared by the streaming push and the unary batch upsert of feature overrides.
+    async fn push_feature_override_items<S>(&self, request: Request<S>, rpc: &'static str) -> Result<Response<PushResponse>, Status>
+    where
+        S: Stream<Item = Result<FeatureOverride, Status>> + Unpin + Send,
+    {
+        let api_key = extract_api_key(&request)?;
+        let api_key = authenticate_api_key(&api_key, &self.auth).await?;
+        require_write_permission(&api_key)?;
+
+        let dry_run = is_dry_run(&request);
+        // A dry run changes nothing, so there is nothing to protect from replays
+        let claim = if dry_run {
+            Claim::Disabled
+        } else {
+            self.idempotency.claim(request.metadata(), &api_key.client_name, rpc).await?
+        };
+        let mut items = request.into_inner();
+        let mut request_hash = RequestHash::default();
+
+        if let Claim::Replay(stored) = claim {
+            while let Some(feature_override) = items.next().await.transpose()? {
+                request_hash.update(&feature_override);
+            }
+            info!("{} replayed a stored response for client: {}", rpc, api_key.client_name);
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        let mut response = PushResponse {
+            dry_run,
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let mut chunk = PushChunk::new();
+        let validator = self.bit_validator().await?;
+
+        while let Some(mut feature_override) = items.next().await.transpose()? {
+            request_hash.update(&feature_override);
+            response.items_received += 1;
+            pronunciation::normalize_in_place(&mut feature_override.pronunciation);
+            let key = feature_override.pronunciation.clone();
+
+            if dry_run {
+                let preview = self
+                    .preview_feature_override(&feature_override, &mut seen)
+                    .await
+                    .and_then(|preview| Ok((preview, validator.check_override(&feature_override)?)));
+                match preview {
+                    Ok(((entry, error_code), warnings)) => record_diff_entry(&mut response, entry, error_code, warnings),
+                    Err(e) => record_item_result(&mut response, rejected_item(key, &e)),
+                }
+                continue;
+            }
+
+            if chunk.is_full() {
+                self.flush_feature_overrides(&mut chunk, &mut response, &api_key.client_name).await;
+            }
+            let timestamps = match validate_feature_override(&feature_override) {
+                Ok(timestamps) => timestamps,
+                Err(e) => {
+                    chunk.reject(key, &e);
+                    continue;
+                }
+            };
+            let first = seen.insert(key.clone());
+            match validator.check_override(&feature_override) {
+                Ok(_) if !first => chunk.duplicate(key, "pronunciation appears more than once in this push"),
+                Ok(warnings) => chunk.push(key, stamped(feature_override, timestamps), warnings),
+                Err(e) => chunk.reject(key, &e),
+            }
+        }
+        self.flush_feature_overrides(&mut chunk, &mut response, &api_key.client_name).await;
+
+        info!(
+            "{} completed{}: {} received, {} created, {} updated, {} unchanged, {} conflicting, {} errors",
+            rpc,
+            if dry_run { " (dry run)" } else { "" },
+            response.items_received,
+            response.items_created,
+            response.items_updated,
+            response.items_unchanged,
+            response.items_conflicting,
+            response.errors.len()
+        );
+
+        if is_final(&response) {
+            claim.complete(request_hash, &response).await;
+        }
+        Ok(Response::new(response))
+    }
+
+    /// Shared by the streaming push and the unary batch upsert of rule patterns.
+    async fn push_rule_pattern_items<S>(&self, request: Request<S>, rpc: &'static str) -> Result<Response<PushResponse>, Status>
+    where
+        S: Stream<Item = Result<RulePattern, Status>> + Unpin + Send,
+    {
+        let api_key = extract_api_key(&request)?;
+        let api_key = authenticate_api_key(&api_key, &self.auth).await?;
+        require_write_permission(&api_key)?;
+
+        let dry_run = is_dry_run(&request);
+        // A dry run changes nothing, so there is nothing to protect from replays
+        let claim = if dry_run {
+            Claim::Disabled
+        } else {
+            self.idempotency.claim(request.metadata(), &api_key.client_name, rpc).await?
+        };
+        let mut items = request.into_inner();
+        let mut request_hash = RequestHash::default();
+
+        if let Claim::Replay(stored) = claim {
+            while let Some(rule_pattern) = items.next().await.transpose()? {
+                request_hash.update(&rule_pattern);
+            }
+            info!("{} replayed a stored response for client: {}", rpc, api_key.client_name);
+            return Ok(Response::new(stored.reply(request_hash)?));
+        }
+
+        let mut response = PushResponse {
+            dry_run,
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let mut chunk = PushChunk::new();
+
+        while let Some(rule_pattern) = items.next().await.transpose()? {
+            request_hash.update(&rule_pattern);
+            response.items_received += 1;
+            let key = rule_pattern_key(&rule_pattern);
+
+            if dry_run {
+                match self.preview_rule_pattern(&rule_pattern, &mut seen).await {
+                    Ok((entry, error_code)) => record_diff_entry(&mut response, entry, error_code, Vec::new()),
+                    Err(e) => record_item_result(&mut response, rejected_item(key, &e)),
+                }
+                continue;
+            }
+
+            if chunk.is_full() {
+                self.flush_rule_patterns(&mut chunk, &mut response, &api_key.client_name).await;
+            }
+            match validate_rule_pattern(&rule_pattern) {
+                Ok(_) if !seen.insert(key.clone()) => {
+                    chunk.duplicate(key, "keyword/pattern appears more than once in this push")
+                }
+                Ok(timestamps) => chunk.push(key, stamped(rule_pattern, timestamps), Vec::new()),
+                Err(e) => chunk.reject(key, &e),
+            }
+        }
+        self.flush_rule_patterns(&mut chunk, &mut response, &api_key.client_name).await;
+
+        info!(
+            "{} completed{}: {} received, {} created, {} updated, {} unchanged, {} conflicting, {} errors",
+            rpc,
+            if dry_run { " (dry run)" } else { "" },
+            response.items_received,
+            response.items_created,
+            response.items_updated,
+            response.items_unchanged,
+            response.items_conflicting,
+            response.errors.len()
+        );
+
+        if is_final(&response) {
+            claim.complete(request_hash, &response).await;
+        }
+        Ok(Response::new(response))
+    }
+
+    /// Writes a push chunk in one transaction and records a result for each of its items.
+    async fn flush_feature_overrides(
+        &self,
+        chunk: &mut PushChunk<FeatureOverride>,
+        response: &mut PushResponse,
+        actor: &str,
+    ) {
+        if chunk.is_empty() {
+            return;
+        }
+        let applied = async {
+            let mut tx = self.db.pool().begin().await?;
+            let applied = apply_feature_override_chunk(&mut tx, &chunk.items(), actor).await?;
+            tx.commit().await?;
+            Ok(applied)
+        }
+        .await;
+
+        for (result, change) in chunk.finish(applied) {
+            self.publish(change);
+            record_item_result(response, result);
+        }
+    }
+
+    async fn flush_rule_patterns(&self, chunk: &mut PushChunk<RulePattern>, response: &mut PushResponse, actor: &str) {
+        if chunk.is_empty() {
+            return;
+        }
+        let applied = async {
+            let mut tx = self.db.pool().begin().await?;
+            let applied = apply_rule_pattern_chunk(&mut tx, &chunk.items(), actor).await?;
+            ruleset::record_current(&mut tx).await?;
+            tx.commit().await?;
+            Ok(applied)
+        }
+        .await;
+
+        for (result, change) in chunk.finish(applied) {
+            self.publish(change);
+            record_item_result(response, result);
+        }
+    }
+
+    async fn flush_cards(&self, chunk: &mut PushChunk<Card>, response: &mut PushResponse, actor: &str) {
+        if chunk.is_empty() {
+            return;
+        }
+        let applied = async {
+            let mut tx = self.db.pool().begin().await?;
+            let applied = apply_card_chunk(&mut tx, &chunk.items(), actor).await?;
+            tx.commit().await?;
+            Ok(applied)
+        }
+        .await;
+
+        for (result, change) in chunk.finish(applied) {
+            self.publish(change);
+            record_item_result(response, result);
+        }
+    }
+
+    /// Loads the feature catalog when the pull asked for decoded feature names.
+    async fn catalog_for(&self, request: &PullRequest) -> Result<Option<FeatureCatalog>, Status> {
+        if !request.include_feature_names {
+            return Ok(None);
+        }
+        FeatureCatalog::load(self.db.pool())
+            .await
+            .map(Some)
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))
+    }
+
+    /// Writes one pushed definition. Like the chunked pushes, a name already pushed earlier
+    /// in the same push and a definition older than the stored one are conflicts.
+    async fn upsert_feature_definition(
+        &self,
+        definition: &FeatureDefinition,
+        seen: &mut HashSet<String>,
+        actor: &str,
+    ) -> Result<PushItemResult, anyhow::Error> {
+        let key = definition.name.clone();
+        let (_, updated_at) = validate_feature_definition(definition)?;
+        let mut tx = self.db.pool().begin().await?;
+        check_bit_available(&mut tx, definition).await?;
+
+        if !seen.insert(key.clone()) {
+            let reason = "name appears more than once in this push".to_string();
+            return Ok(conflict_item(key, PushErrorCode::DuplicateItem, reason));
+        }
+        let stored_updated_at = sqlx::query_scalar!("SELECT updated_at FROM feature_definition WHERE name = ?", key)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(reason) = stored_updated_at.and_then(|stored| stale_reason(&stored, &timestamp::format(&updated_at))) {
+            return Ok(conflict_item(key, PushErrorCode::StaleUpdate, reason));
+        }
+
+        let applied = apply_feature_definition(&mut tx, definition, actor).await?;
+        ruleset::record_current(&mut tx).await?;
+        tx.commit().await?;
+        self.publish(applied.change);
+
+        Ok(applied_item(key, applied.kind))
+    }
+
+    /// Returns the warnings to report with the confirmation.
+    async fn upsert_confirmation(&self, req: &ConfirmRequest, actor: &str) -> Result<Vec<String>, anyhow::Error> {
+        let mut tx = self.db.pool().begin().await?;
+        let warnings = BitValidator::load(&mut *tx, self.bit_policy).await?.check_confirmation(req)?;
+        let applied = apply_confirmation(&mut tx, req, actor).await?;
+        tx.commit().await?;
+        self.publish(applied.change);
+
+        Ok(warnings)
+    }
+
+    async fn delete_confirmation(&self, pronunciation: &str, actor: &str) -> Result<bool, anyhow::Error> {
+        let mut tx = self.db.pool().begin().await?;
+
+        let Some(existing) = history::current_confirmation(&mut tx, pronunciation).await? else {
+            return Ok(false);
+        };
+
+        sqlx::query!(
+            "DELETE FROM feature_confirmation WHERE pronunciation = ?",
+            pronunciation
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let change =
+            history::record_confirmation_change(&mut tx, pronunciation, actor, Some(&existing), None).await?;
+
+        tx.commit().await?;
+        self.publish(change);
+        Ok(true)
+    }
+
+    /// Classifies a pushed override against the stored row without writing anything.
+    async fn preview_feature_override(
+        &self,
+        feature_override: &FeatureOverride,
+        seen: &mut HashSet<String>,
+    ) -> Result<(PushDiffEntry, PushErrorCode), anyhow::Error> {
+        let (_, updated_at) = validate_feature_override(feature_override)?;
+
+        let existing = sqlx::query(
+            "SELECT pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note, extra_bits
+             FROM card_feature_override WHERE pronunciation = ?",
+        )
+        .bind(&feature_override.pronunciation)
+        .fetch_optional(self.db.pool())
+        .await?
+        .map(|row| feature_override_from_row(&row));
+
+        // Show the bitset the write would store, with any stored words it keeps
+        let stored_extra = existing
+            .as_ref()
+            .map(|old| bitset::encode_extra(bitset_words(&old.feature_bits)));
+        let new_values = override_values(feature_override, stored_extra.as_deref());
+        let new_override = FeatureOverride {
+            fixed_bits1: new_values.fixed_bits1,
+            fixed_bits2: new_values.fixed_bits2,
+            feature_bits: bitset_to_proto(new_values.fixed_bits1, new_values.fixed_bits2, &new_values.extra_bits),
+            ..feature_override.clone()
+        };
+
+        let mut entry = PushDiffEntry {
+            key: feature_override.pronunciation.clone(),
+            ..Default::default()
+        };
+
+        let mut error_code = PushErrorCode::Unspecified;
+        let kind = if !seen.insert(feature_override.pronunciation.clone()) {
+            entry.conflict_reason = Some("pronunciation appears more than once in this push".to_string());
+            error_code = PushErrorCode::DuplicateItem;
+            ChangeKind::Conflict
+        } else {
+            match &existing {
+                None => ChangeKind::Created,
+                Some(old) => match newer_stored_timestamp(old.updated_at.as_ref(), updated_at) {
+                    Some(reason) => {
+                        entry.conflict_reason = Some(reason);
+                        error_code = PushErrorCode::StaleUpdate;
+                        ChangeKind::Conflict
+                    }
+                    None if old.feature_bits == new_override.feature_bits
+                        && old.fixed_burst_bits == new_override.fixed_burst_bits
+                        && old.note == new_override.note =>
+                    {
+                        ChangeKind::Unchanged
+                    }
+                    None => ChangeKind::Updated,
+                },
+            }
+        };
+
+        entry.old_override = existing;
+        entry.new_override = Some(new_override);
+        entry.set_kind(kind);
+        Ok((entry, error_code))
+    }
+
+    /// Classifies a pushed rule pattern against the stored row without writing anything.
+    async fn preview_rule_pattern(
+        &self,
+        rule_pattern: &RulePattern,
+        seen: &mut HashSet<String>,
+    ) -> Result<(PushDiffEntry, PushErrorCode), anyhow::Error> {
+        let (_, updated_at) = validate_rule_pattern(rule_pattern)?;
+
+        let existing = sqlx::query(
+            "SELECT keyword, pattern, feature_name, is_enabled, created_at, updated_at
+             FROM rule_pattern WHERE keyword = ? AND pattern = ?",
+        )
+        .bind(&rule_pattern.keyword)
+        .bind(&rule_pattern.pattern)
+        .fetch_optional(self.db.pool())
+        .await?
+        .map(|row| rule_pattern_from_row(&row));
+
+        let key = rule_pattern_key(rule_pattern);
+        let mut entry = PushDiffEntry {
+            key: key.clone(),
+            new_rule: Some(rule_pattern.clone()),
+            ..Default::default()
+        };
+
+        let mut error_code = PushErrorCode::Unspecified;
+        let kind = if !seen.insert(key) {
+            entry.conflict_reason = Some("keyword/pattern appears more than once in this push".to_string());
+            error_code = PushErrorCode::DuplicateItem;
+            ChangeKind::Conflict
+        } else {
+            match &existing {
+                None => ChangeKind::Created,
+                Some(old) => match newer_stored_timestamp(old.updated_at.as_ref(), updated_at) {
+                    Some(reason) => {
+                        entry.conflict_reason = Some(reason);
+                        error_code = PushErrorCode::StaleUpdate;
+                        ChangeKind::Conflict
+                    }
+                    None if old.feature_name == rule_pattern.feature_name
+                        && old.is_enabled == rule_pattern.is_enabled =>
+                    {
+                        ChangeKind::Unchanged
+                    }
+                    None => ChangeKind::Updated,
+                },
+            }
+        };
+
+        entry.old_rule = existing;
+        entry.set_kind(kind);
+        Ok((entry, error_code))
+    }
+
+    /// Classifies a pushed feature definition against the stored row without writing anything.
+    async fn preview_feature_definition(
+        &self,
+        definition: &FeatureDefinition,
+        seen: &mut HashSet<String>,
+    ) -> Result<(PushDiffEntry, PushErrorCode), anyhow::Error> {
+        let (_, updated_at) = validate_feature_definition(definition)?;
+        let mut conn = self.db.pool().acquire().await?;
+        check_bit_available(&mut conn, definition).await?;
+
+        let existing = sqlx::query("SELECT * FROM feature_definition WHERE name = ?")
+            .bind(&definition.name)
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|row| feature_definition_from_row(&row));
+
+        let mut entry = PushDiffEntry {
+            key: definition.name.clone(),
+            new_definition: Some(definition.clone()),
+            ..Default::default()
+        };
+
+        let mut error_code = PushErrorCode::Unspecified;
+        let kind = if !seen.insert(definition.name.clone()) {
+            entry.conflict_reason = Some("name appears more than once in this push".to_string());
+            error_code = PushErrorCode::DuplicateItem;
+            ChangeKind::Conflict
+        } else {
+            match &existing {
+                None => ChangeKind::Created,
+                Some(old) => match newer_stored_timestamp(old.updated_at.as_ref(), updated_at) {
+                    Some(reason) => {
+                        entry.conflict_reason = Some(reason);
+                        error_code = PushErrorCode::StaleUpdate;
+                        ChangeKind::Conflict
+                    }
+                    None if old.bit_field == definition.bit_field
+                        && old.bit_index == definition.bit_index
+                        && old.description == definition.description
+                        && old.category == definition.category =>
+                    {
+                        ChangeKind::Unchanged
+                    }
+                    None => ChangeKind::Updated,
+                },
+            }
+        };
+
+        entry.old_definition = existing;
+        entry.set_kind(kind);
+        Ok((entry, error_code))
+    }
+
+    /// Classifies a pushed card against the stored row without writing anything.
+    async fn preview_card(
+        &self,
+        card: &Card,
+        seen: &mut HashSet<String>,
+    ) -> Result<(PushDiffEntry, PushErrorCode), anyhow::Error> {
+        let (_, updated_at) = validate_card(card)?;
+
+        let existing = sqlx::query("SELECT * FROM card WHERE pronunciation = ?")
+            .bind(&card.pronunciation)
+            .fetch_optional(self.db.pool())
+            .await?
+            .map(|row| card_from_row(&row));
+
+        let mut entry = PushDiffEntry {
+            key: card.pronunciation.clone(),
+            new_card: Some(card.clone()),
+            ..Default::default()
+        };
+
+        let mut error_code = PushErrorCode::Unspecified;
+        let kind = if !seen.insert(card.pronunciation.clone()) {
+            entry.conflict_reason = Some("pronunciation appears more than once in this push".to_string());
+            error_code = PushErrorCode::DuplicateItem;
+            ChangeKind::Conflict
+        } else {
+            match &existing {
+                None => ChangeKind::Created,
+                Some(old) => match newer_stored_timestamp(old.updated_at.as_ref(), updated_at) {
+                    Some(reason) => {
+                        entry.conflict_reason = Some(reason);
+                        error_code = PushErrorCode::StaleUpdate;
+                        ChangeKind::Conflict
+                    }
+                    None if old.name == card.name
+                        && old.card_number == card.card_number
+                        && old.set_code == card.set_code
+                        && old.card_text == card.card_text
+                        && old.burst_text == card.burst_text =>
+                    {
+                        ChangeKind::Unchanged
+                    }
+                    None => ChangeKind::Updated,
+                },
+            }
+        };
+
+        entry.old_card = existing;
+        entry.set_kind(kind);
+        Ok((entry, error_code))
+    }
+}
+
+/// Result of writing one item inside a caller-owned transaction.
+pub(crate) struct AppliedChange {
+    pub kind: ChangeKind,
+    /// Publish after the transaction commits
+    pub change: Option<ChangeRecord>,
+    /// Why the item was not written, when `kind` is a conflict
+    pub conflict: Option<String>,
+}
+
+impl AppliedChange {
+    pub(crate) fn new(existed: bool, change: Option<ChangeRecord>) -> Self {
+        let kind = match (existed, &change) {
+            (false, _) => ChangeKind::Created,
+            (true, Some(_)) => ChangeKind::Updated,
+            (true, None) => ChangeKind::Unchanged,
+        };
+        Self {
+            kind,
+            change,
+            conflict: None,
+        }
+    }
+
+    /// The stored row is newer than the pushed item, which is left unwritten.
+    pub(crate) fn stale(reason: String) -> Self {
+        Self {
+            kind: ChangeKind::Conflict,
+            change: None,
+            conflict: Some(reason),
+        }
+    }
+}
+
+pub(crate) async fn apply_feature_override(
+    conn: &mut SqliteConnection,
+    feature_override: &FeatureOverride,
+    actor: &str,
+) -> Result<AppliedChange, anyhow::Error> {
+    let (created_at, updated_at) = validate_feature_override(feature_override)?;
+
+    let existing = history::current_override(conn, &feature_override.pronunciation).await?;
+    let new_values = override_values(feature_override, existing.as_ref().map(|old| old.extra_bits.as_slice()));
+
+    let created_at_str = timestamp::format(&created_at);
+    let updated_at_str = timestamp::format(&updated_at);
+
+    // An update keeps the stored created_at, as the chunked push does
+    sqlx::query!(
+        "INSERT INTO card_feature_override
+         (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits, created_at, updated_at, note, extra_bits)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(pronunciation) DO UPDATE SET
+             fixed_bits1 = excluded.fixed_bits1,
+             fixed_bits2 = excluded.fixed_bits2,
+             fixed_burst_bits = excluded.fixed_burst_bits,
+             updated_at = excluded.updated_at,
+             note = excluded.note,
+             extra_bits = excluded.extra_bits",
+        feature_override.pronunciation,
+        new_values.fixed_bits1,
+        new_values.fixed_bits2,
+        new_values.fixed_burst_bits,
+        created_at_str,
+        updated_at_str,
+        new_values.note,
+        new_values.extra_bits
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let change = history::record_override_change(
+        conn,
+        &feature_override.pronunciation,
+        actor,
+        existing.as_ref(),
+        Some(&new_values),
+        None,
+    )
+    .await?;
+
+    Ok(AppliedChange::new(existing.is_some(), change))
+}
+
+pub(crate) async fn apply_rule_pattern(
+    conn: &mut SqliteConnection,
+    rule_pattern: &RulePattern,
+    actor: &str,
+) -> Result<AppliedChange, anyhow::Error> {
+    let (created_at, updated_at) = validate_rule_pattern(rule_pattern)?;
+
+    let existing = history::current_rule(conn, &rule_pattern.keyword, &rule_pattern.pattern).await?;
+
+    let created_at_str = timestamp::format(&created_at);
+    let updated_at_str = timestamp::format(&updated_at);
+
+    sqlx::query!(
+        "INSERT INTO rule_pattern (keyword, pattern, feature_name, is_enabled, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         ON CONFLICT(keyword, pattern) DO UPDATE SET
+             feature_name = excluded.feature_name,
+             is_enabled = excluded.is_enabled,
+             updated_at = excluded.updated_at",
+        rule_pattern.keyword,
+        rule_pattern.pattern,
+        rule_pattern.feature_name,
+        rule_pattern.is_enabled,
+        created_at_str,
+        updated_at_str
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let new_values = RuleValues {
+        feature_name: rule_pattern.feature_name.clone(),
+        is_enabled: rule_pattern.is_enabled,
+    };
+    let change = history::record_rule_change(
+        conn,
+        &rule_pattern.keyword,
+        &rule_pattern.pattern,
+        actor,
+        existing.as_ref(),
+        Some(&new_values),
+    )
+    .await?;
+
+    Ok(AppliedChange::new(existing.is_some(), change))
+}
+
+pub(crate) async fn apply_feature_definition(
+    conn: &mut SqliteConnection,
+    definition: &FeatureDefinition,
+    actor: &str,
+) -> Result<AppliedChange, anyhow::Error> {
+    let (created_at, updated_at) = validate_feature_definition(definition)?;
+    check_bit_available(conn, definition).await?;
+
+    let existing = history::current_definition(conn, &definition.name).await?;
+
+    let created_at_str = timestamp::format(&created_at);
+    let updated_at_str = timestamp::format(&updated_at);
+    let new_values = definition_values(definition);
+
+    sqlx::query!(
+        "INSERT INTO feature_definition (name, bit_field, bit_index, description, category, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET
+             bit_field = excluded.bit_field,
+             bit_index = excluded.bit_index,
+             description = excluded.description,
+             category = excluded.category,
+             updated_at = excluded.updated_at",
+        definition.name,
+        new_values.bit_field,
+        new_values.bit_index,
+        new_values.description,
+        new_values.category,
+        created_at_str,
+        updated_at_str
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let change = history::record_definition_change(
+        conn,
+        &definition.name,
+        actor,
+        existing.as_ref(),
+        Some(&new_values),
+    )
+    .await?;
+
+    Ok(AppliedChange::new(existing.is_some(), change))
+}
+
+/// Rejects a definition whose bit already belongs to a feature with another name.
+async fn check_bit_available(conn: &mut SqliteConnection, definition: &FeatureDefinition) -> Result<(), anyhow::Error> {
+    let bit_field = definition_values(definition).bit_field;
+    let owner = sqlx::query_scalar!(
+        "SELECT name FROM feature_definition WHERE bit_field = ? AND bit_index = ? AND name != ?",
+        bit_field,
+        definition.bit_index,
+        definition.name
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match owner {
+        Some(owner) => Err(validation_error(
+            "bit_index",
+            format!("bit {} of {} is already assigned to {}", definition.bit_index, bit_field, owner),
+        )),
+        None => Ok(()),
+    }
+}
+
+pub(crate) async fn apply_card(
+    conn: &mut SqliteConnection,
+    card: &Card,
+    actor: &str,
+) -> Result<AppliedChange, anyhow::Error> {
+    let (created_at, updated_at) = validate_card(card)?;
+
+    let existing = history::current_card(conn, &card.pronunciation).await?;
+
+    let created_at_str = timestamp::format(&created_at);
+    let updated_at_str = timestamp::format(&updated_at);
+
+    sqlx::query!(
+        "INSERT INTO card (pronunciation, name, card_number, set_code, card_text, burst_text, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(pronunciation) DO UPDATE SET
+             name = excluded.name,
+             card_number = excluded.card_number,
+             set_code = excluded.set_code,
+             card_text = excluded.card_text,
+             burst_text = excluded.burst_text,
+             updated_at = excluded.updated_at",
+        card.pronunciation,
+        card.name,
+        card.card_number,
+        card.set_code,
+        card.card_text,
+        card.burst_text,
+        created_at_str,
+        updated_at_str
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let change =
+        history::record_card_change(conn, &card.pronunciation, actor, existing.as_ref(), Some(&card_values(card)))
+            .await?;
+
+    Ok(AppliedChange::new(existing.is_some(), change))
+}
+
+pub(crate) async fn apply_confirmation(
+    conn: &mut SqliteConnection,
+    req: &ConfirmRequest,
+    actor: &str,
+) -> Result<AppliedChange, anyhow::Error> {
+    pronunciation_key("pronunciation", &req.pronunciation)?;
+    validate_bitset(
+        req.feature_bits.as_ref(),
+        [("feature_bits1", req.feature_bits1), ("feature_bits2", req.feature_bits2)],
+    )?;
+
+    // A release name is stored as the version it names
+    let rule_version = match req.rule_version.as_deref() {
+        Some(value) => Some(ruleset::resolve(conn, value).await?.ok_or_else(|| {
+            validation_error(
+                "rule_version",
+                "must be a known ruleset version or release name (GetSyncStatus reports the current version)",
+            )
+        })?),
+        None => None,
+    };
+
+    let existing = history::current_confirmation(conn, &req.pronunciation).await?;
+    let (feature_bits1, feature_bits2, extra_bits) = written_bits(
+        req.feature_bits.as_ref(),
+        (req.feature_bits1, req.feature_bits2),
+        existing.as_ref().map(|old| old.extra_bits.as_slice()),
+    );
+    let new_values = ConfirmationValues {
+        confirmed_by: actor.to_string(),
+        rule_version,
+        feature_bits1,
+        feature_bits2,
+        burst_bits: req.burst_bits,
+        extra_bits,
+    };
+    let confirmed_at = timestamp::now();
+
+    sqlx::query!(
+        "INSERT INTO feature_confirmation
+         (pronunciation, confirmed_at, confirmed_by, rule_version, feature_bits1, feature_bits2, burst_bits, extra_bits)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(pronunciation) DO UPDATE SET
+             confirmed_at = excluded.confirmed_at,
+             confirmed_by = excluded.confirmed_by,
+             rule_version = excluded.rule_version,
+             feature_bits1 = excluded.feature_bits1,
+             feature_bits2 = excluded.feature_bits2,
+             burst_bits = excluded.burst_bits,
+             extra_bits = excluded.extra_bits",
+        req.pronunciation,
+        confirmed_at,
+        actor,
+        new_values.rule_version,
+        new_values.feature_bits1,
+        new_values.feature_bits2,
+        new_values.burst_bits,
+        new_values.extra_bits
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let change = history::record_confirmation_change(
+        conn,
+        &req.pronunciation,
+        actor,
+        existing.as_ref(),
+        Some(&new_values),
+    )
+    .await?;
+
+    Ok(AppliedChange::new(existing.is_some(), change))
+}
+
+/// Returns true when the client set the `dry-run` metadata entry.
+fn is_dry_run<T>(request: &Request<T>) -> bool {
+    request
+        .metadata()
+        .get("dry-run")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(false)
+}
+
+fn record_diff_entry(response: &mut PushResponse, entry: PushDiffEntry, error_code: PushErrorCode, warnings: Vec<String>) {
+    record_item_result(
+        response,
+        PushItemResult {
+            key: entry.key.clone(),
+            outcome: entry.kind,
+            error_code: error_code as i32,
+            message: entry.conflict_reason.clone(),
+            warnings,
+            ..Default::default()
+        },
+    );
+    response.diff.push(entry);
+}
+
+/// Counts one item's outcome and adds its result. Rejected items are also listed in
+/// `errors` for clients that predate per-item results.
+fn record_item_result(response: &mut PushResponse, result: PushItemResult) {
+    match result.outcome() {
+        ChangeKind::Created => response.items_created += 1,
+        ChangeKind::Updated => response.items_updated += 1,
+        ChangeKind::Unchanged => response.items_unchanged += 1,
+        ChangeKind::Conflict => response.items_conflicting += 1,
+        ChangeKind::Rejected => response.errors.push(format!(
+            "Error processing {}: {}",
+            result.key,
+            result.message.as_deref().unwrap_or_default()
+        )),
+        ChangeKind::Unspecified => {}
+    }
+    response.results.push(result);
+}
+
+/// Describes the conflict when the stored row is newer than the pushed one.
+fn newer_stored_timestamp(
+    stored: Option<&prost_types::Timestamp>,
+    pushed: chrono::DateTime<chrono::Utc>,
+) -> Option<String> {
+    let stored = stored.and_then(|ts| chrono::DateTime::from_timestamp(ts.seconds, ts.nanos as u32))?;
+    (stored > pushed).then(|| {
+        format!(
+            "stored row was updated at {}, after the pushed updated_at {}",
+            timestamp::format(&stored),
+            timestamp::format(&pushed)
+        )
+    })
+}
+
+fn history_action_to_proto(action: &str) -> HistoryAction {
+    match action {
+        "create" => HistoryAction::Create,
+        "update" => HistoryAction::Update,
+        "delete" => HistoryAction::Delete,
+        _ => HistoryAction::Unspecified,
+    }
+}
+
+fn history_entry_to_proto(entry: HistoryRow) -> OverrideHistoryEntry {
+    let values_to_proto = |values: HistoryValues| OverrideValues {
+        fixed_bits1: values.fixed_bits1,
+        fixed_bits2: values.fixed_bits2,
+        fixed_burst_bits: values.fixed_burst_bits,
+        note: values.note,
+        feature_bits: bitset_to_proto(values.fixed_bits1, values.fixed_bits2, &values.extra_bits),
+    };
+
+    OverrideHistoryEntry {
+        version: entry.version,
+        pronunciation: entry.pronunciation,
+        action: history_action_to_proto(&entry.action) as i32,
+        actor: entry.actor,
+        changed_at: timestamp_to_proto(&entry.changed_at),
+        old_values: entry.old_values.map(values_to_proto),
+        new_values: entry.new_values.map(values_to_proto),
+        reverted_from: entry.reverted_from,
+    }
+}
+
+pub(crate) fn change_event_to_proto(change: ChangeRecord) -> ChangeEvent {
+    let data_type = change.payload.data_type().to_string();
+    // Creates and updates carry the new state, deletes the last state before deletion.
+    let payload = match change.payload {
+        ChangePayload::FeatureOverride { pronunciation, old, new } => new.or(old).map(|values| {
+            change_event::Payload::FeatureOverride(override_snapshot_to_proto(Snapshot {
+                key: pronunciation,
+                values,
+                created_at: change.changed_at.clone(),
+                updated_at: change.changed_at.clone(),
+            }))
+        }),
+        ChangePayload::RulePattern { keyword, pattern, old, new } => new.or(old).map(|values| {
+            change_event::Payload::RulePattern(rule_snapshot_to_proto(Snapshot {
+                key: (keyword, pattern),
+                values,
+                created_at: change.changed_at.clone(),
+                updated_at: change.changed_at.clone(),
+            }))
+        }),
+        ChangePayload::ConfirmedFeature { pronunciation, old, new } => new.or(old).map(|values| {
+            change_event::Payload::ConfirmedFeature(confirmation_snapshot_to_proto(Snapshot {
+                key: pronunciation,
+                values,
+                created_at: change.changed_at.clone(),
+                updated_at: change.changed_at.clone(),
+            }))
+        }),
+        ChangePayload::FeatureDefinition { name, old, new } => new.or(old).map(|values| {
+            change_event::Payload::FeatureDefinition(definition_snapshot_to_proto(Snapshot {
+                key: name,
+                values,
+                created_at: change.changed_at.clone(),
+                updated_at: change.changed_at.clone(),
+            }))
+        }),
+        ChangePayload::Card { pronunciation, old, new } => new.or(old).map(|values| {
+            change_event::Payload::Card(card_snapshot_to_proto(Snapshot {
+                key: pronunciation,
+                values,
+                created_at: change.changed_at.clone(),
+                updated_at: change.changed_at.clone(),
+            }))
+        }),
+    };
+
+    ChangeEvent {
+        revision: change.revision,
+        data_type,
+        action: history_action_to_proto(&change.action) as i32,
+        actor: change.actor,
+        changed_at: timestamp_to_proto(&change.changed_at),
+        payload,
+    }
+}
+
+fn override_snapshot_to_proto(snapshot: Snapshot<String, HistoryValues>) -> FeatureOverride {
+    FeatureOverride {
+        pronunciation: snapshot.key,
+        fixed_bits1: snapshot.values.fixed_bits1,
+        fixed_bits2: snapshot.values.fixed_bits2,
+        fixed_burst_bits: snapshot.values.fixed_burst_bits,
+        created_at: timestamp_to_proto(&snapshot.created_at),
+        updated_at: timestamp_to_proto(&snapshot.updated_at),
+        feature_bits: bitset_to_proto(
+            snapshot.values.fixed_bits1,
+            snapshot.values.fixed_bits2,
+            &snapshot.values.extra_bits,
+        ),
+        note: snapshot.values.note,
+        feature_names: Vec::new(),
+    }
+}
+
+fn rule_snapshot_to_proto(snapshot: Snapshot<(String, String), RuleValues>) -> RulePattern {
+    let (keyword, pattern) = snapshot.key;
+    RulePattern {
+        keyword,
+        pattern,
+        feature_name: snapshot.values.feature_name,
+        is_enabled: snapshot.values.is_enabled,
+        created_at: timestamp_to_proto(&snapshot.created_at),
+        updated_at: timestamp_to_proto(&snapshot.updated_at),
+    }
+}
+
+fn confirmation_snapshot_to_proto(snapshot: Snapshot<String, ConfirmationValues>) -> ConfirmedFeature {
+    ConfirmedFeature {
+        pronunciation: snapshot.key,
+        confirmed_at: timestamp_to_proto(&snapshot.updated_at),
+        confirmed_by: snapshot.values.confirmed_by,
+        rule_version: snapshot.values.rule_version,
+        feature_bits1: snapshot.values.feature_bits1,
+        feature_bits2: snapshot.values.feature_bits2,
+        burst_bits: snapshot.values.burst_bits,
+        feature_bits: bitset_to_proto(
+            snapshot.values.feature_bits1,
+            snapshot.values.feature_bits2,
+            &snapshot.values.extra_bits,
+        ),
+        feature_names: Vec::new(),
+    }
+}
+
+fn definition_snapshot_to_proto(snapshot: Snapshot<String, DefinitionValues>) -> FeatureDefinition {
+    FeatureDefinition {
+        name: snapshot.key,
+        bit_field: bit_field_to_proto(&snapshot.values.bit_field) as i32,
+        bit_index: snapshot.values.bit_index as i32,
+        description: snapshot.values.description,
+        category: snapshot.values.category,
+        created_at: timestamp_to_proto(&snapshot.created_at),
+        updated_at: timestamp_to_proto(&snapshot.updated_at),
+    }
+}
+
+fn card_snapshot_to_proto(snapshot: Snapshot<String, CardValues>) -> Card {
+    Card {
+        pronunciation: snapshot.key,
+        name: snapshot.values.name,
+        card_number: snapshot.values.card_number,
+        set_code: snapshot.values.set_code,
+        card_text: snapshot.values.card_text,
+        burst_text: snapshot.values.burst_text,
+        created_at: timestamp_to_proto(&snapshot.created_at),
+        updated_at: timestamp_to_proto(&snapshot.updated_at),
+    }
+}
+
+fn bit_field_to_proto(bit_field: &str) -> FeatureBitField {
+    match BitField::parse(bit_field) {
+        Some(BitField::Bits1) => FeatureBitField::Bits1,
+        Some(BitField::Bits2) => FeatureBitField::Bits2,
+        Some(BitField::Burst) => FeatureBitField::Burst,
+        Some(BitField::Extended) => FeatureBitField::Extended,
+        None => FeatureBitField::Unspecified,
+    }
+}
+
+fn bit_field_from_proto(bit_field: FeatureBitField) -> Option<BitField> {
+    match bit_field {
+        FeatureBitField::Bits1 => Some(BitField::Bits1),
+        FeatureBitField::Bits2 => Some(BitField::Bits2),
+        FeatureBitField::Burst => Some(BitField::Burst),
+        FeatureBitField::Extended => Some(BitField::Extended),
+        FeatureBitField::Unspecified => None,
+    }
+}
+
+/// Stored form of a validated definition.
+fn definition_values(definition: &FeatureDefinition) -> DefinitionValues {
+    DefinitionValues {
+        bit_field: bit_field_from_proto(definition.bit_field())
+            .map(BitField::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        bit_index: i64::from(definition.bit_index),
+        description: definition.description.clone(),
+        category: definition.category.clone(),
+    }
+}
+
+/// Stored form of a card.
+pub(crate) fn card_values(card: &Card) -> CardValues {
+    CardValues {
+        name: card.name.clone(),
+        card_number: card.card_number.clone(),
+        set_code: card.set_code.clone(),
+        card_text: card.card_text.clone(),
+        burst_text: card.burst_text.clone(),
+    }
+}
+
+fn rule_pattern_key(rule_pattern: &RulePattern) -> String {
+    format!("{}/{}", rule_pattern.keyword, rule_pattern.pattern)
+}
+
+pub(crate) fn timestamp_from_proto(
+    ts: Option<&prost_types::Timestamp>,
+    field: &str,
+) -> Result<chrono::DateTime<chrono::Utc>, anyhow::Error> {
+    match ts {
+        // google.protobuf.Timestamp only covers 0001-01-01 to 9999-12-31 with nanos below one second
+        Some(ts) if (MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&ts.seconds) => u32::try_from(ts.nanos)
+            .ok()
+            .filter(|nanos| *nanos < 1_000_000_000)
+            .and_then(|nanos| chrono::DateTime::from_timestamp(ts.seconds, nanos))
+            .ok_or_else(|| validation_error(field, "nanos must be between 0 and 999,999,999")),
+        Some(_) => Err(validation_error(field, "must be between 0001-01-01 and 9999-12-31")),
+        None => Ok(chrono::Utc::now()),
+    }
+}
+
+fn timestamp_to_proto(value: &str) -> Option<prost_types::Timestamp> {
+    timestamp::parse(value).map(|dt| prost_types::Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
+    })
+}
+
+/// Normalizes a pronunciation for use as a key, reporting an invalid one against `field`.
+pub(crate) fn pronunciation_key(field: &str, raw: &str) -> Result<String, anyhow::Error> {
+    pronunciation::normalize(raw).map_err(|description| validation_error(field, description))
+}
+
+/// Normalizes a pronunciation prefix, reporting an invalid one against `field`.
+pub(crate) fn pronunciation_prefix(field: &str, raw: &str) -> Result<String, anyhow::Error> {
+    pronunciation::normalize_prefix(raw).map_err(|description| validation_error(field, description))
+}
+
+/// Checks bit fields against the feature catalog under the configured [`BitPolicy`].
+pub(crate) struct BitValidator {
+    policy: BitPolicy,
+    catalog: FeatureCatalog,
+}
+
+impl BitValidator {
+    pub(crate) async fn load<'e>(executor: impl sqlx::SqliteExecutor<'e>, policy: BitPolicy) -> Result<Self, anyhow::Error> {
+        let catalog = match policy {
+            BitPolicy::Off => FeatureCatalog::default(),
+            BitPolicy::Reject | BitPolicy::Warn => FeatureCatalog::load(executor).await?,
+        };
+        Ok(Self { policy, catalog })
+    }
+
+    /// Words kept from the stored row by a write without feature_bits are not checked again.
+    pub(crate) fn check_override(&self, feature_override: &FeatureOverride) -> Result<Vec<String>, anyhow::Error> {
+        let (bits1, bits2, extra) = written_bits(
+            feature_override.feature_bits.as_ref(),
+            (feature_override.fixed_bits1, feature_override.fixed_bits2),
+            None,
+        );
+        self.check([
+            ("fixed_bits1", BitField::Bits1, vec![bits1 as u64]),
+            ("fixed_bits2", BitField::Bits2, vec![bits2 as u64]),
+            ("fixed_burst_bits", BitField::Burst, vec![feature_override.fixed_burst_bits as u64]),
+            ("feature_bits", BitField::Extended, bitset::decode_extra(&extra)),
+        ])
+    }
+
+    pub(crate) fn check_confirmation(&self, req: &ConfirmRequest) -> Result<Vec<String>, anyhow::Error> {
+        let (bits1, bits2, extra) =
+            written_bits(req.feature_bits.as_ref(), (req.feature_bits1, req.feature_bits2), None);
+        self.check([
+            ("feature_bits1", BitField::Bits1, vec![bits1 as u64]),
+            ("feature_bits2", BitField::Bits2, vec![bits2 as u64]),
+            ("burst_bits", BitField::Burst, vec![req.burst_bits as u64]),
+            ("feature_bits", BitField::Extended, bitset::decode_extra(&extra)),
+        ])
+    }
+
+    /// Returns the warnings to report with the item, or a validation error when the policy
+    /// rejects it. Nothing is checked while the catalog is empty.
+    fn check(&self, fields: [(&str, BitField, Vec<u64>); 4]) -> Result<Vec<String>, anyhow::Error> {
+        if self.policy == BitPolicy::Off || self.catalog.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut warnings = Vec::new();
+        for (name, field, value) in fields {
+            let bits = self.catalog.undefined_bits(field, &value);
+            if bits.is_empty() {
+                continue;
+            }
+            let description = describe_undefined_bits(&bits);
+            match self.policy {
+                BitPolicy::Reject => return Err(validation_error(name, description)),
+                BitPolicy::Warn | BitPolicy::Off => warnings.push(format!("{}: {}", name, description)),
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+/// Checks a bitset written alongside the int64 fields it overlaps: those must be 0 or agree
+/// with the bitset's words 0 and 1.
+fn validate_bitset(bitset: Option<&FeatureBitset>, legacy: [(&str, i64); 2]) -> Result<(), anyhow::Error> {
+    let Some(bitset) = bitset else {
+        return Ok(());
+    };
+    if bitset.words.len() > bitset::MAX_WORDS {
+        return Err(validation_error(
+            "feature_bits",
+            format!("must have at most {} words", bitset::MAX_WORDS),
+        ));
+    }
+    for (index, (field, value)) in legacy.into_iter().enumerate() {
+        let word = bitset.words.get(index).copied().unwrap_or(0);
+        if value != 0 && value as u64 != word {
+            return Err(validation_error(field, format!("must be 0 or equal word {} of feature_bits", index)));
+        }
+    }
+    Ok(())
+}
+
+/// Resolves what a write stores as (bits1, bits2, extra_bits). A bitset replaces everything;
+/// without one, a client that only knows bits1 / bits2 replaces those and keeps `stored_extra`.
+pub(crate) fn written_bits(
+    bitset: Option<&FeatureBitset>,
+    (bits1, bits2): (i64, i64),
+    stored_extra: Option<&[u8]>,
+) -> (i64, i64, Vec<u8>) {
+    match bitset {
+        Some(bitset) => {
+            let word = |index: usize| bitset.words.get(index).copied().unwrap_or(0) as i64;
+            (word(0), word(1), bitset::encode_extra(&bitset.words))
+        }
+        None => (bits1, bits2, stored_extra.unwrap_or_default().to_vec()),
+    }
+}
+
+/// The values an override write stores, given the stored row's extra_bits.
+pub(crate) fn override_values(feature_override: &FeatureOverride, stored_extra: Option<&[u8]>) -> HistoryValues {
+    let (fixed_bits1, fixed_bits2, extra_bits) = written_bits(
+        feature_override.feature_bits.as_ref(),
+        (feature_override.fixed_bits1, feature_override.fixed_bits2),
+        stored_extra,
+    );
+    HistoryValues {
+        fixed_bits1,
+        fixed_bits2,
+        fixed_burst_bits: feature_override.fixed_burst_bits,
+        note: feature_override.note.clone(),
+        extra_bits,
+    }
+}
+
+fn bitset_to_proto(bits1: i64, bits2: i64, extra_bits: &[u8]) -> Option<FeatureBitset> {
+    Some(FeatureBitset {
+        words: bitset::words(bits1, bits2, extra_bits),
+    })
+}
+
+fn bitset_words(bitset: &Option<FeatureBitset>) -> &[u64] {
+    bitset.as_ref().map_or(&[], |bitset| bitset.words.as_slice())
+}
+
+type ValidatedTimestamps = (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>);
+
+fn stamped<T>(item: T, (created_at, updated_at): ValidatedTimestamps) -> StampedItem<T> {
+    StampedItem {
+        item,
+        created_at: timestamp::format(&created_at),
+        updated_at: timestamp::format(&updated_at),
+    }
+}
+
+pub(crate) fn validate_feature_override(feature_override: &FeatureOverride) -> Result<ValidatedTimestamps, anyhow::Error> {
+    pronunciation_key("pronunciation", &feature_override.pronunciation)?;
+    validate_bitset(
+        feature_override.feature_bits.as_ref(),
+        [("fixed_bits1", feature_override.fixed_bits1), ("fixed_bits2", feature_override.fixed_bits2)],
+    )?;
+    Ok((
+        timestamp_from_proto(feature_override.created_at.as_ref(), "created_at")?,
+        timestamp_from_proto(feature_override.updated_at.as_ref(), "updated_at")?,
+    ))
+}
+
+pub(crate) fn validate_rule_pattern(rule_pattern: &RulePattern) -> Result<ValidatedTimestamps, anyhow::Error> {
+    if rule_pattern.keyword.trim().is_empty() {
+        return Err(validation_error("keyword", "must not be empty"));
+    }
+    if rule_pattern.pattern.is_empty() {
+        return Err(validation_error("pattern", "must not be empty"));
+    }
+    if let Err(e) = rules::compile(&rule_pattern.pattern) {
+        return Err(validation_error("pattern", format!("does not compile: {}", e)));
+    }
+    if rule_pattern.feature_name.trim().is_empty() {
+        return Err(validation_error("feature_name", "must not be empty"));
+    }
+    Ok((
+        timestamp_from_proto(rule_pattern.created_at.as_ref(), "created_at")?,
+        timestamp_from_proto(rule_pattern.updated_at.as_ref(), "updated_at")?,
+    ))
+}
+
+pub(crate) fn validate_feature_definition(definition: &FeatureDefinition) -> Result<ValidatedTimestamps, anyhow::Error> {
+    if definition.name.trim().is_empty() {
+        return Err(validation_error("name", "must not be empty"));
+    }
+    if definition.name.trim() != definition.name {
+        return Err(validation_error("name", "must not start or end with whitespace"));
+    }
+    let Some(bit_field) = bit_field_from_proto(definition.bit_field()) else {
+        return Err(validation_error("bit_field", "must be BITS1, BITS2, BURST or EXTENDED"));
+    };
+    if usize::try_from(definition.bit_index).map_or(true, |index| index >= bit_field.width()) {
+        return Err(validation_error(
+            "bit_index",
+            format!("must be between 0 and {} for {}", bit_field.width() - 1, bit_field.as_str()),
+        ));
+    }
+    Ok((
+        timestamp_from_proto(definition.created_at.as_ref(), "created_at")?,
+        timestamp_from_proto(definition.updated_at.as_ref(), "updated_at")?,
+    ))
+}
+
+pub(crate) fn validate_card(card: &Card) -> Result<ValidatedTimestamps, anyhow::Error> {
+    pronunciation_key("pronunciation", &card.pronunciation)?;
+    for (field, value) in [("name", &card.name), ("card_number", &card.card_number), ("set_code", &card.set_code)] {
+        // Only cards migrated from bare texts lack these; pushes must give them
+        let value = value.as_deref().unwrap_or_default();
+        if value.trim().is_empty() {
+            return Err(validation_error(field, "must not be empty"));
+        }
+        if value.trim() != value {
+            return Err(validation_error(field, "must not start or end with whitespace"));
+        }
+    }
+    if card.card_text.trim().is_empty() {
+        return Err(validation_error("card_text", "must not be empty"));
+    }
+    if card.burst_text.as_ref().is_some_and(|text| text.trim().is_empty()) {
+        return Err(validation_error("burst_text", "must not be empty; leave it unset for cards without one"));
+    }
+    Ok((
+        timestamp_from_proto(card.created_at.as_ref(), "created_at")?,
+        timestamp_from_proto(card.updated_at.as_ref(), "updated_at")?,
+    ))
+}
+
+fn release_to_proto(release: ruleset::Release) -> RulesetRelease {
+    RulesetRelease {
+        name: release.name,
+        version: release.version,
+        note: release.note,
+        created_by: release.created_by,
+        created_at: timestamp_to_proto(&release.created_at),
+    }
+}
+
+fn stale_check_to_proto(summary: staleness::CheckSummary) -> StaleCheckSummary {
+    StaleCheckSummary {
+        ruleset_version: summary.ruleset_version,
+        confirmations_checked: summary.checked,
+        confirmations_stale: summary.stale,
+        confirmations_overridden: summary.overridden,
+        confirmations_without_text: summary.without_text,
+        checked_at: timestamp_to_proto(&summary.checked_at),
+    }
+}
+
+fn discrepancy_to_proto(discrepancy: report::Discrepancy) -> Discrepancy {
+    let kind = match discrepancy.kind {
+        report::DiscrepancyKind::BitsDiffer => DiscrepancyKind::BitsDiffer,
+        report::DiscrepancyKind::OverrideOnly => DiscrepancyKind::OverrideOnly,
+        report::DiscrepancyKind::ConfirmationOnly => DiscrepancyKind::ConfirmationOnly,
+    };
+    let (override_bits, override_burst_bits) = match discrepancy.override_bits {
+        Some((words, burst)) => (Some(FeatureBitset { words }), burst),
+        None => (None, 0),
+    };
+    let (confirmed_bits, confirmed_burst_bits) = match discrepancy.confirmed_bits {
+        Some((words, burst)) => (Some(FeatureBitset { words }), burst),
+        None => (None, 0),
+    };
+
+    Discrepancy {
+        pronunciation: discrepancy.pronunciation,
+        kind: kind as i32,
+        override_bits,
+        override_burst_bits,
+        confirmed_bits,
+        confirmed_burst_bits,
+        differences: discrepancy
+            .differences
+            .into_iter()
+            .map(|difference| BitDifference {
+                bit_field: bit_field_to_proto(difference.field.as_str()) as i32,
+                bit_index: difference.index as i32,
+                feature_name: difference.feature_name,
+                in_override: difference.in_override,
+                in_confirmation: difference.in_confirmation,
+            })
+            .collect(),
+    }
+}
+
+fn rule_ref(rule: &rules::Rule) -> RuleRef {
+    RuleRef {
+        keyword: rule.keyword.clone(),
+        pattern: rule.pattern.clone(),
+        feature_name: rule.feature_name.clone(),
+    }
+}
+
+fn rule_match_to_proto(found: rules::RuleMatch) -> RuleMatch {
+    RuleMatch {
+        keyword: found.rule.keyword.clone(),
+        pattern: found.rule.pattern.clone(),
+        feature_name: found.rule.feature_name.clone(),
+        start: found.span.start as i32,
+        end: found.span.end as i32,
+        matched_text: found.span.text,
+    }
+}
+
+fn invalid_patterns_to_proto(rule_set: &RuleSet) -> Vec<InvalidRulePattern> {
+    rule_set
+        .invalid
+        .iter()
+        .map(|invalid| InvalidRulePattern {
+            keyword: invalid.keyword.clone(),
+            pattern: invalid.pattern.clone(),
+            error: invalid.error.clone(),
+        })
+        .collect()
+}
+
+pub(crate) fn feature_override_from_row(row: &SqliteRow) -> FeatureOverride {
+    let created_at: String = row.get("created_at");
+    let updated_at: String = row.get("updated_at");
+    let extra_bits: Vec<u8> = row.get("extra_bits");
+
+    FeatureOverride {
+        pronunciation: row.get("pronunciation"),
+        fixed_bits1: row.get("fixed_bits1"),
+        fixed_bits2: row.get("fixed_bits2"),
+        fixed_burst_bits: row.get("fixed_burst_bits"),
+        created_at: timestamp_to_proto(&created_at),
+        updated_at: timestamp_to_proto(&updated_at),
+        note: row.get("note"),
+        feature_names: Vec::new(),
+        feature_bits: bitset_to_proto(row.get("fixed_bits1"), row.get("fixed_bits2"), &extra_bits),
+    }
+}
+
+pub(crate) fn rule_pattern_from_row(row: &SqliteRow) -> RulePattern {
+    let created_at: String = row.get("created_at");
+    let updated_at: String = row.get("updated_at");
+
+    RulePattern {
+        keyword: row.get("keyword"),
+        pattern: row.get("pattern"),
+        feature_name: row.get("feature_name"),
+        is_enabled: row.get("is_enabled"),
+        created_at: timestamp_to_proto(&created_at),
+        updated_at: timestamp_to_proto(&updated_at),
+    }
+}
+
+pub(crate) fn confirmed_feature_from_row(row: &SqliteRow) -> ConfirmedFeature {
+    let confirmed_at: String = row.get("confirmed_at");
+    let extra_bits: Vec<u8> = row.get("extra_bits");
+
+    ConfirmedFeature {
+        pronunciation: row.get("pronunciation"),
+        confirmed_at: timestamp_to_proto(&confirmed_at),
+        confirmed_by: row.get("confirmed_by"),
+        rule_version: row.get("rule_version"),
+        feature_bits1: row.get("feature_bits1"),
+        feature_bits2: row.get("feature_bits2"),
+        burst_bits: row.get("burst_bits"),
+        feature_names: Vec::new(),
+        feature_bits: bitset_to_proto(row.get("feature_bits1"), row.get("feature_bits2"), &extra_bits),
+    }
+}
+
+pub(crate) fn feature_definition_from_row(row: &SqliteRow) -> FeatureDefinition {
+    let bit_field: String = row.get("bit_field");
+    let bit_index: i64 = row.get("bit_index");
+    let created_at: String = row.get("created_at");
+    let updated_at: String = row.get("updated_at");
+
+    FeatureDefinition {
+        name: row.get("name"),
+        bit_field: bit_field_to_proto(&bit_field) as i32,
+        bit_index: bit_index as i32,
+        description: row.get("description"),
+        category: row.get("category"),
+        created_at: timestamp_to_proto(&created_at),
+        updated_at: timestamp_to_proto(&updated_at),
+    }
+}
+
+pub(crate) fn card_from_row(row: &SqliteRow) -> Card {
+    let created_at: String = row.get("created_at");
+    let updated_at: String = row.get("updated_at");
+
+    Card {
+        pronunciation: row.get("pronunciation"),
+        name: row.get("name"),
+        card_number: row.get("card_number"),
+        set_code: row.get("set_code"),
+        card_text: row.get("card_text"),
+        burst_text: row.get("burst_text"),
+        created_at: timestamp_to_proto(&created_at),
+        updated_at: timestamp_to_proto(&updated_at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{record_definition_change, DefinitionValues};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_pool() -> sqlx::SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn define(pool: &sqlx::SqlitePool, name: &str, bit_index: i64) -> ChangeRecord {
+        let values = DefinitionValues {
+            bit_field: "fixed_bits1".to_string(),
+            bit_index,
+            description: None,
+            category: None,
+        };
+        let mut conn = pool.acquire().await.unwrap();
+        record_definition_change(&mut conn, name, "test", None, Some(&values))
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// A server on the test pool, with a read-write API key for it
+    async fn test_server() -> (AdminServer, String) {
+        let pool = test_pool().await;
+        let api_key = AuthService::new(pool.clone()).generate_api_key("test", "read_write").await.unwrap();
+        (AdminServer::new(Database::from_pool(pool)), api_key)
+    }
+
+    fn authorized<T>(message: T, api_key: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("api-key", api_key.parse().unwrap());
+        request
+    }
+
+    fn override_with(pronunciation: &str, bits: u64, note: Option<&str>) -> FeatureOverride {
+        FeatureOverride {
+            pronunciation: pronunciation.to_string(),
+            fixed_bits1: bits as i64,
+            feature_bits: Some(FeatureBitset { words: vec![bits] }),
+            note: note.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    async fn push_overrides(
+        server: &AdminServer,
+        api_key: &str,
+        items: &[FeatureOverride],
+        dry_run: bool,
+    ) -> PushResponse {
+        let mut request = authorized(tokio_stream::iter(items.iter().cloned().map(Ok)), api_key);
+        if dry_run {
+            request.metadata_mut().insert("dry-run", "true".parse().unwrap());
+        }
+        server.push_feature_override_items(request, "PushFeatureOverrides").await.unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn dry_run_previews_what_the_push_writes() {
+        let (server, api_key) = test_server().await;
+        push_overrides(&server, &api_key, &[override_with("ア", 1, None), override_with("ウ", 2, None)], false).await;
+
+        let items = [
+            override_with("ア", 3, Some("updated")),
+            override_with("イ", 4, None),
+            override_with("ウ", 2, None),
+            override_with("ア", 5, None),
+        ];
+        let preview = push_overrides(&server, &api_key, &items, true).await;
+        assert!(preview.dry_run);
+        let kinds: Vec<_> = preview.diff.iter().map(|entry| (entry.key.as_str(), entry.kind())).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("ア", ChangeKind::Updated),
+                ("イ", ChangeKind::Created),
+                ("ウ", ChangeKind::Unchanged),
+                ("ア", ChangeKind::Conflict),
+            ]
+        );
+        assert_eq!(preview.diff[0].old_override.as_ref().unwrap().fixed_bits1, 1);
+
+        // Nothing was written by the preview
+        let stored: Vec<String> = sqlx::query_scalar("SELECT pronunciation FROM card_feature_override ORDER BY 1")
+            .fetch_all(server.db.pool())
+            .await
+            .unwrap();
+        assert_eq!(stored, ["ア", "ウ"]);
+
+        let written = push_overrides(&server, &api_key, &items, false).await;
+        let outcomes = |response: &PushResponse| {
+            let counts = (
+                response.items_created,
+                response.items_updated,
+                response.items_unchanged,
+                response.items_conflicting,
+            );
+            let results: Vec<_> = response.results.iter().map(|item| (item.key.clone(), item.outcome())).collect();
+            (counts, results)
+        };
+        assert_eq!(outcomes(&preview), outcomes(&written));
+
+        for entry in preview.diff.iter().filter(|entry| entry.kind() != ChangeKind::Conflict) {
+            let previewed = entry.new_override.as_ref().unwrap();
+            let (bits1, note): (i64, Option<String>) =
+                sqlx::query_as("SELECT fixed_bits1, note FROM card_feature_override WHERE pronunciation = ?")
+                    .bind(&entry.key)
+                    .fetch_one(server.db.pool())
+                    .await
+                    .unwrap();
+            assert_eq!((bits1, note), (previewed.fixed_bits1, previewed.note.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_upsert_and_get_feature_overrides() {
+        let (server, api_key) = test_server().await;
+        let upsert = BatchUpsertFeatureOverridesRequest {
+            overrides: vec![override_with("ア", 1, None), override_with("イ", 2, None), override_with("ア", 3, None)],
+        };
+        let response = server
+            .batch_upsert_feature_overrides(authorized(upsert, &api_key))
+            .await
+            .unwrap()
+            .into_inner();
+        let outcomes: Vec<_> = response.results.iter().map(|item| (item.outcome(), item.error_code())).collect();
+        assert_eq!(
+            outcomes,
+            [
+                (ChangeKind::Created, PushErrorCode::Unspecified),
+                (ChangeKind::Created, PushErrorCode::Unspecified),
+                (ChangeKind::Conflict, PushErrorCode::DuplicateItem),
+            ]
+        );
+
+        // Request order, each card once however it is spelled, and unknown keys as they were sent
+        let get = BatchGetFeatureOverridesRequest {
+            pronunciations: ["イ", "あ", "ウ", "ア"].map(str::to_string).to_vec(),
+        };
+        let response = server.batch_get_feature_overrides(authorized(get, &api_key)).await.unwrap().into_inner();
+        let found: Vec<_> = response.overrides.iter().map(|o| (o.pronunciation.as_str(), o.fixed_bits1)).collect();
+        assert_eq!(found, [("イ", 2), ("ア", 1)]);
+        assert_eq!(response.not_found, ["ウ"]);
+
+        let too_many = BatchGetFeatureOverridesRequest {
+            pronunciations: vec!["ア".to_string(); MAX_BATCH_GET_SIZE + 1],
+        };
+        let status = server.batch_get_feature_overrides(authorized(too_many, &api_key)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn batch_upsert_rule_patterns_validates_like_the_push() {
+        let (server, api_key) = test_server().await;
+        let rule = |keyword: &str, pattern: &str| RulePattern {
+            keyword: keyword.to_string(),
+            pattern: pattern.to_string(),
+            feature_name: "draw".to_string(),
+            is_enabled: true,
+            ..Default::default()
+        };
+        let upsert = BatchUpsertRulePatternsRequest {
+            rule_patterns: vec![rule("draw", "カードを\\d+枚引く"), rule("", "x"), rule("broken", "(")],
+        };
+        let response = server.batch_upsert_rule_patterns(authorized(upsert, &api_key)).await.unwrap().into_inner();
+        let outcomes: Vec<_> = response.results.iter().map(|item| (item.outcome(), item.field.clone())).collect();
+        assert_eq!(
+            outcomes,
+            [
+                (ChangeKind::Created, None),
+                (ChangeKind::Rejected, Some("keyword".to_string())),
+                (ChangeKind::Rejected, Some("pattern".to_string())),
+            ]
+        );
+        assert_eq!(response.items_created, 1);
+    }
+
+    #[test]
+    fn bit_policy_decides_between_warning_and_rejecting() {
+        let catalog = FeatureCatalog::from_names([(BitField::Bits1, 0, "ドロー"), (BitField::Burst, 0, "バースト")]);
+        let validator = |policy| BitValidator { policy, catalog: catalog.clone() };
+        let unknown = override_with("ア", 0b101, None);
+        let known = override_with("イ", 0b1, None);
+
+        let warnings = validator(BitPolicy::Warn).check_override(&unknown).unwrap();
+        assert_eq!(warnings, ["fixed_bits1: bit 2 has no feature definition"]);
+        let error = validator(BitPolicy::Reject).check_override(&unknown).unwrap_err();
+        assert_eq!(errors::push_error(&error).1.as_deref(), Some("fixed_bits1"));
+        assert!(validator(BitPolicy::Off).check_override(&unknown).unwrap().is_empty());
+        assert!(validator(BitPolicy::Reject).check_override(&known).unwrap().is_empty());
+
+        let confirmation = ConfirmRequest { burst_bits: 0b10, ..Default::default() };
+        let error = validator(BitPolicy::Reject).check_confirmation(&confirmation).unwrap_err();
+        assert_eq!(errors::push_error(&error).1.as_deref(), Some("burst_bits"));
+
+        // Nothing to check against until features are defined
+        let empty = BitValidator { policy: BitPolicy::Reject, catalog: FeatureCatalog::default() };
+        assert!(empty.check_override(&unknown).unwrap().is_empty());
+
+        assert_eq!(BitPolicy::parse(" Reject "), Some(BitPolicy::Reject));
+        assert_eq!(BitPolicy::parse("strict"), None);
+    }
+
+    #[tokio::test]
+    async fn push_reports_undefined_bits_under_the_policy() {
+        let (mut server, api_key) = test_server().await;
+        sqlx::query("INSERT INTO feature_definition (name, bit_field, bit_index) VALUES ('ドロー', 'bits1', 0)")
+            .execute(server.db.pool())
+            .await
+            .unwrap();
+        let items = [override_with("ア", 0b11, None)];
+
+        server.bit_policy = BitPolicy::Warn;
+        let warned = push_overrides(&server, &api_key, &items, false).await;
+        assert_eq!(warned.results[0].outcome(), ChangeKind::Created);
+        assert_eq!(warned.results[0].warnings, ["fixed_bits1: bit 1 has no feature definition"]);
+
+        server.bit_policy = BitPolicy::Reject;
+        let rejected = push_overrides(&server, &api_key, &[override_with("イ", 0b11, None)], false).await;
+        assert_eq!(rejected.results[0].outcome(), ChangeKind::Rejected);
+        assert_eq!(rejected.results[0].error_code(), PushErrorCode::InvalidField);
+        assert_eq!(rejected.results[0].field.as_deref(), Some("fixed_bits1"));
+        assert_eq!(rejected.items_created, 0);
+    }
+
+    fn rule(keyword: &str, pattern: &str, feature_name: &str) -> RulePattern {
+        RulePattern {
+            keyword: keyword.to_string(),
+            pattern: pattern.to_string(),
+            feature_name: feature_name.to_string(),
+            is_enabled: true,
+            ..Default::default()
+        }
+    }
+
+    async fn evaluate(
+        server: &AdminServer,
+        api_key: &str,
+        text: &str,
+        pronunciation: Option<&str>,
+    ) -> EvaluateTextResponse {
+        let request = EvaluateTextRequest { text: text.to_string(), pronunciation: pronunciation.map(str::to_string) };
+        server.evaluate_text(authorized(request, api_key)).await.unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn evaluate_text_applies_a_stored_override_on_top() {
+        let (server, api_key) = test_server().await;
+        sqlx::query(
+            "INSERT INTO feature_definition (name, bit_field, bit_index)
+             VALUES ('ドロー', 'bits1', 0), ('破壊', 'bits1', 1)",
+        )
+        .execute(server.db.pool())
+        .await
+        .unwrap();
+        let rules = BatchUpsertRulePatternsRequest {
+            rule_patterns: vec![rule("引く", "カードを\\d+枚引く", "ドロー"), rule("捨てる", "手札を捨てる", "未定義")],
+        };
+        server.batch_upsert_rule_patterns(authorized(rules, &api_key)).await.unwrap();
+        push_overrides(&server, &api_key, &[override_with("ア", 0b10, None)], false).await;
+
+        let detected = evaluate(&server, &api_key, "カードを2枚引く。手札を捨てる。", None).await;
+        assert!(!detected.override_applied);
+        assert_eq!(detected.feature_names, ["ドロー"]);
+        assert_eq!(detected.feature_bits1, 0b1);
+        assert_eq!(detected.undefined_features, ["未定義"]);
+        let spans: Vec<_> = detected.matches.iter().map(|m| (m.feature_name.as_str(), m.start, m.end)).collect();
+        assert_eq!(spans, [("ドロー", 0, 8), ("未定義", 9, 15)]);
+
+        // The override replaces the result; what the rules found is still reported
+        let overridden = evaluate(&server, &api_key, "カードを2枚引く。", Some("あ")).await;
+        assert!(overridden.override_applied);
+        assert_eq!(overridden.feature_names, ["破壊"]);
+        assert_eq!(overridden.feature_bits, Some(FeatureBitset { words: vec![0b10, 0] }));
+        assert_eq!(overridden.detected_bits, Some(FeatureBitset { words: vec![0b1, 0] }));
+        assert_eq!(overridden.matches.len(), 1);
+
+        let no_override = evaluate(&server, &api_key, "カードを2枚引く。", Some("イ")).await;
+        assert!(!no_override.override_applied);
+        assert_eq!(no_override.feature_names, ["ドロー"]);
+    }
+
+    #[tokio::test]
+    async fn invalid_patterns_are_rejected_and_reported() {
+        let (server, api_key) = test_server().await;
+
+        // Too large once compiled counts as invalid too
+        let rules = BatchUpsertRulePatternsRequest {
+            rule_patterns: vec![rule("引く", "(", "ドロー"), rule("引く", "\\w{1000}{1000}", "ドロー")],
+        };
+        let response = server.batch_upsert_rule_patterns(authorized(rules, &api_key)).await.unwrap().into_inner();
+        for result in &response.results {
+            assert_eq!(result.outcome(), ChangeKind::Rejected);
+            assert_eq!(result.field.as_deref(), Some("pattern"));
+            assert!(result.message.as_deref().unwrap().contains("does not compile"));
+        }
+        assert_eq!(response.items_created, 0);
+
+        let test = |pattern: &str| {
+            let request = TestRulePatternRequest {
+                pattern: pattern.to_string(),
+                sample_texts: vec!["カードを2枚引く。".to_string(), "手札を捨てる。".to_string()],
+                ..Default::default()
+            };
+            server.test_rule_pattern(authorized(request, &api_key))
+        };
+        let invalid = test("(").await.unwrap().into_inner();
+        assert!(!invalid.valid);
+        assert!(invalid.error.is_some());
+        assert_eq!(invalid.texts_tested, 0);
+        let valid = test("\\d+枚").await.unwrap().into_inner();
+        assert!(valid.valid);
+        assert_eq!((valid.texts_tested, valid.texts_matched), (2, 1));
+        assert_eq!(valid.results[0].matches[0].text, "2枚");
+
+        // A pattern stored before validation existed is skipped by evaluation and reported
+        sqlx::query("INSERT INTO rule_pattern (keyword, pattern, feature_name) VALUES ('引く', '(', 'ドロー')")
+            .execute(server.db.pool())
+            .await
+            .unwrap();
+        let evaluated = evaluate(&server, &api_key, "カードを2枚引く。", None).await;
+        assert!(evaluated.matches.is_empty());
+        let skipped = &evaluated.invalid_patterns;
+        assert_eq!(skipped.len(), 1);
+        assert_eq!((skipped[0].keyword.as_str(), skipped[0].pattern.as_str()), ("引く", "("));
+    }
+
+    #[tokio::test]
+    async fn watcher_delivers_changes_broadcast_out_of_order() {
+        let pool = test_pool().await;
+        let first = define(&pool, "first", 0).await;
+        let second = define(&pool, "second", 1).await;
+        assert_eq!(second.revision, first.revision + 1);
+
+        let mut watcher = ChangeWatcher {
+            pool: pool.clone(),
+            data_types: HashSet::new(),
+            last_revision: first.revision - 1,
+        };
+        let (tx, mut rx) = mpsc::channel(8);
+        watcher.receive(&tx, second.clone()).await.unwrap();
+        watcher.receive(&tx, first.clone()).await.unwrap();
+        drop(tx);
+
+        let mut delivered = Vec::new();
+        while let Some(event) = rx.recv().await {
+            delivered.push(event.unwrap().revision);
+        }
+        assert_eq!(delivered, vec![first.revision, second.revision]);
+        assert_eq!(watcher.last_revision, second.revision);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_leaves_the_resume_cursor_unset() {
+        let watcher = ChangeWatcher {
+            pool: test_pool().await,
+            data_types: HashSet::new(),
+            last_revision: 5,
+        };
+        let (tx, mut rx) = mpsc::channel(1);
+        watcher.send_heartbeat(&tx).await.unwrap();
+
+        let event = rx.recv().await.unwrap().unwrap();
+        assert_eq!(event.revision, 0);
+        match event.payload {
+            Some(change_event::Payload::Heartbeat(heartbeat)) => assert_eq!(heartbeat.latest_revision, 5),
+            other => panic!("expected a heartbeat, got {:?}", other),
+        }
+    }
+
+    /// Pulls end with `Err(Status)` of code OK to attach the next-page trailer; this pins that
+    /// tonic turns it into a successful end of stream whose trailers carry the token.
+    #[tokio::test]
+    async fn next_page_token_reaches_the_client_as_a_trailer() {
+        use proto::admin_sync_client::AdminSyncClient;
+        use tonic::transport::server::TcpIncoming;
+
+        // A file, since the server's pool opens several connections
+        let path = std::env::temp_dir().join(format!("admin_backend_test_{}.db", uuid::Uuid::new_v4()));
+        let db = Database::new(&format!("sqlite://{}", path.display())).await.unwrap();
+        db.migrate().await.unwrap();
+        let api_key = AuthService::new(db.pool().clone()).generate_api_key("test", "read").await.unwrap();
+        for pronunciation in ["ア", "イ", "ウ"] {
+            let feature_override = FeatureOverride { pronunciation: pronunciation.to_string(), ..Default::default() };
+            let mut conn = db.pool().acquire().await.unwrap();
+            apply_feature_override(&mut conn, &feature_override, "test").await.unwrap();
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        let server = Server::builder().add_service(AdminSyncServer::new(AdminServer::new(db)));
+        tokio::spawn(server.serve_with_incoming(incoming));
+
+        let client = AdminSyncClient::connect(format!("http://{}", addr)).await.unwrap();
+        let pull = |page_token: Option<String>| {
+            let mut client = client.clone();
+            let mut request = Request::new(PullRequest { limit: Some(2), page_token, ..Default::default() });
+            request.metadata_mut().insert("api-key", api_key.parse().unwrap());
+            async move {
+                let mut stream = client.pull_feature_overrides(request).await.unwrap().into_inner();
+                let mut pronunciations = Vec::new();
+                while let Some(feature_override) = stream.message().await.unwrap() {
+                    pronunciations.push(feature_override.pronunciation);
+                }
+                let trailers = stream.trailers().await.unwrap().unwrap_or_default();
+                let token = trailers.get(crate::pull::NEXT_PAGE_TOKEN_TRAILER).map(|v| v.to_str().unwrap().to_string());
+                (pronunciations, token)
+            }
+        };
+
+        let (first, token) = pull(None).await;
+        assert_eq!(first, ["ア", "イ"]);
+        assert!(token.is_some());
+        let (rest, token) = pull(token).await;
+        assert_eq!(rest, ["ウ"]);
+        assert_eq!(token, None);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn write_without_feature_bits_keeps_stored_extended_words() {
+        let stored = vec![1, 2, 7];
+        let stored_extra = bitset::encode_extra(&stored);
+
+        // An old client pulls bits1 / bits2, drops feature_bits and pushes a new bits1
+        assert!(validate_bitset(None, [("fixed_bits1", 5), ("fixed_bits2", 2)]).is_ok());
+        let (bits1, bits2, extra) = written_bits(None, (5, 2), Some(&stored_extra));
+        assert_eq!(bitset::words(bits1, bits2, &extra), vec![5, 2, 7]);
+
+        // A client that sends feature_bits replaces the extended words too
+        let cleared = FeatureBitset { words: vec![5, 2] };
+        assert!(validate_bitset(Some(&cleared), [("fixed_bits1", 5), ("fixed_bits2", 0)]).is_ok());
+        let (bits1, bits2, extra) = written_bits(Some(&cleared), (5, 0), Some(&stored_extra));
+        assert_eq!((bits1, bits2, extra), (5, 2, Vec::new()));
+    }
+
+    #[test]
+    fn feature_bits_must_agree_with_legacy_fields() {
+        let bitset = FeatureBitset { words: vec![1, 2, 3] };
+        assert!(validate_bitset(Some(&bitset), [("fixed_bits1", 1), ("fixed_bits2", 2)]).is_ok());
+        assert!(validate_bitset(Some(&bitset), [("fixed_bits1", 3), ("fixed_bits2", 0)]).is_err());
+        let wide = FeatureBitset { words: vec![0; bitset::MAX_WORDS + 1] };
+        assert!(validate_bitset(Some(&wide), [("fixed_bits1", 0), ("fixed_bits2", 0)]).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Stale confirmations: confirmed bits that no longer match what the server computes for the
+//! card text under the current ruleset. Computed bits are what EvaluateText returns for the
+//! card, so a stored override takes the place of the rules. The rules are evaluated for every
+//! card all the same, and a card with an override is also stale when what the rules detect
+//! differs from its confirmation, so a rule change is never hidden behind an override.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::bitset;
+use crate::rules::RuleSet;
+use crate::ruleset;
+use crate::timestamp;
+
+/// Minutes between background checks when STALE_CHECK_INTERVAL_MINUTES is not set
+const DEFAULT_INTERVAL_MINUTES: u64 = 60;
+
+/// Outcome of one check.
+pub struct CheckSummary {
+    pub ruleset_version: String,
+    pub checked: i64,
+    pub stale: i64,
+    /// Checked confirmations whose card has an override supplying the computed bits
+    pub overridden: i64,
+    /// Confirmations that could not be checked because their card is not stored
+    pub without_text: i64,
+    pub checked_at: String,
+}
+
+/// A confirmation whose bits differ from the computed ones.
+pub struct StaleConfirmation {
+    pub pronunciation: String,
+    pub confirmed_at: String,
+    pub confirmed_by: String,
+    pub rule_version: Option<String>,
+    pub confirmed_words: Vec<u64>,
+    pub confirmed_burst: i64,
+    pub computed_words: Vec<u64>,
+    pub computed_burst: i64,
+    pub override_applied: bool,
+    /// What the rules alone detect; the computed bits unless an override applied
+    pub detected_words: Vec<u64>,
+    pub detected_burst: i64,
+    /// Ruleset the bits were computed with
+    pub ruleset_version: String,
+    pub detected_at: String,
+}
+
+struct Computed {
+    pronunciation: String,
+    confirmed_at: String,
+    words: Vec<u64>,
+    burst: i64,
+    override_applied: bool,
+    detected_words: Vec<u64>,
+    detected_burst: i64,
+}
+
+/// Reads STALE_CHECK_INTERVAL_MINUTES. 0 turns the background check off.
+pub fn interval_from_env() -> Option<Duration> {
+    let minutes = std::env::var("STALE_CHECK_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_INTERVAL_MINUTES);
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+/// Runs [`check`] now and then every `interval`.
+pub fn spawn_job(pool: SqlitePool, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match check(&pool).await {
+                Ok(summary) => info!(
+                    "Stale confirmation check: {} of {} confirmations stale under ruleset {} ({} without a stored card)",
+                    summary.stale, summary.checked, summary.ruleset_version, summary.without_text
+                ),
+                Err(e) => warn!("Stale confirmation check failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Re-evaluates every confirmed card that is stored and replaces the stale confirmation list
+/// with the ones whose bits differ from the computed or the detected bits. A confirmation that
+/// stays stale keeps the time it was first detected.
+pub async fn check(pool: &SqlitePool) -> Result<CheckSummary> {
+    // The rules, their version and the confirmations are read from one snapshot, so the
+    // recorded version is the one the bits were computed with. Every ruleset that was ever
+    // committed has its version recorded already.
+    let mut snapshot = pool.begin().await?;
+    let rule_set = RuleSet::load(&mut snapshot).await?;
+    let version = ruleset::current_version(&mut snapshot).await?;
+    let rows = sqlx::query!(
+        r#"SELECT c.pronunciation, c.confirmed_at, c.feature_bits1, c.feature_bits2, c.burst_bits, c.extra_bits,
+                  t.card_text || COALESCE(char(10) || t.burst_text, '') AS "text!: String",
+                  o.fixed_bits1 AS "override_bits1?", o.fixed_bits2 AS "override_bits2?",
+                  o.fixed_burst_bits AS "override_burst_bits?", o.extra_bits AS "override_extra_bits?"
+           FROM feature_confirmation c
+           JOIN card t ON t.pronunciation = c.pronunciation
+           LEFT JOIN card_feature_override o ON o.pronunciation = c.pronunciation"#
+    )
+    .fetch_all(&mut *snapshot)
+    .await?;
+    let without_text = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM feature_confirmation
+           WHERE pronunciation NOT IN (SELECT pronunciation FROM card)"#
+    )
+    .fetch_one(&mut *snapshot)
+    .await?;
+    snapshot.commit().await?;
+    let checked = rows.len() as i64;
+    let overridden = rows.iter().filter(|row| row.override_bits1.is_some()).count() as i64;
+
+    // Evaluating every text is CPU-bound, so it runs off the async workers
+    let stale: Vec<Computed> = tokio::task::spawn_blocking(move || {
+        rows.into_iter()
+            .filter_map(|row| {
+                let evaluation = rule_set.evaluate(&row.text);
+                let (words, burst, override_applied) = match (row.override_bits1, row.override_bits2) {
+                    (Some(bits1), Some(bits2)) => (
+                        bitset::words(bits1, bits2, &row.override_extra_bits.unwrap_or_default()),
+                        row.override_burst_bits.unwrap_or_default(),
+                        true,
+                    ),
+                    _ => (evaluation.words.clone(), evaluation.burst, false),
+                };
+                let confirmed = bitset::words(row.feature_bits1, row.feature_bits2, &row.extra_bits);
+                let matches = |words: &[u64], burst: i64| bitset::equal(words, &confirmed) && burst == row.burst_bits;
+                if matches(&words, burst) && matches(&evaluation.words, evaluation.burst) {
+                    return None;
+                }
+                Some(Computed {
+                    pronunciation: row.pronunciation,
+                    confirmed_at: row.confirmed_at,
+                    words,
+                    burst,
+                    override_applied,
+                    detected_words: evaluation.words,
+                    detected_burst: evaluation.burst,
+                })
+            })
+            .collect()
+    })
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let checked_at = timestamp::now();
+
+    let detected: HashMap<(String, String), String> =
+        sqlx::query!("SELECT pronunciation, confirmed_at, detected_at FROM stale_confirmation")
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|row| ((row.pronunciation, row.confirmed_at), row.detected_at))
+            .collect();
+
+    sqlx::query!("DELETE FROM stale_confirmation").execute(&mut *tx).await?;
+    for computed in &stale {
+        let detected_at = detected
+            .get(&(computed.pronunciation.clone(), computed.confirmed_at.clone()))
+            .unwrap_or(&checked_at);
+        let bits1 = computed.words[0] as i64;
+        let bits2 = computed.words[1] as i64;
+        let extra_bits = bitset::encode_extra(&computed.words);
+        let detected_bits1 = computed.detected_words[0] as i64;
+        let detected_bits2 = computed.detected_words[1] as i64;
+        let detected_extra_bits = bitset::encode_extra(&computed.detected_words);
+        sqlx::query!(
+            "INSERT INTO stale_confirmation
+             (pronunciation, confirmed_at, ruleset_version, computed_bits1, computed_bits2, computed_burst_bits,
+              computed_extra_bits, override_applied, detected_bits1, detected_bits2, detected_burst_bits,
+              detected_extra_bits, detected_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            computed.pronunciation,
+            computed.confirmed_at,
+            version,
+            bits1,
+            bits2,
+            computed.burst,
+            extra_bits,
+            computed.override_applied,
+            detected_bits1,
+            detected_bits2,
+            computed.detected_burst,
+            detected_extra_bits,
+            detected_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let stale_count = stale.len() as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO stale_check
+         (id, ruleset_version, confirmations_checked, confirmations_stale, confirmations_overridden,
+          confirmations_without_text, checked_at)
+         VALUES (1, ?, ?, ?, ?, ?, ?)",
+        version,
+        checked,
+        stale_count,
+        overridden,
+        without_text,
+        checked_at
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(CheckSummary {
+        ruleset_version: version,
+        checked,
+        stale: stale_count,
+        overridden,
+        without_text,
+        checked_at,
+    })
+}
+
+/// The latest check, or None before the first one.
+pub async fn last_check(pool: &SqlitePool) -> Result<Option<CheckSummary>> {
+    let summary = sqlx::query_as!(
+        CheckSummary,
+        r#"SELECT ruleset_version, confirmations_checked AS checked, confirmations_stale AS stale,
+                  confirmations_overridden AS overridden, confirmations_without_text AS without_text, checked_at
+           FROM stale_check WHERE id = 1"#
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(summary)
+}
+
+/// Stale confirmations from the latest check, oldest detection first. Cards confirmed again
+/// since that check are left out.
+pub async fn stale_confirmations(pool: &SqlitePool, limit: i64) -> Result<Vec<StaleConfirmation>> {
+    let rows = sqlx::query!(
+        r#"SELECT s.pronunciation, s.confirmed_at, c.confirmed_by, c.rule_version,
+                c.feature_bits1, c.feature_bits2, c.burst_bits, c.extra_bits,
+                s.computed_bits1, s.computed_bits2, s.computed_burst_bits, s.computed_extra_bits,
+                s.override_applied AS "override_applied: bool",
+                s.detected_bits1, s.detected_bits2, s.detected_burst_bits, s.detected_extra_bits,
+                s.ruleset_version, s.detected_at
+         FROM stale_confirmation s
+         JOIN feature_confirmation c ON c.pronunciation = s.pronunciation AND c.confirmed_at = s.confirmed_at
+         ORDER BY s.detected_at, s.pronunciation
+         LIMIT ?"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| StaleConfirmation {
+            pronunciation: row.pronunciation,
+            confirmed_at: row.confirmed_at,
+            confirmed_by: row.confirmed_by,
+            rule_version: row.rule_version,
+            confirmed_words: bitset::words(row.feature_bits1, row.feature_bits2, &row.extra_bits),
+            confirmed_burst: row.burst_bits,
+            computed_words: bitset::words(row.computed_bits1, row.computed_bits2, &row.computed_extra_bits),
+            computed_burst: row.computed_burst_bits,
+            override_applied: row.override_applied,
+            detected_words: bitset::words(row.detected_bits1, row.detected_bits2, &row.detected_extra_bits),
+            detected_burst: row.detected_burst_bits,
+            ruleset_version: row.ruleset_version,
+            detected_at: row.detected_at,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn execute(pool: &SqlitePool, sql: &str) {
+        sqlx::query(sql).execute(pool).await.unwrap();
+    }
+
+    /// Two drawing cards confirmed as detected, イ also with an override of the same bits.
+    async fn confirmed_cards() -> SqlitePool {
+        let pool = test_pool().await;
+        execute(
+            &pool,
+            "INSERT INTO feature_definition (name, bit_field, bit_index) VALUES ('ドロー', 'bits1', 0)",
+        )
+        .await;
+        execute(
+            &pool,
+            "INSERT INTO rule_pattern (keyword, pattern, feature_name) VALUES ('引く', 'カードを\\d枚引く', 'ドロー')",
+        )
+        .await;
+        for pronunciation in ["ア", "イ"] {
+            sqlx::query("INSERT INTO card (pronunciation, card_text) VALUES (?, 'カードを1枚引く。')")
+                .bind(pronunciation)
+                .execute(&pool)
+                .await
+                .unwrap();
+            sqlx::query(
+                "INSERT INTO feature_confirmation
+                 (pronunciation, confirmed_at, confirmed_by, feature_bits1, feature_bits2, burst_bits)
+                 VALUES (?, '2024-01-01T00:00:00.000Z', 'test', 1, 0, 0)",
+            )
+            .bind(pronunciation)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        execute(
+            &pool,
+            "INSERT INTO card_feature_override (pronunciation, fixed_bits1, fixed_bits2, fixed_burst_bits)
+             VALUES ('イ', 1, 0, 0)",
+        )
+        .await;
+        // A confirmation without a stored card is counted but not checked
+        execute(
+            &pool,
+            "INSERT INTO feature_confirmation
+             (pronunciation, confirmed_at, confirmed_by, feature_bits1, feature_bits2, burst_bits)
+             VALUES ('ウ', '2024-01-01T00:00:00.000Z', 'test', 1, 0, 0)",
+        )
+        .await;
+        pool
+    }
+
+    #[tokio::test]
+    async fn confirmations_matching_the_rules_are_fresh() {
+        let pool = confirmed_cards().await;
+        let summary = check(&pool).await.unwrap();
+
+        assert_eq!((summary.checked, summary.stale, summary.overridden, summary.without_text), (2, 0, 1, 1));
+        assert!(stale_confirmations(&pool, 100).await.unwrap().is_empty());
+        let last = last_check(&pool).await.unwrap().unwrap();
+        assert_eq!((last.ruleset_version, last.checked_at), (summary.ruleset_version, summary.checked_at));
+    }
+
+    #[tokio::test]
+    async fn rule_change_flags_confirmations_and_keeps_detected_at() {
+        let pool = confirmed_cards().await;
+        let before = check(&pool).await.unwrap();
+
+        // The feature moves to another bit: the rules now detect bit 2 for both cards
+        execute(&pool, "UPDATE feature_definition SET bit_index = 2").await;
+        let first = check(&pool).await.unwrap();
+        assert_ne!(first.ruleset_version, before.ruleset_version);
+        assert_eq!(first.stale, 2);
+
+        let stale = stale_confirmations(&pool, 100).await.unwrap();
+        let plain = stale.iter().find(|stale| stale.pronunciation == "ア").unwrap();
+        assert!(!plain.override_applied);
+        assert_eq!((plain.computed_words.as_slice(), plain.detected_words.as_slice()), (&[4, 0][..], &[4, 0][..]));
+        assert_eq!(plain.confirmed_words, [1, 0]);
+        assert_eq!(plain.detected_at, first.checked_at);
+        assert_eq!(plain.ruleset_version, first.ruleset_version);
+
+        // The override still gives the confirmed bits, but the rules no longer do
+        let overridden = stale.iter().find(|stale| stale.pronunciation == "イ").unwrap();
+        assert!(overridden.override_applied);
+        assert_eq!(overridden.computed_words, [1, 0]);
+        assert_eq!(overridden.detected_words, [4, 0]);
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let second = check(&pool).await.unwrap();
+        assert_ne!(second.checked_at, first.checked_at);
+        let stale = stale_confirmations(&pool, 100).await.unwrap();
+        assert_eq!(stale.len(), 2);
+        assert!(stale.iter().all(|stale| stale.detected_at == first.checked_at));
+
+        // Confirming again takes the card off the list before the next check
+        execute(
+            &pool,
+            "UPDATE feature_confirmation SET confirmed_at = '2024-02-01T00:00:00.000Z' WHERE pronunciation = 'ア'",
+        )
+        .await;
+        let stale = stale_confirmations(&pool, 100).await.unwrap();
+        assert_eq!(stale.iter().map(|stale| stale.pronunciation.as_str()).collect::<Vec<_>>(), ["イ"]);
+    }
+}
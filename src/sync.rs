@@ -0,0 +1,519 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Status, Streaming};
+use tracing::info;
+
+use crate::auth::{require_write_permission, ApiKey};
+use crate::catalog::BitPolicy;
+use crate::errors::{invalid_argument, push_error};
+use crate::history::{self, ChangeKey, ChangeRecord};
+use crate::pronunciation;
+use crate::push::PUSH_CHUNK_SIZE;
+use crate::ruleset;
+use crate::server::proto::sync_client_message::Message as ClientMessage;
+use crate::server::proto::sync_server_message::Message as ServerMessage;
+use crate::server::proto::*;
+use crate::server::{
+    apply_card, apply_confirmation, apply_feature_definition, apply_feature_override, apply_rule_pattern,
+    change_event_to_proto, AppliedChange, BitValidator,
+};
+
+pub(crate) const DATA_TYPES: [&str; 5] =
+    ["feature_override", "rule_pattern", "confirmed_feature", "feature_definition", "card"];
+
+/// Client items one session may send before committing. Sync applies them in one transaction
+/// after the commit, so they are all held in memory until then.
+pub(crate) const MAX_SYNC_ITEMS: usize = PUSH_CHUNK_SIZE * 20;
+
+/// Changes read from change_log per query while sending the client what it is missing
+const SEND_BATCH_SIZE: i64 = 500;
+
+type ServerSender = mpsc::Sender<Result<SyncServerMessage, Status>>;
+
+/// A client change held until the session commits.
+enum PendingItem {
+    FeatureOverride(FeatureOverride),
+    RulePattern(RulePattern),
+    Confirmation(ConfirmRequest),
+    FeatureDefinition(FeatureDefinition),
+    Card(Card),
+}
+
+impl PendingItem {
+    fn data_type(&self) -> &'static str {
+        match self {
+            PendingItem::FeatureOverride(_) => "feature_override",
+            PendingItem::RulePattern(_) => "rule_pattern",
+            PendingItem::Confirmation(_) => "confirmed_feature",
+            PendingItem::FeatureDefinition(_) => "feature_definition",
+            PendingItem::Card(_) => "card",
+        }
+    }
+
+    fn key(&self) -> String {
+        match self {
+            PendingItem::FeatureOverride(item) => item.pronunciation.clone(),
+            PendingItem::RulePattern(item) => format!("{}/{}", item.keyword, item.pattern),
+            PendingItem::Confirmation(item) => item.pronunciation.clone(),
+            PendingItem::FeatureDefinition(item) => item.name.clone(),
+            PendingItem::Card(item) => item.pronunciation.clone(),
+        }
+    }
+
+    fn change_key(&self) -> ChangeKey<'_> {
+        match self {
+            PendingItem::FeatureOverride(item) => ChangeKey::FeatureOverride(&item.pronunciation),
+            PendingItem::RulePattern(item) => ChangeKey::RulePattern(&item.keyword, &item.pattern),
+            PendingItem::Confirmation(item) => ChangeKey::ConfirmedFeature(&item.pronunciation),
+            PendingItem::FeatureDefinition(item) => ChangeKey::FeatureDefinition(&item.name),
+            PendingItem::Card(item) => ChangeKey::Card(&item.pronunciation),
+        }
+    }
+
+    /// Checks the item's bits, returning the warnings to report with it.
+    fn check_bits(&self, validator: &BitValidator) -> anyhow::Result<Vec<String>> {
+        match self {
+            PendingItem::FeatureOverride(item) => validator.check_override(item),
+            PendingItem::Confirmation(item) => validator.check_confirmation(item),
+            PendingItem::RulePattern(_) | PendingItem::FeatureDefinition(_) | PendingItem::Card(_) => Ok(Vec::new()),
+        }
+    }
+
+    async fn apply(&self, conn: &mut SqliteConnection, actor: &str) -> anyhow::Result<AppliedChange> {
+        match self {
+            PendingItem::FeatureOverride(item) => apply_feature_override(conn, item, actor).await,
+            PendingItem::RulePattern(item) => apply_rule_pattern(conn, item, actor).await,
+            PendingItem::Confirmation(item) => apply_confirmation(conn, item, actor).await,
+            PendingItem::FeatureDefinition(item) => apply_feature_definition(conn, item, actor).await,
+            PendingItem::Card(item) => apply_card(conn, item, actor).await,
+        }
+    }
+}
+
+/// Server side of one bidirectional Sync call.
+pub(crate) struct SyncSession {
+    pub pool: SqlitePool,
+    pub changes: broadcast::Sender<ChangeRecord>,
+    pub api_key: ApiKey,
+    pub bit_policy: BitPolicy,
+}
+
+impl SyncSession {
+    pub async fn run(self, inbound: Streaming<SyncClientMessage>, tx: ServerSender) {
+        if let Err(status) = self.sync(inbound, &tx).await {
+            let _ = tx.send(Err(status)).await;
+        }
+    }
+
+    /// Runs the session over any stream of client messages; `run` passes the gRPC request stream.
+    async fn sync(
+        &self,
+        mut inbound: impl Stream<Item = Result<SyncClientMessage, Status>> + Unpin,
+        tx: &ServerSender,
+    ) -> Result<(), Status> {
+        let start = match inbound.next().await.transpose()? {
+            Some(SyncClientMessage {
+                message: Some(ClientMessage::Start(start)),
+            }) => start,
+            _ => return Err(invalid_argument("message", "Sync must begin with a start message")),
+        };
+        for data_type in &start.data_types {
+            if !DATA_TYPES.contains(&data_type.as_str()) {
+                return Err(invalid_argument("start.data_types", format!("unknown data_type {}", data_type)));
+            }
+        }
+        if start.cursor < 0 {
+            return Err(invalid_argument("start.cursor", "must not be negative"));
+        }
+        let client_id = if start.client_id.is_empty() {
+            self.api_key.client_name.clone()
+        } else {
+            start.client_id.clone()
+        };
+
+        let mut pending = Vec::new();
+        while let Some(message) = inbound.next().await.transpose()? {
+            if pending.len() == MAX_SYNC_ITEMS && !matches!(message.message, Some(ClientMessage::Commit(_))) {
+                return Err(Status::resource_exhausted(format!(
+                    "at most {} items per Sync session; send the rest in another session",
+                    MAX_SYNC_ITEMS
+                )));
+            }
+            match message.message {
+                Some(ClientMessage::FeatureOverride(mut item)) => {
+                    pronunciation::normalize_in_place(&mut item.pronunciation);
+                    pending.push(PendingItem::FeatureOverride(item))
+                }
+                Some(ClientMessage::RulePattern(item)) => pending.push(PendingItem::RulePattern(item)),
+                Some(ClientMessage::Confirmation(mut item)) => {
+                    pronunciation::normalize_in_place(&mut item.pronunciation);
+                    pending.push(PendingItem::Confirmation(item))
+                }
+                Some(ClientMessage::FeatureDefinition(item)) => pending.push(PendingItem::FeatureDefinition(item)),
+                Some(ClientMessage::Card(mut item)) => {
+                    pronunciation::normalize_in_place(&mut item.pronunciation);
+                    pending.push(PendingItem::Card(item))
+                }
+                Some(ClientMessage::Commit(_)) => break,
+                Some(ClientMessage::Start(_)) | None => {
+                    return Err(invalid_argument("message", "expected an item or commit after start"));
+                }
+            }
+        }
+        if !pending.is_empty() {
+            require_write_permission(&self.api_key)?;
+        }
+
+        let (results, applied) = self.apply(&pending, start.cursor).await?;
+        let own_revisions: HashSet<i64> = applied.iter().map(|change| change.revision).collect();
+        let mut pushed: HashMap<&str, i64> = HashMap::new();
+        for (item, result) in pending.iter().zip(&results) {
+            if result.revision.is_some() || result.kind() == ChangeKind::Unchanged {
+                *pushed.entry(item.data_type()).or_default() += 1;
+            }
+        }
+        let items_applied = pushed.values().sum::<i64>() as i32;
+
+        for change in applied {
+            // An error only means nobody is watching right now
+            let _ = self.changes.send(change);
+        }
+        for result in results {
+            send(tx, ServerMessage::Result(result)).await?;
+        }
+
+        // Everything after the client's cursor except what it just sent us
+        let mut cursor = start.cursor;
+        let mut pulled: HashMap<&str, i64> = HashMap::new();
+        loop {
+            let batch = history::changes_since(&self.pool, cursor, SEND_BATCH_SIZE)
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+            let done = (batch.len() as i64) < SEND_BATCH_SIZE;
+
+            for change in batch {
+                cursor = change.revision;
+                let data_type = change.payload.data_type();
+                if own_revisions.contains(&change.revision)
+                    || (!start.data_types.is_empty() && !start.data_types.iter().any(|t| t == data_type))
+                {
+                    continue;
+                }
+                *pulled.entry(data_type).or_default() += 1;
+                send(tx, ServerMessage::Change(change_event_to_proto(change))).await?;
+            }
+            if done {
+                break;
+            }
+        }
+        let items_sent = pulled.values().sum::<i64>() as i32;
+
+        send(
+            tx,
+            ServerMessage::Complete(SyncComplete {
+                cursor,
+                items_applied,
+                items_sent,
+            }),
+        )
+        .await?;
+
+        for data_type in DATA_TYPES {
+            if !start.data_types.is_empty() && !start.data_types.iter().any(|t| t == data_type) {
+                continue;
+            }
+            let counts = [
+                ("push", pushed.get(data_type).copied().unwrap_or(0)),
+                ("pull", pulled.get(data_type).copied().unwrap_or(0)),
+            ];
+            for (sync_type, items_count) in counts {
+                record_sync_metadata(&self.pool, &client_id, sync_type, data_type, items_count)
+                    .await
+                    .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+            }
+        }
+
+        info!(
+            "Sync completed for client {}: {} applied, {} sent, cursor {}",
+            client_id, items_applied, items_sent, cursor
+        );
+        Ok(())
+    }
+
+    /// Applies every pending item in one transaction. Items changed on the server after
+    /// `cursor` are reported as conflicts and left for the client to pull.
+    async fn apply(
+        &self,
+        pending: &[PendingItem],
+        cursor: i64,
+    ) -> Result<(Vec<SyncItemResult>, Vec<ChangeRecord>), Status> {
+        let db_error = |e: sqlx::Error| Status::internal(format!("Database error: {}", e));
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let validator = BitValidator::load(&mut *tx, self.bit_policy)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+        let mut results = Vec::with_capacity(pending.len());
+        let mut applied = Vec::new();
+        let mut seen = HashSet::new();
+
+        for item in pending {
+            let mut result = SyncItemResult {
+                data_type: item.data_type().to_string(),
+                key: item.key(),
+                ..Default::default()
+            };
+
+            if !seen.insert((item.data_type(), result.key.clone())) {
+                result.set_kind(ChangeKind::Conflict);
+                result.set_error_code(PushErrorCode::DuplicateItem);
+                result.error = Some("item appears more than once in this session".to_string());
+            } else if history::changed_since(&mut tx, item.change_key(), cursor)
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?
+            {
+                result.set_kind(ChangeKind::Conflict);
+                result.set_error_code(PushErrorCode::StaleUpdate);
+                result.error = Some(format!("changed on the server after revision {}", cursor));
+            } else {
+                // Each item is written under its own savepoint, so a failure part way through
+                // an item rolls back only that item's writes.
+                let mut savepoint = tx.begin().await.map_err(db_error)?;
+                let checked = match item.check_bits(&validator) {
+                    Ok(warnings) => item
+                        .apply(&mut savepoint, &self.api_key.client_name)
+                        .await
+                        .map(|change| (change, warnings)),
+                    Err(e) => Err(e),
+                };
+                match &checked {
+                    Ok(_) => savepoint.commit().await.map_err(db_error)?,
+                    Err(_) => savepoint.rollback().await.map_err(db_error)?,
+                }
+                match checked {
+                    Ok((change, warnings)) => {
+                        result.set_kind(change.kind);
+                        result.revision = change.change.as_ref().map(|c| c.revision);
+                        result.warnings = warnings;
+                        applied.extend(change.change);
+                    }
+                    Err(e) => {
+                        let (code, field, _) = push_error(&e);
+                        result.set_kind(ChangeKind::Rejected);
+                        result.set_error_code(code);
+                        result.field = field;
+                        result.error = Some(e.to_string());
+                    }
+                }
+            }
+
+            results.push(result);
+        }
+
+        if pending
+            .iter()
+            .any(|item| matches!(item, PendingItem::RulePattern(_) | PendingItem::FeatureDefinition(_)))
+        {
+            ruleset::record_current(&mut tx)
+                .await
+                .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok((results, applied))
+    }
+}
+
+async fn send(tx: &ServerSender, message: ServerMessage) -> Result<(), Status> {
+    tx.send(Ok(SyncServerMessage { message: Some(message) }))
+        .await
+        .map_err(|_| Status::cancelled("Client closed the sync session"))
+}
+
+pub(crate) async fn record_sync_metadata(
+    pool: &SqlitePool,
+    client_id: &str,
+    sync_type: &str,
+    data_type: &str,
+    items_count: i64,
+) -> anyhow::Result<()> {
+    let synced_at = crate::timestamp::now();
+    sqlx::query!(
+        "INSERT INTO sync_metadata (client_id, sync_type, data_type, items_count, synced_at)
+         VALUES (?, ?, ?, ?, ?)",
+        client_id,
+        sync_type,
+        data_type,
+        items_count,
+        synced_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_pool() -> SqlitePool {
+        // One connection, since every in-memory connection is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    fn session(pool: &SqlitePool) -> SyncSession {
+        SyncSession {
+            pool: pool.clone(),
+            changes: broadcast::channel(16).0,
+            api_key: ApiKey {
+                key_hash: String::new(),
+                client_name: "client".to_string(),
+                permissions: "read_write".to_string(),
+                created_at: String::new(),
+                last_used_at: None,
+            },
+            bit_policy: BitPolicy::Off,
+        }
+    }
+
+    /// Runs a session that sends `items` after a start at `cursor`, returning what the server sent.
+    async fn sync(pool: &SqlitePool, cursor: i64, items: Vec<ClientMessage>) -> Result<Vec<ServerMessage>, Status> {
+        let start = ClientMessage::Start(SyncStart {
+            cursor,
+            ..Default::default()
+        });
+        let inbound = std::iter::once(start)
+            .chain(items)
+            .map(|message| Ok(SyncClientMessage { message: Some(message) }));
+        let (tx, mut rx) = mpsc::channel(1024);
+        session(pool).sync(tokio_stream::iter(inbound), &tx).await?;
+        drop(tx);
+
+        let mut sent = Vec::new();
+        while let Some(message) = rx.recv().await {
+            sent.extend(message.unwrap().message);
+        }
+        Ok(sent)
+    }
+
+    fn override_item(pronunciation: &str, bits: i64, seconds: i64) -> ClientMessage {
+        let at = prost_types::Timestamp { seconds, nanos: 0 };
+        ClientMessage::FeatureOverride(FeatureOverride {
+            pronunciation: pronunciation.to_string(),
+            fixed_bits1: bits,
+            created_at: Some(at),
+            updated_at: Some(at),
+            ..Default::default()
+        })
+    }
+
+    fn complete(sent: &[ServerMessage]) -> &SyncComplete {
+        match sent.last() {
+            Some(ServerMessage::Complete(complete)) => complete,
+            _ => panic!("session did not complete"),
+        }
+    }
+
+    #[tokio::test]
+    async fn update_keeps_created_at() {
+        let pool = test_pool().await;
+        let first = sync(&pool, 0, vec![override_item("ア", 1, 1_000)]).await.unwrap();
+        let cursor = complete(&first).cursor;
+        sync(&pool, cursor, vec![override_item("ア", 2, 2_000)]).await.unwrap();
+
+        let (fixed_bits1, created_at, updated_at): (i64, String, String) =
+            sqlx::query_as("SELECT fixed_bits1, created_at, updated_at FROM card_feature_override")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(fixed_bits1, 2);
+        assert_eq!(created_at, "1970-01-01T00:16:40.000Z");
+        assert_eq!(updated_at, "1970-01-01T00:33:20.000Z");
+    }
+
+    #[tokio::test]
+    async fn conflicting_item_is_reported_and_server_changes_are_sent_back() {
+        let pool = test_pool().await;
+        let first = sync(&pool, 0, vec![override_item("ア", 1, 1_000), override_item("イ", 1, 1_000)]).await.unwrap();
+        let cursor = complete(&first).cursor;
+        // Another client changes イ after our cursor
+        let other = sync(&pool, cursor, vec![override_item("イ", 5, 2_000)]).await.unwrap();
+        let other_revision = complete(&other).cursor;
+
+        let items = vec![override_item("イ", 7, 3_000), override_item("ウ", 1, 3_000)];
+        let sent = sync(&pool, cursor, items).await.unwrap();
+
+        let results: Vec<&SyncItemResult> = sent
+            .iter()
+            .filter_map(|message| match message {
+                ServerMessage::Result(result) => Some(result),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].key.as_str(), results[0].kind()), ("イ", ChangeKind::Conflict));
+        assert_eq!(results[0].error_code(), PushErrorCode::StaleUpdate);
+        assert_eq!(results[0].revision, None);
+        assert_eq!((results[1].key.as_str(), results[1].kind()), ("ウ", ChangeKind::Created));
+        let own_revision = results[1].revision.expect("ウ was written");
+
+        // The other client's change comes back; our own write does not
+        let changes: Vec<(i64, Option<&str>)> = sent
+            .iter()
+            .filter_map(|message| match message {
+                ServerMessage::Change(change) => Some((
+                    change.revision,
+                    match &change.payload {
+                        Some(change_event::Payload::FeatureOverride(item)) => Some(item.pronunciation.as_str()),
+                        _ => None,
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(changes, [(other_revision, Some("イ"))]);
+
+        let complete = complete(&sent);
+        assert_eq!((complete.cursor, complete.items_applied, complete.items_sent), (own_revision, 1, 1));
+
+        let bits: i64 = sqlx::query_scalar("SELECT fixed_bits1 FROM card_feature_override WHERE pronunciation = 'イ'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(bits, 5);
+
+        let counts: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT sync_type, items_count FROM sync_metadata
+             WHERE client_id = 'client' AND data_type = 'feature_override' ORDER BY rowid DESC LIMIT 2",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(counts, [("pull".to_string(), 1), ("push".to_string(), 1)]);
+    }
+
+    #[tokio::test]
+    async fn too_many_items_are_rejected() {
+        let pool = test_pool().await;
+        let items = (0..=MAX_SYNC_ITEMS).map(|i| override_item(&format!("ア{}", i), 1, 1_000)).collect();
+
+        let status = sync(&pool, 0, items).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        let written: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM card_feature_override")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(written, 0);
+    }
+}
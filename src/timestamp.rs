@@ -0,0 +1,56 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// Formats a time in the canonical storage form: UTC RFC3339 with milliseconds,
+/// e.g. `2025-01-31T12:34:56.789Z`. Every timestamp column holds this form, so
+/// comparing the text compares the times.
+pub fn format(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// The current time in the canonical storage form.
+pub fn now() -> String {
+    format(&Utc::now())
+}
+
+/// Parses a stored timestamp.
+pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn formats_utc_with_milliseconds() {
+        assert_eq!(format(&Utc.timestamp_opt(1000, 0).unwrap()), "1970-01-01T00:16:40.000Z");
+        assert_eq!(format(&Utc.timestamp_opt(1000, 123_456_789).unwrap()), "1970-01-01T00:16:40.123Z");
+    }
+
+    #[test]
+    fn parses_any_offset_into_the_canonical_form() {
+        let parsed = parse("2025-01-31T21:34:56.789+09:00").unwrap();
+        assert_eq!(format(&parsed), "2025-01-31T12:34:56.789Z");
+        assert_eq!(parse(&format(&parsed)), Some(parsed));
+        assert_eq!(format(&parse("2025-01-31T12:34:56Z").unwrap()), "2025-01-31T12:34:56.000Z");
+    }
+
+    #[test]
+    fn rejects_other_forms() {
+        // SQLite's datetime('now') form, which older rows were written in
+        assert_eq!(parse("2025-01-31 12:34:56"), None);
+        assert_eq!(parse("2025-01-31"), None);
+        assert_eq!(parse("2025-13-01T00:00:00Z"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn canonical_text_sorts_in_time_order() {
+        let times = [0, 999, 1000, 59_999, 60_000, 86_400_000]
+            .map(|millis| format(&Utc.timestamp_millis_opt(millis).unwrap()));
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}